@@ -0,0 +1,81 @@
+//! Pushes a million publishes through `MessageStore`, run with
+//! `cargo bench -p backend --bench store`
+
+use std::{env, hint::black_box, time::Instant};
+
+use backend::{
+    message::{Publish, QoS},
+    store::{MessageStore, StoreLimit, StoreOptions},
+};
+
+const MESSAGES: usize = 1_000_000;
+
+fn run(name: &str, options: StoreOptions, publishes: &[Publish]) {
+    let mut store = MessageStore::new(options);
+    let start = Instant::now();
+    for publish in publishes {
+        // sharing the payload, as the backend does with the frontend
+        store.push(black_box(publish.clone()));
+    }
+    store.flush();
+    let elapsed = start.elapsed();
+    println!(
+        "{:<22} {:>8.1} ms {:>7.0} ns/msg  kept {:>7}  {:>7.1} MB  evicted {:>7}",
+        name,
+        elapsed.as_secs_f64() * 1e3,
+        elapsed.as_nanos() as f64 / publishes.len() as f64,
+        store.len(),
+        store.bytes() as f64 / 1e6,
+        store.evicted(),
+    );
+}
+
+fn main() {
+    let payload = br#"{"temperature":21.5,"humidity":40,"battery":97}"#;
+    let publishes: Vec<Publish> = (0..MESSAGES)
+        .map(|i| {
+            Publish::new(
+                format!("sensors/{}/state", i % 1000),
+                QoS::AtMostOnce,
+                &payload[..],
+            )
+        })
+        .collect();
+
+    run(
+        "unbounded",
+        StoreOptions {
+            limit: StoreLimit::Count(usize::MAX),
+            ..Default::default()
+        },
+        &publishes,
+    );
+    run(
+        "count 100k",
+        StoreOptions {
+            limit: StoreLimit::Count(100_000),
+            ..Default::default()
+        },
+        &publishes,
+    );
+    run(
+        "bytes 16 MB",
+        StoreOptions {
+            limit: StoreLimit::Bytes(16_000_000),
+            ..Default::default()
+        },
+        &publishes,
+    );
+
+    let overflow = env::temp_dir().join("mqtt_v_store_bench.log");
+    let _ = std::fs::remove_file(&overflow);
+    run(
+        "count 100k + overflow",
+        StoreOptions {
+            limit: StoreLimit::Count(100_000),
+            overflow_path: overflow.to_string_lossy().into_owned(),
+        },
+        &publishes,
+    );
+    let _ = std::fs::remove_file(&overflow);
+}
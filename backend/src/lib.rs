@@ -1,6 +1,14 @@
-use message::{ToBackend, ToFrontend};
+use message::{ConnectionState, FromClient, MqttError, ToBackend, ToFrontend};
 pub mod message;
 pub mod mqtt_client;
+pub mod mqtt_client_v5;
+mod outbox;
+mod reconnect;
+pub mod record;
+pub mod store;
+pub mod tls;
+mod tracker;
+pub mod transport;
 use tokio::{
     runtime::Builder,
     sync::mpsc::{Receiver, Sender},
@@ -9,12 +17,21 @@ use tokio::{
 pub struct Backend {
     back_tx: Sender<ToFrontend>,
     front_rx: Receiver<ToBackend>,
-    //    egui_context: eframe::egui::Context,
+    /// woken after every message so the UI only repaints when there is news
+    egui_context: eframe::egui::Context,
 }
 
 impl Backend {
-    pub fn new(back_tx: Sender<ToFrontend>, front_rx: Receiver<ToBackend>) -> Self {
-        Self { back_tx, front_rx }
+    pub fn new(
+        back_tx: Sender<ToFrontend>,
+        front_rx: Receiver<ToBackend>,
+        egui_context: eframe::egui::Context,
+    ) -> Self {
+        Self {
+            back_tx,
+            front_rx,
+            egui_context,
+        }
     }
 
     pub fn init(&mut self) {
@@ -33,25 +50,86 @@ impl Backend {
                         let (incomming_tx, mut incomming_rx) = tokio::sync::mpsc::channel(100);
                         let (outgoing_tx, outgoing_rx) = tokio::sync::mpsc::channel(10);
                         let back_tx = self.back_tx.clone();
+                        let ctx = self.egui_context.clone();
                         rt.spawn(async move {
                             match opts {
                                 message::MqttOpts::V3(opt) => {
                                     let cli_id = opt.client_id.clone();
-                                    let _res = back_tx
-                                        .send(ToFrontend::ClientCreated(cli_id, outgoing_tx))
-                                        .await;
-                                    mqtt_client::new(incomming_tx, outgoing_rx, opt.convert())
-                                        .await;
+                                    let reconnect = opt.reconnect.clone();
+                                    match opt.convert() {
+                                        Ok(options) => {
+                                            let _res = back_tx
+                                                .send(ToFrontend::ClientCreated(
+                                                    cli_id,
+                                                    outgoing_tx,
+                                                ))
+                                                .await;
+                                            ctx.request_repaint();
+                                            mqtt_client::new(
+                                                incomming_tx,
+                                                outgoing_rx,
+                                                options,
+                                                reconnect,
+                                            )
+                                            .await;
+                                        }
+                                        Err(e) => {
+                                            let error = MqttError::Options(e.to_string());
+                                            let state = ConnectionState::Failed(error.clone());
+                                            let _ = incomming_tx
+                                                .send((cli_id.clone(), FromClient::Error(error)))
+                                                .await;
+                                            let _ = incomming_tx
+                                                .send((cli_id, FromClient::State(state)))
+                                                .await;
+                                        }
+                                    }
                                 }
 
-                                message::MqttOpts::V5(_v5) => {}
+                                message::MqttOpts::V5(opt) => {
+                                    let cli_id = opt.client_id.clone();
+                                    let reconnect = opt.reconnect.clone();
+                                    match opt.convert() {
+                                        Ok(options) => {
+                                            let _res = back_tx
+                                                .send(ToFrontend::ClientCreated(
+                                                    cli_id,
+                                                    outgoing_tx,
+                                                ))
+                                                .await;
+                                            ctx.request_repaint();
+                                            mqtt_client_v5::new(
+                                                incomming_tx,
+                                                outgoing_rx,
+                                                options,
+                                                reconnect,
+                                            )
+                                            .await;
+                                        }
+                                        Err(e) => {
+                                            let error = MqttError::Options(e.to_string());
+                                            let state = ConnectionState::Failed(error.clone());
+                                            let _ = incomming_tx
+                                                .send((cli_id.clone(), FromClient::Error(error)))
+                                                .await;
+                                            let _ = incomming_tx
+                                                .send((cli_id, FromClient::State(state)))
+                                                .await;
+                                        }
+                                    }
+                                }
                             }
                         });
                         let tx = self.back_tx.clone();
+                        let ctx = self.egui_context.clone();
 
                         rt.spawn(async move {
                             while let Some((client_id, client_msg)) = incomming_rx.recv().await {
-                                let _ = tx.try_send(ToFrontend::ClientMsg(client_id, client_msg));
+                                let msg = ToFrontend::ClientMsg(client_id, client_msg);
+                                if tx.send(msg).await.is_err() {
+                                    break;
+                                }
+                                ctx.request_repaint();
                             }
                         });
                     }
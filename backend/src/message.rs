@@ -1,10 +1,14 @@
-use std::time::Duration;
+use std::{fmt, io, time::Duration};
 
-use rumqttc::MqttOptions;
-pub use rumqttc::{ClientError, Event, Outgoing, Packet, Publish, QoS, Subscribe};
+use rumqttc::{v5, LastWill, MqttOptions};
+pub use rumqttc::{
+    ClientError, ConnAck, ConnectReturnCode, Event, Outgoing, Packet, Publish, QoS, Subscribe,
+};
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::Sender;
 
+use crate::transport;
+
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum MqttOpts {
@@ -13,10 +17,14 @@ pub enum MqttOpts {
 }
 
 #[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct OptionsV3 {
     pub client_id: String,
     pub broker_addr: String,
     pub port: u16,
+    pub transport: Transport,
+    pub tls: TlsOptions,
+    pub ws: WsOptions,
     pub keep_alive: bool,
     pub heatbbeat: u64,
     pub clean_session: bool,
@@ -24,11 +32,257 @@ pub struct OptionsV3 {
     pub credentials: bool,
     pub username: String,
     pub password: String,
+    pub last_will: bool,
+    pub will: WillOptions,
+    pub reconnect: ReconnectOptions,
 }
 
 #[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct OptionsV5 {
     pub client_id: String,
+    pub broker_addr: String,
+    pub port: u16,
+    pub transport: Transport,
+    pub tls: TlsOptions,
+    pub ws: WsOptions,
+    pub keep_alive: bool,
+    pub heatbbeat: u64,
+    pub clean_start: bool,
+    pub credentials: bool,
+    pub username: String,
+    pub password: String,
+    pub last_will: bool,
+    pub will: WillOptions,
+    pub reconnect: ReconnectOptions,
+    /// seconds the broker keeps the session after the network connection closes
+    pub session_expiry: Option<u32>,
+    pub receive_maximum: Option<u16>,
+    pub max_packet_size: Option<u32>,
+    pub topic_alias_max: Option<u16>,
+    pub user_properties: Vec<(String, String)>,
+}
+
+/// Stored as a string, RON can not read unit variants back from inside the
+/// internally tagged `MqttOpts`
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "&'static str", try_from = "String")]
+pub enum Transport {
+    #[default]
+    Tcp,
+    Tls,
+    Ws,
+    Wss,
+}
+
+impl From<Transport> for &'static str {
+    fn from(transport: Transport) -> Self {
+        match transport {
+            Transport::Tcp => "Tcp",
+            Transport::Tls => "Tls",
+            Transport::Ws => "Ws",
+            Transport::Wss => "Wss",
+        }
+    }
+}
+
+impl TryFrom<String> for Transport {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        match name.as_str() {
+            "Tcp" => Ok(Transport::Tcp),
+            "Tls" => Ok(Transport::Tls),
+            "Ws" => Ok(Transport::Ws),
+            "Wss" => Ok(Transport::Wss),
+            _ => Err(format!("unknown transport {:?}", name)),
+        }
+    }
+}
+
+impl Transport {
+    pub fn is_tls(&self) -> bool {
+        matches!(self, Transport::Tls | Transport::Wss)
+    }
+
+    pub fn is_websocket(&self) -> bool {
+        matches!(self, Transport::Ws | Transport::Wss)
+    }
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsOptions {
+    /// PEM bundle of trusted CAs, the platform roots are used when empty
+    pub ca_path: String,
+    pub client_auth: bool,
+    pub client_cert_path: String,
+    pub client_key_path: String,
+    pub alpn: Vec<String>,
+    /// skip server certificate verification, for lab brokers with self-signed certs
+    pub accept_invalid_certs: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WsOptions {
+    pub path: String,
+    /// extra headers sent with the websocket upgrade request
+    pub headers: Vec<(String, String)>,
+}
+
+impl Default for WsOptions {
+    fn default() -> Self {
+        Self {
+            path: "/mqtt".to_owned(),
+            headers: vec![],
+        }
+    }
+}
+
+/// Message the broker publishes on behalf of the client when it goes away uncleanly
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WillOptions {
+    pub topic: String,
+    pub payload: String,
+    #[serde(with = "qos_serde")]
+    pub qos: QoS,
+    pub retain: bool,
+}
+
+impl Default for WillOptions {
+    fn default() -> Self {
+        Self {
+            topic: String::new(),
+            payload: String::new(),
+            qos: QoS::AtMostOnce,
+            retain: false,
+        }
+    }
+}
+
+impl WillOptions {
+    /// Whether `publish` is this will as the broker delivers it
+    pub fn matches(&self, publish: &Publish) -> bool {
+        self.topic == publish.topic && self.payload.as_bytes() == publish.payload
+    }
+
+    fn validate(&self) -> io::Result<()> {
+        if self.topic.is_empty() || self.topic.contains(['+', '#']) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid last will topic {:?}", self.topic),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Stored as a string for the same reason as `Transport`
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "&'static str", try_from = "String")]
+pub enum ReconnectPolicy {
+    Off,
+    Fixed,
+    #[default]
+    Exponential,
+}
+
+impl From<ReconnectPolicy> for &'static str {
+    fn from(policy: ReconnectPolicy) -> Self {
+        match policy {
+            ReconnectPolicy::Off => "Off",
+            ReconnectPolicy::Fixed => "Fixed",
+            ReconnectPolicy::Exponential => "Exponential",
+        }
+    }
+}
+
+impl TryFrom<String> for ReconnectPolicy {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        match name.as_str() {
+            "Off" => Ok(ReconnectPolicy::Off),
+            "Fixed" => Ok(ReconnectPolicy::Fixed),
+            "Exponential" => Ok(ReconnectPolicy::Exponential),
+            _ => Err(format!("unknown reconnect policy {:?}", name)),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectOptions {
+    pub policy: ReconnectPolicy,
+    /// delay between attempts, the first delay when backing off
+    pub interval_ms: u64,
+    /// upper bound of the exponential delay
+    pub max_interval_ms: u64,
+    /// randomize each delay between half and the full value
+    pub jitter: bool,
+    /// failed attempts in a row before giving up, 0 retries forever
+    pub max_attempts: u32,
+}
+
+impl Default for ReconnectOptions {
+    fn default() -> Self {
+        Self {
+            policy: ReconnectPolicy::Exponential,
+            interval_ms: 1000,
+            max_interval_ms: 60_000,
+            jitter: true,
+            max_attempts: 0,
+        }
+    }
+}
+
+impl ReconnectOptions {
+    /// Delay before reconnect attempt number `attempt` (starting at 1),
+    /// `None` once the policy gives up
+    pub fn delay(&self, attempt: u32) -> Option<Duration> {
+        if self.max_attempts != 0 && attempt > self.max_attempts {
+            return None;
+        }
+        let millis = match self.policy {
+            ReconnectPolicy::Off => return None,
+            ReconnectPolicy::Fixed => self.interval_ms,
+            ReconnectPolicy::Exponential => self
+                .interval_ms
+                .saturating_mul(1 << (attempt.saturating_sub(1)).min(32))
+                .min(self.max_interval_ms.max(self.interval_ms)),
+        };
+        let millis = if self.jitter {
+            millis / 2 + random() % (millis / 2 + 1)
+        } else {
+            millis
+        };
+        Some(Duration::from_millis(millis))
+    }
+}
+
+/// Cheap randomness for the jitter, not worth a dependency
+fn random() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish()
+}
+
+/// Stores `QoS` as its numeric level
+pub mod qos_serde {
+    use rumqttc::QoS;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(qos: &QoS, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*qos as u8)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<QoS, D::Error> {
+        let level = u8::deserialize(deserializer)?;
+        rumqttc::qos(level).map_err(serde::de::Error::custom)
+    }
 }
 
 impl Default for MqttOpts {
@@ -37,6 +291,9 @@ impl Default for MqttOpts {
             client_id: "mosquitto".to_string(),
             broker_addr: "test.mosquitto.org".to_string(),
             port: 1883,
+            transport: Transport::Tcp,
+            tls: TlsOptions::default(),
+            ws: WsOptions::default(),
             keep_alive: true,
             heatbbeat: 20,
             clean_session: false,
@@ -44,6 +301,9 @@ impl Default for MqttOpts {
             credentials: false,
             username: "".to_owned(),
             password: "".to_owned(),
+            last_will: false,
+            will: WillOptions::default(),
+            reconnect: ReconnectOptions::default(),
         })
     }
 }
@@ -55,24 +315,145 @@ impl MqttOpts {
             MqttOpts::V5(v5) => v5.client_id.clone(),
         }
     }
+
+    /// The will configured for this profile, if enabled
+    pub fn last_will(&self) -> Option<&WillOptions> {
+        match self {
+            MqttOpts::V3(v3) => v3.last_will.then_some(&v3.will),
+            MqttOpts::V5(v5) => v5.last_will.then_some(&v5.will),
+        }
+    }
+}
+
+impl From<OptionsV3> for OptionsV5 {
+    fn from(v3: OptionsV3) -> Self {
+        OptionsV5 {
+            client_id: v3.client_id,
+            broker_addr: v3.broker_addr,
+            port: v3.port,
+            transport: v3.transport,
+            tls: v3.tls,
+            ws: v3.ws,
+            keep_alive: v3.keep_alive,
+            heatbbeat: v3.heatbbeat,
+            clean_start: v3.clean_session,
+            credentials: v3.credentials,
+            username: v3.username,
+            password: v3.password,
+            last_will: v3.last_will,
+            will: v3.will,
+            reconnect: v3.reconnect,
+            ..OptionsV5::default()
+        }
+    }
+}
+
+impl From<OptionsV5> for OptionsV3 {
+    fn from(v5: OptionsV5) -> Self {
+        OptionsV3 {
+            client_id: v5.client_id,
+            broker_addr: v5.broker_addr,
+            port: v5.port,
+            transport: v5.transport,
+            tls: v5.tls,
+            ws: v5.ws,
+            keep_alive: v5.keep_alive,
+            heatbbeat: v5.heatbbeat,
+            clean_session: v5.clean_start,
+            max_packet_size: (u16::MAX, u16::MAX),
+            credentials: v5.credentials,
+            username: v5.username,
+            password: v5.password,
+            last_will: v5.last_will,
+            will: v5.will,
+            reconnect: v5.reconnect,
+        }
+    }
 }
 
 impl OptionsV3 {
-    pub fn convert(self) -> MqttOptions {
-        let mut opts = MqttOptions::new(self.client_id, self.broker_addr, self.port);
+    pub fn convert(self) -> io::Result<MqttOptions> {
+        let broker_addr =
+            transport::broker_addr(self.transport, &self.broker_addr, self.port, &self.ws);
+        let mut opts = MqttOptions::new(self.client_id, broker_addr, self.port);
+        opts.set_transport(transport::transport(self.transport, &self.tls)?);
+        if self.transport.is_websocket() {
+            let headers = transport::headers(&self.ws.headers)?;
+            opts.set_request_modifier(move |request| {
+                transport::with_headers(request, headers.clone())
+            });
+        }
         opts.set_max_packet_size(self.max_packet_size.0.into(), self.max_packet_size.1.into());
         if self.credentials {
             opts.set_credentials(self.username, self.password);
         }
+        if self.last_will {
+            self.will.validate()?;
+            let will = &self.will;
+            opts.set_last_will(LastWill::new(
+                &will.topic,
+                will.payload.as_bytes(),
+                will.qos,
+                will.retain,
+            ));
+        }
 
         if self.keep_alive {
             opts.set_keep_alive(Duration::from_secs(self.heatbbeat));
             opts.set_clean_session(self.clean_session);
         }
-        opts
+        Ok(opts)
+    }
+}
+
+impl OptionsV5 {
+    pub fn convert(self) -> io::Result<v5::MqttOptions> {
+        let broker_addr =
+            transport::broker_addr(self.transport, &self.broker_addr, self.port, &self.ws);
+        let mut opts = v5::MqttOptions::new(self.client_id, broker_addr, self.port);
+        opts.set_transport(transport::transport(self.transport, &self.tls)?);
+        if self.transport.is_websocket() {
+            let headers = transport::headers(&self.ws.headers)?;
+            opts.set_request_modifier(move |request| {
+                transport::with_headers(request, headers.clone())
+            });
+        }
+        if self.credentials {
+            opts.set_credentials(self.username, self.password);
+        }
+        if self.last_will {
+            self.will.validate()?;
+            let will = &self.will;
+            opts.set_last_will(v5::mqttbytes::v5::LastWill::new(
+                &will.topic,
+                will.payload.as_bytes(),
+                crate::mqtt_client_v5::qos_to_v5(will.qos),
+                will.retain,
+                None,
+            ));
+        }
+
+        if self.keep_alive {
+            opts.set_keep_alive(Duration::from_secs(self.heatbbeat));
+        }
+        opts.set_clean_start(self.clean_start);
+
+        let mut props = v5::mqttbytes::v5::ConnectProperties::new();
+        props.session_expiry_interval = self.session_expiry;
+        props.receive_maximum = self.receive_maximum;
+        props.max_packet_size = self.max_packet_size;
+        props.topic_alias_max = self.topic_alias_max;
+        props.user_properties = self
+            .user_properties
+            .into_iter()
+            .filter(|(k, _)| !k.is_empty())
+            .collect();
+        opts.set_connect_properties(props);
+        Ok(opts)
     }
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum ToBackend {
     Startup,
     Shutdown,
@@ -83,13 +464,178 @@ pub enum ToBackend {
 pub type ClientId = String;
 pub type Topic = String;
 
-pub type PublishRef = String;
+pub type PublishRef = uuid::Uuid;
 
 #[derive(Debug)]
 pub enum FromClient {
-    Disconnected,
+    State(ConnectionState),
     Event(Event),
     PublishReslt(PublishRef, Result<(), ClientError>),
+    Delivery(PublishRef, Delivery),
+    Subscription(Topic, SubscriptionStatus),
+    /// the UNSUBSCRIBE could not be queued, the subscription is kept
+    UnsubscribeFailed(Topic, MqttError),
+    Reconnect(ReconnectStatus),
+    Error(MqttError),
+    /// this many events were dropped while the frontend was behind
+    Dropped(u64),
+}
+
+/// Progress of the reconnect policy, `attempt` counts from the last lost connection,
+/// each failed attempt is reported as a `FromClient::Error` first
+#[derive(Debug, Clone)]
+pub enum ReconnectStatus {
+    /// waiting before the next attempt
+    Waiting { attempt: u32, delay: Duration },
+    /// the attempt got a ConnAck
+    Succeeded { attempt: u32 },
+    /// the policy is exhausted and the client stopped
+    GaveUp { attempts: u32 },
+}
+
+/// Where an outgoing publish is on its way to the broker
+#[derive(Debug, Clone)]
+pub enum Delivery {
+    /// handed to the client, waiting for its turn on the wire
+    Queued,
+    /// written with this packet id, QoS 0 publishes stop here
+    Sent(u16),
+    /// QoS 2 PubRec, waiting for PubComp
+    Received,
+    /// PubAck or PubComp arrived this long after sending
+    Acked(Duration),
+    Failed(String),
+}
+
+/// Where a subscription is between the request and the broker's answer
+#[derive(Debug, Clone, Default)]
+pub enum SubscriptionStatus {
+    /// known to the profile but not requested in this session
+    #[default]
+    Inactive,
+    /// asked for, not on the wire yet
+    Requested,
+    /// SUBSCRIBE sent with this packet id
+    Pending(u16),
+    Granted(QoS),
+    Refused(String),
+    /// UNSUBSCRIBE requested, with its packet id once sent
+    Unsubscribing(Option<u16>),
+    Unsubscribed,
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum ConnectionState {
+    #[default]
+    Idle,
+    Connecting,
+    Connected,
+    /// waiting for or running this reconnect attempt
+    Reconnecting(u32),
+    Disconnecting,
+    Failed(MqttError),
+}
+
+impl ConnectionState {
+    /// A connection exists or is being established
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            ConnectionState::Connecting
+                | ConnectionState::Connected
+                | ConnectionState::Reconnecting(_)
+        )
+    }
+}
+
+/// Why a client could not connect or lost its connection
+#[derive(Debug, Clone)]
+pub enum MqttError {
+    /// the profile could not be turned into client options
+    Options(String),
+    Io(io::ErrorKind, String),
+    Tls(String),
+    Websocket(String),
+    /// the broker answered CONNECT with this return code
+    Refused(String),
+    /// no network activity or ping response in time
+    Timeout,
+    Protocol(String),
+    /// a request could not be handed to the client, its queue is full or closed
+    Request(String),
+}
+
+impl MqttError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MqttError::Options(_) => "options",
+            MqttError::Io(..) => "I/O",
+            MqttError::Tls(_) => "TLS",
+            MqttError::Websocket(_) => "websocket",
+            MqttError::Refused(_) => "refused",
+            MqttError::Timeout => "timeout",
+            MqttError::Protocol(_) => "protocol",
+            MqttError::Request(_) => "request",
+        }
+    }
+}
+
+impl fmt::Display for MqttError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MqttError::Io(_, msg)
+            | MqttError::Options(msg)
+            | MqttError::Tls(msg)
+            | MqttError::Websocket(msg)
+            | MqttError::Protocol(msg)
+            | MqttError::Request(msg) => write!(f, "{}: {}", self.kind(), msg),
+            MqttError::Refused(code) => write!(f, "connection refused: {}", code),
+            MqttError::Timeout => f.write_str("timeout"),
+        }
+    }
+}
+
+impl From<io::Error> for MqttError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::TimedOut {
+            return MqttError::Timeout;
+        }
+        MqttError::Io(e.kind(), e.to_string())
+    }
+}
+
+impl From<rumqttc::ConnectionError> for MqttError {
+    fn from(e: rumqttc::ConnectionError) -> Self {
+        use rumqttc::{ConnectionError as E, StateError};
+        match e {
+            E::MqttState(StateError::Io(e)) | E::Io(e) => e.into(),
+            E::MqttState(StateError::AwaitPingResp) | E::NetworkTimeout | E::FlushTimeout => {
+                MqttError::Timeout
+            }
+            E::Tls(e) => MqttError::Tls(e.to_string()),
+            E::ConnectionRefused(code) => MqttError::Refused(format!("{:?}", code)),
+            E::Websocket(_) | E::WsConnect(_) | E::InvalidUrl(_) | E::ResponseValidation(_) => {
+                MqttError::Websocket(e.to_string())
+            }
+            e => MqttError::Protocol(e.to_string()),
+        }
+    }
+}
+
+impl From<v5::ConnectionError> for MqttError {
+    fn from(e: v5::ConnectionError) -> Self {
+        use v5::{ConnectionError as E, StateError};
+        match e {
+            E::MqttState(StateError::Io(e)) | E::Io(e) => e.into(),
+            E::MqttState(StateError::AwaitPingResp) | E::Timeout(_) => MqttError::Timeout,
+            E::Tls(e) => MqttError::Tls(e.to_string()),
+            E::ConnectionRefused(code) => MqttError::Refused(format!("{:?}", code)),
+            E::Websocket(_) | E::WsConnect(_) | E::InvalidUrl(_) | E::ResponseValidation(_) => {
+                MqttError::Websocket(e.to_string())
+            }
+            e => MqttError::Protocol(e.to_string()),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -1,91 +1,181 @@
-use rumqttc::{AsyncClient, Event, MqttOptions, Packet, Publish};
-use tokio::sync::mpsc::{Receiver, Sender};
+use rumqttc::{AsyncClient, ClientError, MqttOptions, Publish, QoS};
+use tokio::sync::{
+    mpsc::{Receiver, Sender},
+    oneshot,
+};
 
-use crate::message::{ClientId, FromClient, ToClient};
+use crate::{
+    message::{
+        ClientId, ConnectionState, FromClient, MqttError, ReconnectOptions, SubscriptionStatus,
+        ToClient, Topic,
+    },
+    outbox::Outbox,
+    reconnect::Reconnector,
+    tracker::{Queued, RequestTracker},
+};
 
-pub async fn new(
+/// The request side of a connection, implemented for both the v3 and v5 clients
+/// so they can share the thread that serves `ToClient` messages
+pub(crate) trait RequestHandle: Send + 'static {
+    fn try_publish(&self, publish: Publish) -> Result<(), ClientError>;
+    fn try_subscribe(&self, topic: Topic, qos: QoS) -> Result<(), ClientError>;
+    fn try_unsubscribe(&self, topic: Topic) -> Result<(), ClientError>;
+    fn try_disconnect(&self) -> Result<(), ClientError>;
+}
+
+impl RequestHandle for AsyncClient {
+    fn try_publish(&self, publish: Publish) -> Result<(), ClientError> {
+        let Publish {
+            qos,
+            retain,
+            topic,
+            payload,
+            dup: _,
+            pkid: _,
+        } = publish;
+        AsyncClient::try_publish(self, topic, qos, retain, payload)
+    }
+
+    fn try_subscribe(&self, topic: Topic, qos: QoS) -> Result<(), ClientError> {
+        AsyncClient::try_subscribe(self, topic, qos)
+    }
+
+    fn try_unsubscribe(&self, topic: Topic) -> Result<(), ClientError> {
+        AsyncClient::try_unsubscribe(self, topic)
+    }
+
+    fn try_disconnect(&self) -> Result<(), ClientError> {
+        AsyncClient::try_disconnect(self)
+    }
+}
+
+/// Serves `ToClient` messages on a dedicated thread, the returned receiver
+/// resolves once the client was asked to disconnect or the channel is closed
+pub(crate) fn spawn_requests(
+    client_id: ClientId,
+    client: impl RequestHandle,
     sender: Sender<(ClientId, FromClient)>,
     mut receiver: Receiver<ToClient>,
-    mqttoptions: MqttOptions,
-) {
-    let client_id = mqttoptions.client_id();
-    let client_id2 = mqttoptions.client_id();
-    let sender2 = sender.clone();
-
-    let (client, mut eventloop) = AsyncClient::new(mqttoptions, 100);
-    let (tx, mut rx) = tokio::sync::oneshot::channel();
-    let client_tx = client.clone();
+    queued: Queued,
+) -> oneshot::Receiver<()> {
+    let (tx, rx) = oneshot::channel();
 
     std::thread::spawn(move || {
         while let Some(msg) = receiver.blocking_recv() {
             match msg {
                 ToClient::Publish(pkg_id, publish) => {
-                    let Publish {
-                        qos,
-                        retain,
-                        topic,
-                        payload,
-                        dup: _,
-                        pkid: _,
-                    } = publish;
-                    let result = client_tx.try_publish(topic, qos, retain, payload);
-                    let _ = sender
-                        .try_send((client_id.clone(), FromClient::PublishReslt(pkg_id, result)));
+                    // queue first, the event loop may send it before try_publish returns
+                    queued.lock().unwrap().publishes.push_back(pkg_id);
+                    let result = client.try_publish(publish);
+                    if result.is_err() {
+                        queued.lock().unwrap().publishes.pop_back();
+                    }
+                    let _ = sender.blocking_send((
+                        client_id.clone(),
+                        FromClient::PublishReslt(pkg_id, result),
+                    ));
                 }
                 ToClient::Subscribe((topic, qos)) => {
-                    let _ = client_tx.try_subscribe(topic, qos);
+                    queued.lock().unwrap().subscribes.push_back(topic.clone());
+                    if let Err(e) = client.try_subscribe(topic.clone(), qos) {
+                        queued.lock().unwrap().subscribes.pop_back();
+                        let status = SubscriptionStatus::Refused(e.to_string());
+                        let _ = sender.blocking_send((
+                            client_id.clone(),
+                            FromClient::Subscription(topic, status),
+                        ));
+                    }
                 }
                 ToClient::Connect => {}
-                ToClient::Disconnect => {
-                    if client_tx.try_disconnect().is_ok() {
+                ToClient::Disconnect => match client.try_disconnect() {
+                    Ok(()) => {
+                        let _ = sender.blocking_send((
+                            client_id.clone(),
+                            FromClient::State(ConnectionState::Disconnecting),
+                        ));
                         break;
                     }
-                }
+                    // still connected, the user can try again
+                    Err(e) => {
+                        let _ = sender.blocking_send((
+                            client_id.clone(),
+                            FromClient::Error(MqttError::Request(e.to_string())),
+                        ));
+                    }
+                },
                 ToClient::Unsubscribe(topic) => {
-                    let _ = client_tx.try_unsubscribe(topic);
+                    queued.lock().unwrap().unsubscribes.push_back(topic.clone());
+                    if let Err(e) = client.try_unsubscribe(topic.clone()) {
+                        queued.lock().unwrap().unsubscribes.pop_back();
+                        let error = MqttError::Request(format!("unsubscribe {}: {}", topic, e));
+                        let _ = sender.blocking_send((
+                            client_id.clone(),
+                            FromClient::UnsubscribeFailed(topic, error),
+                        ));
+                    }
                 }
             }
         }
 
-        tx.send(()).unwrap();
+        let _ = tx.send(());
     });
 
-    loop {
-        tokio::select! {
-            _ = (&mut rx) =>{
-            let    disconnect_msg =  (client_id2.clone(), FromClient::Disconnected);
-                let _ = sender2.try_send(disconnect_msg);
-                break;
-         },
-          msg = eventloop.poll()=>{
-            match msg {
+    rx
+}
+
+pub async fn new(
+    sender: Sender<(ClientId, FromClient)>,
+    receiver: Receiver<ToClient>,
+    mqttoptions: MqttOptions,
+    reconnect: ReconnectOptions,
+) {
+    let client_id = mqttoptions.client_id();
+
+    let (client, mut eventloop) = AsyncClient::new(mqttoptions, 100);
+    let outbox = Outbox::new(client_id.clone(), sender.clone());
+    let mut tracker = RequestTracker::new(outbox.clone());
+    let mut rx = spawn_requests(
+        client_id.clone(),
+        client,
+        sender.clone(),
+        receiver,
+        tracker.queued(),
+    );
+    let mut reconnector = Reconnector::new(reconnect, outbox.clone());
+    reconnector.set_state(ConnectionState::Connecting);
+
+    let stopped = loop {
+        let delay = tokio::select! {
+            biased;
+            _ = (&mut rx) => break true,
+            msg = eventloop.poll() => match msg {
                 Ok(notification) => {
-                    let event_clone = notification.clone();
-                    let event_msg = (client_id2.clone(), FromClient::Event(event_clone));
-                    let _ = sender2.try_send(event_msg);
-                    match notification {
-                        Event::Incoming(packet) => {
-                            //  println!("Incoming  {:?}", packet);
-                            match packet {
-                                Packet::ConnAck(_) => {
-                                    //  println!("ConnAck  {:?}", packet);
-                                }
-                                _ => {
-                                    // println!("inComming  {:?}", packet);
-                                }
-                            }
-                        }
-                        Event::Outgoing(_p) => {
-                            //  println!("Outgoing  {:?}", p);
-                        }
-                    }
-                }
-                Err(e) => {
-                    let _ = e;
-                    //  println!("{:}",e)
+                    let event = notification;
+                    reconnector.on_event(&event);
+                    tracker.on_event(&event);
+                    outbox.event(event);
+                    continue;
                 }
+                Err(e) => reconnector.on_error(e),
+            },
+            _ = outbox.room() => {
+                outbox.flush();
+                continue;
             }
-          }
         };
+        match delay {
+            Some(delay) => tokio::select! {
+                biased;
+                _ = (&mut rx) => break true,
+                _ = tokio::time::sleep(delay) => {}
+            },
+            None => break false,
+        }
+    };
+    tracker.abandon();
+    // a client that gave up already reported `Failed`
+    if stopped {
+        reconnector.set_state(ConnectionState::Idle);
     }
+    outbox.drain().await;
 }
@@ -0,0 +1,314 @@
+use rumqttc::{
+    v5::{
+        self,
+        mqttbytes::{v5 as packet, QoS as QoSV5},
+        AsyncClient,
+    },
+    ClientError, ConnAck, ConnectReturnCode, Event, Packet, Protocol, PubAck, PubComp, PubRec,
+    PubRel, Publish, QoS, Request, SubAck, Subscribe, SubscribeFilter, SubscribeReasonCode,
+    UnsubAck, Unsubscribe,
+};
+use tokio::sync::mpsc::{Receiver, Sender};
+
+use crate::{
+    message::{ClientId, ConnectionState, FromClient, ReconnectOptions, ToClient, Topic},
+    mqtt_client::{spawn_requests, RequestHandle},
+    outbox::Outbox,
+    reconnect::Reconnector,
+    tracker::RequestTracker,
+};
+
+impl RequestHandle for AsyncClient {
+    fn try_publish(&self, publish: Publish) -> Result<(), ClientError> {
+        AsyncClient::try_publish(
+            self,
+            publish.topic.clone(),
+            qos_to_v5(publish.qos),
+            publish.retain,
+            publish.payload.clone(),
+        )
+        .map_err(|_| ClientError::TryRequest(Request::Publish(publish)))
+    }
+
+    fn try_subscribe(&self, topic: Topic, qos: QoS) -> Result<(), ClientError> {
+        AsyncClient::try_subscribe(self, topic.clone(), qos_to_v5(qos))
+            .map_err(|_| ClientError::TryRequest(Request::Subscribe(Subscribe::new(topic, qos))))
+    }
+
+    fn try_unsubscribe(&self, topic: Topic) -> Result<(), ClientError> {
+        AsyncClient::try_unsubscribe(self, topic.clone())
+            .map_err(|_| ClientError::TryRequest(Request::Unsubscribe(Unsubscribe::new(topic))))
+    }
+
+    fn try_disconnect(&self) -> Result<(), ClientError> {
+        AsyncClient::try_disconnect(self)
+            .map_err(|_| ClientError::TryRequest(Request::Disconnect(rumqttc::Disconnect)))
+    }
+}
+
+pub async fn new(
+    sender: Sender<(ClientId, FromClient)>,
+    receiver: Receiver<ToClient>,
+    mqttoptions: v5::MqttOptions,
+    reconnect: ReconnectOptions,
+) {
+    let client_id = mqttoptions.client_id();
+
+    let (client, mut eventloop) = AsyncClient::new(mqttoptions, 100);
+    let outbox = Outbox::new(client_id.clone(), sender.clone());
+    let mut tracker = RequestTracker::new(outbox.clone());
+    let mut rx = spawn_requests(
+        client_id.clone(),
+        client,
+        sender.clone(),
+        receiver,
+        tracker.queued(),
+    );
+    let mut reconnector = Reconnector::new(reconnect, outbox.clone());
+    reconnector.set_state(ConnectionState::Connecting);
+
+    let stopped = loop {
+        let delay = tokio::select! {
+            biased;
+            _ = (&mut rx) => break true,
+            msg = eventloop.poll() => match msg {
+                Ok(notification) => {
+                    match refusal(&notification) {
+                        Some(Refusal::Publish(pkid, reason)) => {
+                            tracker.publish_refused(pkid, reason)
+                        }
+                        Some(Refusal::Subscribe(pkid, reason)) => {
+                            tracker.subscribe_refused(pkid, reason)
+                        }
+                        None => {}
+                    }
+                    let event = into_event(notification);
+                    reconnector.on_event(&event);
+                    tracker.on_event(&event);
+                    outbox.event(event);
+                    continue;
+                }
+                Err(e) => reconnector.on_error(e),
+            },
+            _ = outbox.room() => {
+                outbox.flush();
+                continue;
+            }
+        };
+        match delay {
+            Some(delay) => tokio::select! {
+                biased;
+                _ = (&mut rx) => break true,
+                _ = tokio::time::sleep(delay) => {}
+            },
+            None => break false,
+        }
+    };
+    tracker.abandon();
+    // a client that gave up already reported `Failed`
+    if stopped {
+        reconnector.set_state(ConnectionState::Idle);
+    }
+    outbox.drain().await;
+}
+
+/// Reason codes of a refused request, which v3 acks cannot express
+enum Refusal {
+    Publish(u16, String),
+    Subscribe(u16, String),
+}
+
+fn refusal(event: &v5::Event) -> Option<Refusal> {
+    use packet::{PubAckReason, PubRecReason};
+    let v5::Event::Incoming(packet) = event else {
+        return None;
+    };
+    match packet {
+        packet::Packet::PubAck(ack)
+            if !matches!(
+                ack.reason,
+                PubAckReason::Success | PubAckReason::NoMatchingSubscribers
+            ) =>
+        {
+            Some(Refusal::Publish(ack.pkid, format!("{:?}", ack.reason)))
+        }
+        packet::Packet::PubRec(rec)
+            if !matches!(
+                rec.reason,
+                PubRecReason::Success | PubRecReason::NoMatchingSubscribers
+            ) =>
+        {
+            Some(Refusal::Publish(rec.pkid, format!("{:?}", rec.reason)))
+        }
+        packet::Packet::SubAck(ack) => ack.return_codes.iter().find_map(|code| match code {
+            packet::SubscribeReasonCode::Success(_) => None,
+            code => Some(Refusal::Subscribe(ack.pkid, format!("{:?}", code))),
+        }),
+        _ => None,
+    }
+}
+
+/// Maps a v5 event onto the v3 `Event` the frontend renders,
+/// v5 properties and reason codes without a v3 equivalent are dropped
+pub fn into_event(event: v5::Event) -> Event {
+    match event {
+        v5::Event::Incoming(packet) => Event::Incoming(into_packet(packet)),
+        v5::Event::Outgoing(outgoing) => Event::Outgoing(outgoing),
+    }
+}
+
+fn into_packet(packet: packet::Packet) -> Packet {
+    match packet {
+        packet::Packet::Connect(connect, last_will, login) => Packet::Connect(rumqttc::Connect {
+            protocol: Protocol::V5,
+            keep_alive: connect.keep_alive,
+            client_id: connect.client_id,
+            clean_session: connect.clean_start,
+            last_will: last_will.map(|will| rumqttc::LastWill {
+                topic: String::from_utf8_lossy(&will.topic).into_owned(),
+                message: will.message,
+                qos: qos_from_v5(will.qos),
+                retain: will.retain,
+            }),
+            login: login.map(|login| rumqttc::Login {
+                username: login.username,
+                password: login.password,
+            }),
+        }),
+        packet::Packet::ConnAck(ack) => Packet::ConnAck(ConnAck {
+            session_present: ack.session_present,
+            code: connect_code(ack.code),
+        }),
+        packet::Packet::Publish(p) => Packet::Publish(Publish {
+            dup: p.dup,
+            qos: qos_from_v5(p.qos),
+            retain: p.retain,
+            topic: String::from_utf8_lossy(&p.topic).into_owned(),
+            pkid: p.pkid,
+            payload: p.payload,
+        }),
+        packet::Packet::PubAck(ack) => Packet::PubAck(PubAck { pkid: ack.pkid }),
+        packet::Packet::PubRec(rec) => Packet::PubRec(PubRec { pkid: rec.pkid }),
+        packet::Packet::PubRel(rel) => Packet::PubRel(PubRel { pkid: rel.pkid }),
+        packet::Packet::PubComp(comp) => Packet::PubComp(PubComp { pkid: comp.pkid }),
+        packet::Packet::PingReq(_) => Packet::PingReq,
+        packet::Packet::PingResp(_) => Packet::PingResp,
+        packet::Packet::Subscribe(sub) => Packet::Subscribe(Subscribe {
+            pkid: sub.pkid,
+            filters: sub
+                .filters
+                .into_iter()
+                .map(|f| SubscribeFilter::new(f.path, qos_from_v5(f.qos)))
+                .collect(),
+        }),
+        packet::Packet::SubAck(ack) => Packet::SubAck(SubAck {
+            pkid: ack.pkid,
+            return_codes: ack
+                .return_codes
+                .into_iter()
+                .map(|code| match code {
+                    packet::SubscribeReasonCode::Success(qos) => {
+                        SubscribeReasonCode::Success(qos_from_v5(qos))
+                    }
+                    _ => SubscribeReasonCode::Failure,
+                })
+                .collect(),
+        }),
+        packet::Packet::Unsubscribe(unsub) => Packet::Unsubscribe(Unsubscribe {
+            pkid: unsub.pkid,
+            topics: unsub.filters,
+        }),
+        packet::Packet::UnsubAck(ack) => Packet::UnsubAck(UnsubAck { pkid: ack.pkid }),
+        packet::Packet::Disconnect(_) => Packet::Disconnect,
+    }
+}
+
+fn connect_code(code: packet::ConnectReturnCode) -> ConnectReturnCode {
+    use packet::ConnectReturnCode as V5;
+    match code {
+        V5::Success => ConnectReturnCode::Success,
+        V5::RefusedProtocolVersion | V5::UnsupportedProtocolVersion => {
+            ConnectReturnCode::RefusedProtocolVersion
+        }
+        V5::BadClientId | V5::ClientIdentifierNotValid => ConnectReturnCode::BadClientId,
+        V5::BadUserNamePassword | V5::BadAuthenticationMethod => {
+            ConnectReturnCode::BadUserNamePassword
+        }
+        V5::NotAuthorized | V5::Banned => ConnectReturnCode::NotAuthorized,
+        _ => ConnectReturnCode::ServiceUnavailable,
+    }
+}
+
+pub(crate) fn qos_to_v5(qos: QoS) -> QoSV5 {
+    match qos {
+        QoS::AtMostOnce => QoSV5::AtMostOnce,
+        QoS::AtLeastOnce => QoSV5::AtLeastOnce,
+        QoS::ExactlyOnce => QoSV5::ExactlyOnce,
+    }
+}
+
+pub(crate) fn qos_from_v5(qos: QoSV5) -> QoS {
+    match qos {
+        QoSV5::AtMostOnce => QoS::AtMostOnce,
+        QoSV5::AtLeastOnce => QoS::AtLeastOnce,
+        QoSV5::ExactlyOnce => QoS::ExactlyOnce,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suback(return_codes: Vec<packet::SubscribeReasonCode>) -> v5::Event {
+        v5::Event::Incoming(packet::Packet::SubAck(packet::SubAck {
+            pkid: 3,
+            return_codes,
+            properties: None,
+        }))
+    }
+
+    fn puback(reason: packet::PubAckReason) -> v5::Event {
+        v5::Event::Incoming(packet::Packet::PubAck(packet::PubAck {
+            pkid: 4,
+            reason,
+            properties: None,
+        }))
+    }
+
+    #[test]
+    fn reason_codes_refuse_a_filter_of_the_suback() {
+        let event = suback(vec![
+            packet::SubscribeReasonCode::Success(QoSV5::AtLeastOnce),
+            packet::SubscribeReasonCode::NotAuthorized,
+        ]);
+        assert!(matches!(
+            refusal(&event),
+            Some(Refusal::Subscribe(3, reason)) if reason == "NotAuthorized"
+        ));
+        let Event::Incoming(Packet::SubAck(ack)) = into_event(event) else {
+            panic!("not a SubAck");
+        };
+        assert_eq!(
+            ack.return_codes,
+            [
+                SubscribeReasonCode::Success(QoS::AtLeastOnce),
+                SubscribeReasonCode::Failure
+            ]
+        );
+
+        let granted = suback(vec![packet::SubscribeReasonCode::Success(
+            QoSV5::ExactlyOnce,
+        )]);
+        assert!(refusal(&granted).is_none());
+    }
+
+    #[test]
+    fn only_failing_pubacks_refuse_the_publish() {
+        assert!(refusal(&puback(packet::PubAckReason::Success)).is_none());
+        assert!(refusal(&puback(packet::PubAckReason::NoMatchingSubscribers)).is_none());
+        assert!(matches!(
+            refusal(&puback(packet::PubAckReason::QuotaExceeded)),
+            Some(Refusal::Publish(4, reason)) if reason == "QuotaExceeded"
+        ));
+    }
+}
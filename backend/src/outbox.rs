@@ -0,0 +1,153 @@
+use std::{
+    collections::VecDeque,
+    future,
+    sync::{Arc, Mutex},
+};
+
+use rumqttc::{Event, Packet};
+use tokio::sync::mpsc::{error::TrySendError, Sender};
+
+use crate::message::{ClientId, FromClient};
+
+/// The event loop's side of the channel to the frontend, it never waits for
+/// room so that a slow frontend cannot stall pings and acks. Events that find
+/// the channel full are dropped and counted, states and request outcomes are
+/// kept in order until there is room again
+#[derive(Clone)]
+pub(crate) struct Outbox {
+    client_id: ClientId,
+    sender: Sender<(ClientId, FromClient)>,
+    pending: Arc<Mutex<Pending>>,
+}
+
+#[derive(Default)]
+struct Pending {
+    messages: VecDeque<FromClient>,
+    /// events dropped since the last `FromClient::Dropped`
+    dropped: u64,
+}
+
+impl Outbox {
+    pub fn new(client_id: ClientId, sender: Sender<(ClientId, FromClient)>) -> Self {
+        Self {
+            client_id,
+            sender,
+            pending: Default::default(),
+        }
+    }
+
+    /// Forwards `event`, or counts it as dropped when the frontend is behind
+    pub fn event(&self, event: Event) {
+        // the frontend subscribes again on ConnAck
+        if matches!(event, Event::Incoming(Packet::ConnAck(_))) {
+            return self.send(FromClient::Event(event));
+        }
+        let mut pending = self.pending.lock().unwrap();
+        self.flush_locked(&mut pending);
+        if !pending.messages.is_empty() || pending.dropped > 0 {
+            pending.dropped += 1;
+            return;
+        }
+        let msg = (self.client_id.clone(), FromClient::Event(event));
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(msg) {
+            pending.dropped += 1;
+        }
+    }
+
+    /// Queues `msg` behind what is still pending, it is never dropped
+    pub fn send(&self, msg: FromClient) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.messages.push_back(msg);
+        self.flush_locked(&mut pending);
+    }
+
+    /// Hands what is pending to the channel as far as there is room
+    pub fn flush(&self) {
+        self.flush_locked(&mut self.pending.lock().unwrap());
+    }
+
+    /// Resolves once something is pending and the channel has room for it,
+    /// never while nothing is pending
+    pub async fn room(&self) {
+        if self.is_empty() {
+            return future::pending().await;
+        }
+        // a closed channel is noticed by the next flush
+        let _ = self.sender.reserve().await;
+    }
+
+    /// Waits until everything pending was handed to the channel
+    pub async fn drain(&self) {
+        while !self.is_empty() {
+            self.room().await;
+            self.flush();
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        let pending = self.pending.lock().unwrap();
+        pending.messages.is_empty() && pending.dropped == 0
+    }
+
+    fn flush_locked(&self, pending: &mut Pending) {
+        while let Some(msg) = pending.messages.pop_front() {
+            match self.sender.try_send((self.client_id.clone(), msg)) {
+                Ok(()) => {}
+                Err(TrySendError::Full((_, msg))) => {
+                    pending.messages.push_front(msg);
+                    return;
+                }
+                Err(TrySendError::Closed(_)) => {
+                    *pending = Pending::default();
+                    return;
+                }
+            }
+        }
+        if pending.dropped > 0 {
+            let msg = (self.client_id.clone(), FromClient::Dropped(pending.dropped));
+            match self.sender.try_send(msg) {
+                Ok(()) => pending.dropped = 0,
+                Err(TrySendError::Full(_)) => {}
+                Err(TrySendError::Closed(_)) => pending.dropped = 0,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rumqttc::Outgoing;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::message::ConnectionState;
+
+    #[test]
+    fn a_full_channel_drops_events_and_keeps_states() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let outbox = Outbox::new("client".to_owned(), tx);
+
+        outbox.event(Event::Outgoing(Outgoing::PingReq));
+        outbox.event(Event::Outgoing(Outgoing::PingReq));
+        outbox.send(FromClient::State(ConnectionState::Connected));
+        outbox.event(Event::Outgoing(Outgoing::PingReq));
+
+        let mut received = vec![];
+        while let Ok((_, msg)) = rx.try_recv() {
+            received.push(msg);
+            outbox.flush();
+        }
+        assert!(matches!(
+            received.as_slice(),
+            [
+                FromClient::Event(_),
+                FromClient::State(ConnectionState::Connected),
+                FromClient::Dropped(2),
+            ]
+        ));
+        assert!(outbox.is_empty());
+
+        outbox.event(Event::Outgoing(Outgoing::PingReq));
+        assert!(matches!(rx.try_recv(), Ok((_, FromClient::Event(_)))));
+    }
+}
@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use rumqttc::{ConnectReturnCode, Event, Packet};
+
+use crate::{
+    message::{ConnectionState, FromClient, MqttError, ReconnectOptions, ReconnectStatus},
+    outbox::Outbox,
+};
+
+/// Applies the reconnect policy of a profile to its event loop and reports
+/// every step and connection state transition to the frontend
+pub(crate) struct Reconnector {
+    options: ReconnectOptions,
+    attempt: u32,
+    outbox: Outbox,
+}
+
+impl Reconnector {
+    pub fn new(options: ReconnectOptions, outbox: Outbox) -> Self {
+        Self {
+            options,
+            attempt: 0,
+            outbox,
+        }
+    }
+
+    /// Called for every event, a successful ConnAck resets the attempts
+    pub fn on_event(&mut self, event: &Event) {
+        if let Event::Incoming(Packet::ConnAck(ack)) = event {
+            if ack.code == ConnectReturnCode::Success {
+                self.set_state(ConnectionState::Connected);
+                if self.attempt > 0 {
+                    self.report(ReconnectStatus::Succeeded {
+                        attempt: self.attempt,
+                    });
+                }
+                self.attempt = 0;
+            }
+        }
+    }
+
+    /// Called when polling the event loop failed, returns how long to wait
+    /// before polling again or `None` when the client should stop
+    pub fn on_error(&mut self, error: impl Into<MqttError>) -> Option<Duration> {
+        let error = error.into();
+        self.send(FromClient::Error(error.clone()));
+        self.attempt += 1;
+        match self.options.delay(self.attempt) {
+            Some(delay) => {
+                self.set_state(ConnectionState::Reconnecting(self.attempt));
+                self.report(ReconnectStatus::Waiting {
+                    attempt: self.attempt,
+                    delay,
+                });
+                Some(delay)
+            }
+            None => {
+                self.report(ReconnectStatus::GaveUp {
+                    attempts: self.attempt - 1,
+                });
+                self.set_state(ConnectionState::Failed(error));
+                None
+            }
+        }
+    }
+
+    pub fn set_state(&self, state: ConnectionState) {
+        self.send(FromClient::State(state));
+    }
+
+    fn report(&self, status: ReconnectStatus) {
+        self.send(FromClient::Reconnect(status));
+    }
+
+    fn send(&self, msg: FromClient) {
+        self.outbox.send(msg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rumqttc::ConnAck;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::message::ReconnectPolicy;
+
+    fn options(policy: ReconnectPolicy, jitter: bool) -> ReconnectOptions {
+        ReconnectOptions {
+            policy,
+            interval_ms: 1000,
+            max_interval_ms: 10_000,
+            jitter,
+            max_attempts: 0,
+        }
+    }
+
+    fn millis(options: &ReconnectOptions, attempt: u32) -> Option<u64> {
+        options.delay(attempt).map(|d| d.as_millis() as u64)
+    }
+
+    #[test]
+    fn exponential_doubles_up_to_the_max() {
+        let options = options(ReconnectPolicy::Exponential, false);
+        let delays: Vec<_> = (1..=6).map(|a| millis(&options, a).unwrap()).collect();
+        assert_eq!(delays, [1000, 2000, 4000, 8000, 10_000, 10_000]);
+        // no overflow however long it retries
+        assert_eq!(millis(&options, u32::MAX), Some(10_000));
+    }
+
+    #[test]
+    fn fixed_off_and_max_attempts() {
+        let mut fixed = options(ReconnectPolicy::Fixed, false);
+        assert_eq!(millis(&fixed, 1), Some(1000));
+        assert_eq!(millis(&fixed, 50), Some(1000));
+        fixed.max_attempts = 3;
+        assert_eq!(millis(&fixed, 3), Some(1000));
+        assert_eq!(millis(&fixed, 4), None);
+        assert_eq!(millis(&options(ReconnectPolicy::Off, false), 1), None);
+    }
+
+    #[test]
+    fn jitter_stays_between_half_and_full() {
+        let options = options(ReconnectPolicy::Exponential, true);
+        for attempt in 1..=6 {
+            let full = (1000u64 << (attempt - 1)).min(10_000);
+            for _ in 0..50 {
+                let delay = millis(&options, attempt).unwrap();
+                assert!((full / 2..=full).contains(&delay), "{} of {}", delay, full);
+            }
+        }
+        let zero = ReconnectOptions {
+            interval_ms: 0,
+            ..options
+        };
+        assert_eq!(millis(&zero, 1), Some(0));
+    }
+
+    fn received(rx: &mut mpsc::Receiver<(String, FromClient)>) -> Vec<FromClient> {
+        std::iter::from_fn(|| rx.try_recv().ok().map(|(_, msg)| msg)).collect()
+    }
+
+    #[test]
+    fn reports_attempts_and_recovery() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let outbox = Outbox::new("client".to_owned(), tx);
+        let mut reconnector = Reconnector::new(options(ReconnectPolicy::Fixed, false), outbox);
+
+        assert_eq!(
+            reconnector.on_error(MqttError::Timeout),
+            Some(Duration::from_secs(1))
+        );
+        assert!(matches!(
+            received(&mut rx).as_slice(),
+            [
+                FromClient::Error(MqttError::Timeout),
+                FromClient::State(ConnectionState::Reconnecting(1)),
+                FromClient::Reconnect(ReconnectStatus::Waiting { attempt: 1, .. }),
+            ]
+        ));
+
+        let ack = ConnAck {
+            session_present: false,
+            code: ConnectReturnCode::Success,
+        };
+        reconnector.on_event(&Event::Incoming(Packet::ConnAck(ack)));
+        assert!(matches!(
+            received(&mut rx).as_slice(),
+            [
+                FromClient::State(ConnectionState::Connected),
+                FromClient::Reconnect(ReconnectStatus::Succeeded { attempt: 1 }),
+            ]
+        ));
+        assert_eq!(reconnector.attempt, 0);
+    }
+
+    #[test]
+    fn gives_up_with_the_last_error() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let outbox = Outbox::new("client".to_owned(), tx);
+        let mut reconnector = Reconnector::new(options(ReconnectPolicy::Off, false), outbox);
+
+        assert_eq!(reconnector.on_error(MqttError::Timeout), None);
+        assert!(matches!(
+            received(&mut rx).as_slice(),
+            [
+                FromClient::Error(_),
+                FromClient::Reconnect(ReconnectStatus::GaveUp { attempts: 0 }),
+                FromClient::State(ConnectionState::Failed(MqttError::Timeout)),
+            ]
+        ));
+    }
+}
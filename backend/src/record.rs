@@ -0,0 +1,55 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, FixedOffset, Local, SecondsFormat};
+use rumqttc::{Publish, QoS};
+use serde::{Deserialize, Serialize};
+
+use crate::message::qos_serde;
+
+/// A publish as written to a session file, one JSON object per line
+#[derive(Serialize, Deserialize)]
+pub struct Record {
+    /// RFC 3339 with milliseconds
+    pub time: String,
+    pub topic: String,
+    #[serde(with = "qos_serde")]
+    pub qos: QoS,
+    pub retain: bool,
+    /// the payload as text, base64 when it is not UTF-8
+    pub payload: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub base64: bool,
+}
+
+impl Record {
+    pub fn new(time: DateTime<Local>, publish: &Publish) -> Self {
+        let (payload, base64) = match std::str::from_utf8(&publish.payload) {
+            Ok(text) => (text.to_owned(), false),
+            Err(_) => (STANDARD.encode(&publish.payload), true),
+        };
+        Self {
+            time: time.to_rfc3339_opts(SecondsFormat::Millis, false),
+            topic: publish.topic.clone(),
+            qos: publish.qos,
+            retain: publish.retain,
+            payload,
+            base64,
+        }
+    }
+
+    pub fn time(&self) -> Result<DateTime<FixedOffset>, String> {
+        DateTime::parse_from_rfc3339(&self.time).map_err(|e| format!("time {}: {}", self.time, e))
+    }
+
+    pub fn publish(&self) -> Result<Publish, String> {
+        let payload = if self.base64 {
+            STANDARD
+                .decode(&self.payload)
+                .map_err(|e| format!("payload: {}", e))?
+        } else {
+            self.payload.clone().into_bytes()
+        };
+        let mut publish = Publish::new(self.topic.clone(), self.qos, payload);
+        publish.retain = self.retain;
+        Ok(publish)
+    }
+}
@@ -0,0 +1,348 @@
+use std::{
+    collections::{vec_deque, VecDeque},
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    mem,
+    path::PathBuf,
+};
+
+use chrono::Local;
+use rumqttc::Publish;
+use serde::{Deserialize, Serialize};
+
+use crate::record::Record;
+
+/// What bounds a `MessageStore`, the oldest messages are evicted past it
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StoreLimit {
+    Count(usize),
+    Bytes(usize),
+}
+
+impl Default for StoreLimit {
+    fn default() -> Self {
+        StoreLimit::Count(100_000)
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StoreOptions {
+    pub limit: StoreLimit,
+    /// evicted messages are appended to this file as NDJSON, nothing is kept
+    /// when empty
+    pub overflow_path: String,
+}
+
+/// A message kept in a `MessageStore`
+pub trait StoreItem {
+    /// Approximate heap and inline size, payloads shared with other messages
+    /// are counted by every one of them
+    fn size(&self) -> usize;
+
+    /// Writes the message as one line of the overflow file, a `Record` for
+    /// publishes so that the file can be replayed like a session
+    fn spill(&self, out: &mut dyn Write) -> io::Result<()>;
+}
+
+impl StoreItem for Publish {
+    fn size(&self) -> usize {
+        mem::size_of::<Publish>() + self.topic.len() + self.payload.len()
+    }
+
+    /// A bare publish carries no time, it is stamped when evicted
+    fn spill(&self, out: &mut dyn Write) -> io::Result<()> {
+        spill(out, &Record::new(Local::now(), self))
+    }
+}
+
+/// Writes `record` as one line of JSON
+pub fn spill(out: &mut dyn Write, record: &Record) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, record)?;
+    out.write_all(b"\n")
+}
+
+/// First in, first out buffer of messages bounded by count or by bytes.
+///
+/// Payloads are `Bytes`, so a message shared with the backend or another view
+/// is not copied when stored.
+pub struct MessageStore<T> {
+    items: VecDeque<T>,
+    options: StoreOptions,
+    bytes: usize,
+    evicted: u64,
+    /// sequence number of the oldest message still in memory
+    first_seq: u64,
+    overflow: Option<BufWriter<File>>,
+    /// set once writing the overflow failed, the file is not retried
+    overflow_error: Option<io::Error>,
+}
+
+impl<T: StoreItem> MessageStore<T> {
+    pub fn new(options: StoreOptions) -> Self {
+        Self {
+            items: VecDeque::new(),
+            options,
+            bytes: 0,
+            evicted: 0,
+            first_seq: 0,
+            overflow: None,
+            overflow_error: None,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.bytes += item.size();
+        self.items.push_back(item);
+        self.evict();
+    }
+
+    pub fn iter(&self) -> vec_deque::Iter<'_, T> {
+        self.items.iter()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.items.get(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Sequence number of the message at `index`, it stays the same while
+    /// older messages are evicted or cleared
+    pub fn seq(&self, index: usize) -> u64 {
+        self.first_seq + index as u64
+    }
+
+    /// Index of the message with sequence number `seq`, if still in memory
+    pub fn index_of(&self, seq: u64) -> Option<usize> {
+        let index = usize::try_from(seq.checked_sub(self.first_seq)?).ok()?;
+        (index < self.items.len()).then_some(index)
+    }
+
+    /// Approximate size of the messages in memory
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Messages dropped from memory since the store was created
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+
+    pub fn options(&self) -> &StoreOptions {
+        &self.options
+    }
+
+    pub fn overflow_error(&self) -> Option<&io::Error> {
+        self.overflow_error.as_ref()
+    }
+
+    /// Applies new options, evicting right away if the limit shrank
+    pub fn set_options(&mut self, options: StoreOptions) {
+        if options.overflow_path != self.options.overflow_path {
+            self.flush();
+            self.overflow = None;
+            self.overflow_error = None;
+        }
+        self.options = options;
+        self.evict();
+        self.flush();
+    }
+
+    /// Drops every message, the overflow file is left as it is
+    pub fn clear(&mut self) {
+        self.first_seq += self.items.len() as u64;
+        self.items.clear();
+        self.bytes = 0;
+    }
+
+    pub fn flush(&mut self) {
+        if let Some(Err(e)) = self.overflow.as_mut().map(|out| out.flush()) {
+            self.overflow = None;
+            self.overflow_error = Some(e);
+        }
+    }
+
+    fn over_limit(&self) -> bool {
+        match self.options.limit {
+            StoreLimit::Count(count) => self.items.len() > count,
+            StoreLimit::Bytes(bytes) => self.bytes > bytes,
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.over_limit() {
+            let Some(item) = self.items.pop_front() else {
+                break;
+            };
+            self.bytes -= item.size();
+            self.evicted += 1;
+            self.first_seq += 1;
+            self.spill(&item);
+        }
+    }
+
+    fn spill(&mut self, item: &T) {
+        if self.options.overflow_path.is_empty() || self.overflow_error.is_some() {
+            return;
+        }
+        if self.overflow.is_none() {
+            let path = PathBuf::from(&self.options.overflow_path);
+            match OpenOptions::new().create(true).append(true).open(path) {
+                Ok(file) => self.overflow = Some(BufWriter::new(file)),
+                Err(e) => {
+                    self.overflow_error = Some(e);
+                    return;
+                }
+            }
+        }
+        if let Some(Err(e)) = self.overflow.as_mut().map(|out| item.spill(out)) {
+            self.overflow = None;
+            self.overflow_error = Some(e);
+        }
+    }
+}
+
+impl<T> Drop for MessageStore<T> {
+    fn drop(&mut self) {
+        if let Some(out) = self.overflow.as_mut() {
+            let _ = out.flush();
+        }
+    }
+}
+
+impl<'a, T: StoreItem> IntoIterator for &'a MessageStore<T> {
+    type Item = &'a T;
+    type IntoIter = vec_deque::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rumqttc::QoS;
+
+    use super::*;
+
+    fn publish(i: usize) -> Publish {
+        Publish::new(format!("t/{}", i), QoS::AtMostOnce, vec![0; 10])
+    }
+
+    fn store(limit: StoreLimit) -> MessageStore<Publish> {
+        MessageStore::new(StoreOptions {
+            limit,
+            overflow_path: String::new(),
+        })
+    }
+
+    fn topics(store: &MessageStore<Publish>) -> Vec<String> {
+        store.iter().map(|p| p.topic.clone()).collect()
+    }
+
+    #[test]
+    fn evicts_the_oldest_past_the_count() {
+        let mut store = store(StoreLimit::Count(3));
+        for i in 0..5 {
+            store.push(publish(i));
+        }
+        assert_eq!(topics(&store), ["t/2", "t/3", "t/4"]);
+        assert_eq!(store.evicted(), 2);
+        assert_eq!(store.seq(0), 2);
+        assert_eq!(store.index_of(1), None);
+        assert_eq!(store.index_of(4), Some(2));
+        assert_eq!(store.index_of(5), None);
+    }
+
+    #[test]
+    fn evicts_the_oldest_past_the_bytes() {
+        // not the inherent `Publish::size`, the encoded length
+        let size = StoreItem::size(&publish(0));
+        let mut store = store(StoreLimit::Bytes(size * 2 + size / 2));
+        for i in 0..4 {
+            store.push(publish(i));
+        }
+        assert_eq!(topics(&store), ["t/2", "t/3"]);
+        assert_eq!(store.bytes(), size * 2);
+
+        // a single message larger than the limit is not kept either
+        store.push(Publish::new("big", QoS::AtMostOnce, vec![0; size * 3]));
+        assert!(store.is_empty());
+        assert_eq!(store.bytes(), 0);
+    }
+
+    #[test]
+    fn shrinking_the_limit_evicts_right_away() {
+        let mut store = store(StoreLimit::Count(10));
+        for i in 0..5 {
+            store.push(publish(i));
+        }
+        store.set_options(StoreOptions {
+            limit: StoreLimit::Count(2),
+            overflow_path: String::new(),
+        });
+        assert_eq!(topics(&store), ["t/3", "t/4"]);
+    }
+
+    #[test]
+    fn clear_keeps_the_sequence_numbers() {
+        let mut store = store(StoreLimit::Count(10));
+        store.push(publish(0));
+        store.push(publish(1));
+        store.clear();
+        store.push(publish(2));
+        assert_eq!(store.seq(0), 2);
+        assert_eq!(store.index_of(2), Some(0));
+        assert_eq!(store.evicted(), 0);
+    }
+
+    #[test]
+    fn evicted_payloads_can_be_read_back_from_the_file() {
+        let path = std::env::temp_dir().join(format!("store-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut store = MessageStore::new(StoreOptions {
+            limit: StoreLimit::Count(1),
+            overflow_path: path.to_string_lossy().into_owned(),
+        });
+        let mut binary = Publish::new("t/1", QoS::ExactlyOnce, vec![0xff, 0x00, 0xfe]);
+        binary.retain = true;
+        store.push(publish(0));
+        store.push(binary);
+        store.push(publish(2));
+        store.flush();
+        let spilled = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let records: Vec<Record> = spilled
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].publish().unwrap().payload.as_ref(), &[0; 10]);
+        assert!(!records[0].base64 && records[1].base64);
+        let read = records[1].publish().unwrap();
+        assert_eq!(read.topic, "t/1");
+        assert_eq!(read.payload.as_ref(), &[0xff, 0x00, 0xfe]);
+        assert_eq!((read.qos, read.retain), (QoS::ExactlyOnce, true));
+        assert!(records[1].time().is_ok());
+        assert!(store.overflow_error().is_none());
+    }
+
+    #[test]
+    fn an_unwritable_overflow_file_is_reported() {
+        let mut store = MessageStore::new(StoreOptions {
+            limit: StoreLimit::Count(0),
+            overflow_path: "/nonexistent/dir/overflow.log".to_owned(),
+        });
+        store.push(publish(0));
+        assert!(store.overflow_error().is_some());
+        assert_eq!(store.evicted(), 1);
+    }
+}
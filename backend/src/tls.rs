@@ -0,0 +1,204 @@
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    sync::Arc,
+};
+
+use rumqttc::{
+    tokio_rustls::rustls::{
+        self,
+        client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        crypto::{self, ring, CryptoProvider},
+        pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+        ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    },
+    TlsConfiguration,
+};
+
+use crate::message::TlsOptions;
+
+/// Builds the rustls configuration described by the connection profile
+pub fn configuration(opts: &TlsOptions) -> io::Result<TlsConfiguration> {
+    let mut roots = RootCertStore::empty();
+    if opts.ca_path.is_empty() {
+        for cert in rustls_native_certs::load_native_certs()? {
+            let _ = roots.add(cert);
+        }
+    } else {
+        roots.add_parsable_certificates(read_certs(&opts.ca_path)?);
+        if roots.is_empty() {
+            return Err(invalid_data(format!(
+                "no valid CA certificate in {}",
+                opts.ca_path
+            )));
+        }
+    }
+
+    let builder = ClientConfig::builder().with_root_certificates(roots);
+    let mut config = if opts.client_auth {
+        let certs = read_certs(&opts.client_cert_path)?;
+        if certs.is_empty() {
+            return Err(invalid_data(format!(
+                "no valid client certificate in {}",
+                opts.client_cert_path
+            )));
+        }
+        let key = read_key(&opts.client_key_path)?;
+        builder
+            .with_client_auth_cert(certs, key)
+            .map_err(|e| invalid_data(e.to_string()))?
+    } else {
+        builder.with_no_client_auth()
+    };
+
+    config.alpn_protocols = opts
+        .alpn
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| p.as_bytes().to_vec())
+        .collect();
+
+    if opts.accept_invalid_certs {
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(AcceptAnyCert(ring::default_provider())));
+    }
+
+    Ok(TlsConfiguration::Rustls(Arc::new(config)))
+}
+
+fn read_certs(path: &str) -> io::Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(open(path)?);
+    rustls_pemfile::certs(&mut reader).collect()
+}
+
+fn read_key(path: &str) -> io::Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(open(path)?);
+    rustls_pemfile::private_key(&mut reader)?
+        .ok_or_else(|| invalid_data(format!("no valid private key in {}", path)))
+}
+
+fn open(path: &str) -> io::Result<File> {
+    File::open(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Accepts any server certificate while still checking the handshake signatures
+#[derive(Debug)]
+struct AcceptAnyCert(CryptoProvider);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("mqtt_v-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn error(opts: &TlsOptions) -> io::Error {
+        match configuration(opts) {
+            Ok(_) => panic!("configured"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn platform_roots_with_alpn() {
+        let opts = TlsOptions {
+            alpn: vec![
+                " mqtt ".to_owned(),
+                "".to_owned(),
+                "x-amzn-mqtt-ca".to_owned(),
+            ],
+            accept_invalid_certs: true,
+            ..TlsOptions::default()
+        };
+        let Ok(TlsConfiguration::Rustls(config)) = configuration(&opts) else {
+            panic!("not rustls");
+        };
+        assert_eq!(config.alpn_protocols, [&b"mqtt"[..], b"x-amzn-mqtt-ca"]);
+    }
+
+    #[test]
+    fn missing_and_empty_files_name_the_path() {
+        let missing = error(&TlsOptions {
+            ca_path: "/nonexistent/ca.pem".to_owned(),
+            ..TlsOptions::default()
+        });
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+        assert!(missing.to_string().starts_with("/nonexistent/ca.pem: "));
+
+        let empty = temp_file("empty.pem", "not a certificate\n");
+        let no_ca = error(&TlsOptions {
+            ca_path: empty.clone(),
+            ..TlsOptions::default()
+        });
+        assert_eq!(
+            no_ca.to_string(),
+            format!("no valid CA certificate in {}", empty)
+        );
+
+        let no_cert = error(&TlsOptions {
+            client_auth: true,
+            client_cert_path: empty.clone(),
+            client_key_path: empty.clone(),
+            ..TlsOptions::default()
+        });
+        std::fs::remove_file(&empty).unwrap();
+        assert_eq!(
+            no_cert.to_string(),
+            format!("no valid client certificate in {}", empty)
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use rumqttc::{Event, Outgoing, Packet, SubscribeReasonCode};
+
+use crate::{
+    message::{Delivery, FromClient, PublishRef, SubscriptionStatus, Topic},
+    outbox::Outbox,
+};
+
+/// Requests handed to the client, in the order the event loop will send them
+#[derive(Default)]
+pub(crate) struct Requests {
+    pub publishes: VecDeque<PublishRef>,
+    pub subscribes: VecDeque<Topic>,
+    pub unsubscribes: VecDeque<Topic>,
+}
+
+pub(crate) type Queued = Arc<Mutex<Requests>>;
+
+/// Matches requests to the packet ids they were sent with and reports their
+/// acknowledgements, publishes are followed up to the last PubAck or PubComp
+pub(crate) struct RequestTracker {
+    queued: Queued,
+    /// packet id -> publish waiting for PubAck or PubComp
+    publishes: HashMap<u16, (PublishRef, Instant)>,
+    subscribes: HashMap<u16, Topic>,
+    unsubscribes: HashMap<u16, Topic>,
+    outbox: Outbox,
+}
+
+impl RequestTracker {
+    pub fn new(outbox: Outbox) -> Self {
+        Self {
+            queued: Default::default(),
+            publishes: HashMap::new(),
+            subscribes: HashMap::new(),
+            unsubscribes: HashMap::new(),
+            outbox,
+        }
+    }
+
+    pub fn queued(&self) -> Queued {
+        self.queued.clone()
+    }
+
+    pub fn on_event(&mut self, event: &Event) {
+        match event {
+            // a publish resent after reconnecting keeps its packet id
+            Event::Outgoing(Outgoing::Publish(pkid)) if self.publishes.contains_key(pkid) => {}
+            Event::Outgoing(Outgoing::Publish(pkid)) => {
+                let Some(id) = self.queued.lock().unwrap().publishes.pop_front() else {
+                    return;
+                };
+                self.delivery(id, Delivery::Sent(*pkid));
+                // QoS 0 is never acknowledged
+                if *pkid != 0 {
+                    self.publishes.insert(*pkid, (id, Instant::now()));
+                }
+            }
+            Event::Incoming(Packet::PubRec(rec)) => {
+                if let Some((id, _)) = self.publishes.get(&rec.pkid) {
+                    self.delivery(*id, Delivery::Received);
+                }
+            }
+            Event::Incoming(Packet::PubAck(ack)) => self.acked(ack.pkid),
+            Event::Incoming(Packet::PubComp(comp)) => self.acked(comp.pkid),
+
+            Event::Outgoing(Outgoing::Subscribe(pkid)) => {
+                let Some(topic) = self.queued.lock().unwrap().subscribes.pop_front() else {
+                    return;
+                };
+                self.subscription(topic.clone(), SubscriptionStatus::Pending(*pkid));
+                self.subscribes.insert(*pkid, topic);
+            }
+            Event::Incoming(Packet::SubAck(ack)) => {
+                if let Some(topic) = self.subscribes.remove(&ack.pkid) {
+                    // one filter per SUBSCRIBE, a refusal of any filter wins
+                    let refused = ack
+                        .return_codes
+                        .iter()
+                        .any(|code| matches!(code, SubscribeReasonCode::Failure));
+                    let status = match ack.return_codes.first() {
+                        Some(SubscribeReasonCode::Success(qos)) if !refused => {
+                            SubscriptionStatus::Granted(*qos)
+                        }
+                        _ => SubscriptionStatus::Refused("Failure".to_owned()),
+                    };
+                    self.subscription(topic, status);
+                }
+            }
+            Event::Outgoing(Outgoing::Unsubscribe(pkid)) => {
+                let Some(topic) = self.queued.lock().unwrap().unsubscribes.pop_front() else {
+                    return;
+                };
+                self.subscription(
+                    topic.clone(),
+                    SubscriptionStatus::Unsubscribing(Some(*pkid)),
+                );
+                self.unsubscribes.insert(*pkid, topic);
+            }
+            Event::Incoming(Packet::UnsubAck(ack)) => {
+                if let Some(topic) = self.unsubscribes.remove(&ack.pkid) {
+                    self.subscription(topic, SubscriptionStatus::Unsubscribed);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The broker refused the publish with packet id `pkid`
+    pub fn publish_refused(&mut self, pkid: u16, reason: String) {
+        if let Some((id, _)) = self.publishes.remove(&pkid) {
+            self.delivery(id, Delivery::Failed(reason));
+        }
+    }
+
+    /// The broker refused the subscription with packet id `pkid`
+    pub fn subscribe_refused(&mut self, pkid: u16, reason: String) {
+        if let Some(topic) = self.subscribes.remove(&pkid) {
+            self.subscription(topic, SubscriptionStatus::Refused(reason));
+        }
+    }
+
+    /// Fails every publish still pending once the client stops
+    pub fn abandon(&mut self) {
+        let mut pending: Vec<_> = self.queued.lock().unwrap().publishes.drain(..).collect();
+        pending.extend(self.publishes.drain().map(|(_, (id, _))| id));
+        for id in pending {
+            self.delivery(id, Delivery::Failed("client stopped".to_owned()));
+        }
+    }
+
+    fn acked(&mut self, pkid: u16) {
+        if let Some((id, sent)) = self.publishes.remove(&pkid) {
+            self.delivery(id, Delivery::Acked(sent.elapsed()));
+        }
+    }
+
+    fn delivery(&self, id: PublishRef, delivery: Delivery) {
+        self.send(FromClient::Delivery(id, delivery));
+    }
+
+    fn subscription(&self, topic: Topic, status: SubscriptionStatus) {
+        self.send(FromClient::Subscription(topic, status));
+    }
+
+    fn send(&self, msg: FromClient) {
+        self.outbox.send(msg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rumqttc::{PubAck, PubComp, PubRec, QoS, SubAck, UnsubAck};
+    use tokio::sync::mpsc::{self, Receiver};
+
+    use super::*;
+    use crate::message::ClientId;
+
+    fn tracker() -> (RequestTracker, Receiver<(ClientId, FromClient)>) {
+        let (tx, rx) = mpsc::channel(64);
+        (
+            RequestTracker::new(Outbox::new("client".to_owned(), tx)),
+            rx,
+        )
+    }
+
+    /// Queues publishes the way the request thread does, one per name
+    fn queue(tracker: &RequestTracker, names: &[&'static str]) -> Vec<(PublishRef, &'static str)> {
+        let ids: Vec<_> = names
+            .iter()
+            .map(|name| (PublishRef::new_v4(), *name))
+            .collect();
+        let queued = tracker.queued();
+        queued
+            .lock()
+            .unwrap()
+            .publishes
+            .extend(ids.iter().map(|(id, _)| *id));
+        ids
+    }
+
+    /// Deliveries reported since the last call, as "name step"
+    fn deliveries(
+        rx: &mut Receiver<(ClientId, FromClient)>,
+        ids: &[(PublishRef, &str)],
+    ) -> Vec<String> {
+        let mut reported = vec![];
+        while let Ok((_, msg)) = rx.try_recv() {
+            let FromClient::Delivery(id, delivery) = msg else {
+                panic!("not a delivery: {:?}", msg);
+            };
+            let name = ids.iter().find(|(i, _)| *i == id).unwrap().1;
+            let step = match delivery {
+                Delivery::Acked(_) => "Acked".to_owned(),
+                delivery => format!("{:?}", delivery),
+            };
+            reported.push(format!("{} {}", name, step));
+        }
+        reported
+    }
+
+    fn sent(pkid: u16) -> Event {
+        Event::Outgoing(Outgoing::Publish(pkid))
+    }
+
+    fn incoming(packet: Packet) -> Event {
+        Event::Incoming(packet)
+    }
+
+    #[test]
+    fn qos_1_is_sent_then_acked() {
+        let (mut tracker, mut rx) = tracker();
+        let ids = queue(&tracker, &["a"]);
+        tracker.on_event(&sent(1));
+        tracker.on_event(&incoming(Packet::PubAck(PubAck::new(1))));
+        tracker.on_event(&incoming(Packet::PubAck(PubAck::new(1))));
+        assert_eq!(deliveries(&mut rx, &ids), ["a Sent(1)", "a Acked"]);
+    }
+
+    #[test]
+    fn qos_2_is_received_then_completed() {
+        let (mut tracker, mut rx) = tracker();
+        let ids = queue(&tracker, &["a"]);
+        tracker.on_event(&sent(7));
+        tracker.on_event(&incoming(Packet::PubRec(PubRec::new(7))));
+        tracker.on_event(&incoming(Packet::PubComp(PubComp::new(7))));
+        assert_eq!(
+            deliveries(&mut rx, &ids),
+            ["a Sent(7)", "a Received", "a Acked"]
+        );
+    }
+
+    #[test]
+    fn qos_0_stops_once_sent() {
+        let (mut tracker, mut rx) = tracker();
+        let ids = queue(&tracker, &["a", "b"]);
+        tracker.on_event(&sent(0));
+        tracker.on_event(&sent(0));
+        tracker.on_event(&incoming(Packet::PubAck(PubAck::new(0))));
+        tracker.abandon();
+        assert_eq!(deliveries(&mut rx, &ids), ["a Sent(0)", "b Sent(0)"]);
+    }
+
+    #[test]
+    fn resent_publishes_keep_their_request() {
+        let (mut tracker, mut rx) = tracker();
+        let ids = queue(&tracker, &["a", "b"]);
+        tracker.on_event(&sent(1));
+        // after a reconnect the unacked publish goes out again
+        tracker.on_event(&sent(1));
+        tracker.on_event(&sent(2));
+        tracker.on_event(&incoming(Packet::PubAck(PubAck::new(1))));
+        assert_eq!(
+            deliveries(&mut rx, &ids),
+            ["a Sent(1)", "b Sent(2)", "a Acked"]
+        );
+    }
+
+    #[test]
+    fn requests_match_packet_ids_in_order() {
+        let (mut tracker, mut rx) = tracker();
+        let ids = queue(&tracker, &["a", "b", "c"]);
+        for pkid in 1..=3 {
+            tracker.on_event(&sent(pkid));
+        }
+        tracker.on_event(&incoming(Packet::PubAck(PubAck::new(2))));
+        tracker.on_event(&incoming(Packet::PubAck(PubAck::new(3))));
+        tracker.on_event(&incoming(Packet::PubAck(PubAck::new(1))));
+        assert_eq!(
+            deliveries(&mut rx, &ids),
+            [
+                "a Sent(1)",
+                "b Sent(2)",
+                "c Sent(3)",
+                "b Acked",
+                "c Acked",
+                "a Acked"
+            ]
+        );
+    }
+
+    #[test]
+    fn refused_and_abandoned_publishes_fail() {
+        let (mut tracker, mut rx) = tracker();
+        let ids = queue(&tracker, &["a", "b", "c"]);
+        tracker.on_event(&sent(1));
+        tracker.on_event(&sent(2));
+        tracker.publish_refused(1, "QuotaExceeded".to_owned());
+        tracker.on_event(&incoming(Packet::PubAck(PubAck::new(1))));
+        tracker.abandon();
+        let mut reported = deliveries(&mut rx, &ids);
+        reported[3..].sort();
+        assert_eq!(
+            reported,
+            [
+                "a Sent(1)",
+                "b Sent(2)",
+                "a Failed(\"QuotaExceeded\")",
+                "b Failed(\"client stopped\")",
+                "c Failed(\"client stopped\")"
+            ]
+        );
+    }
+
+    /// Subscription statuses reported since the last call, as "topic status"
+    fn statuses(rx: &mut Receiver<(ClientId, FromClient)>) -> Vec<String> {
+        let mut reported = vec![];
+        while let Ok((_, msg)) = rx.try_recv() {
+            let FromClient::Subscription(topic, status) = msg else {
+                panic!("not a subscription: {:?}", msg);
+            };
+            reported.push(format!("{} {:?}", topic, status));
+        }
+        reported
+    }
+
+    #[test]
+    fn subscriptions_are_granted_or_refused_per_request() {
+        let (mut tracker, mut rx) = tracker();
+        let queued = tracker.queued();
+        queued
+            .lock()
+            .unwrap()
+            .subscribes
+            .extend(["a".to_owned(), "b".to_owned()]);
+        tracker.on_event(&Event::Outgoing(Outgoing::Subscribe(1)));
+        tracker.on_event(&Event::Outgoing(Outgoing::Subscribe(2)));
+        let refused = SubAck::new(
+            2,
+            vec![
+                SubscribeReasonCode::Success(QoS::AtLeastOnce),
+                SubscribeReasonCode::Failure,
+            ],
+        );
+        tracker.on_event(&incoming(Packet::SubAck(refused)));
+        let granted = SubAck::new(1, vec![SubscribeReasonCode::Success(QoS::AtMostOnce)]);
+        tracker.on_event(&incoming(Packet::SubAck(granted.clone())));
+        tracker.on_event(&incoming(Packet::SubAck(granted)));
+        assert_eq!(
+            statuses(&mut rx),
+            [
+                "a Pending(1)",
+                "b Pending(2)",
+                "b Refused(\"Failure\")",
+                "a Granted(AtMostOnce)"
+            ]
+        );
+    }
+
+    #[test]
+    fn refusals_with_a_reason_replace_the_suback() {
+        let (mut tracker, mut rx) = tracker();
+        tracker
+            .queued()
+            .lock()
+            .unwrap()
+            .subscribes
+            .push_back("a".to_owned());
+        tracker.on_event(&Event::Outgoing(Outgoing::Subscribe(1)));
+        tracker.subscribe_refused(1, "NotAuthorized".to_owned());
+        let ack = SubAck::new(1, vec![SubscribeReasonCode::Failure]);
+        tracker.on_event(&incoming(Packet::SubAck(ack)));
+        assert_eq!(
+            statuses(&mut rx),
+            ["a Pending(1)", "a Refused(\"NotAuthorized\")"]
+        );
+    }
+
+    #[test]
+    fn unsubacks_match_only_unsubscribes_that_went_out() {
+        let (mut tracker, mut rx) = tracker();
+        tracker
+            .queued()
+            .lock()
+            .unwrap()
+            .unsubscribes
+            .push_back("a".to_owned());
+        tracker.on_event(&incoming(Packet::UnsubAck(UnsubAck::new(5))));
+        assert!(statuses(&mut rx).is_empty());
+
+        tracker.on_event(&Event::Outgoing(Outgoing::Unsubscribe(5)));
+        tracker.on_event(&incoming(Packet::UnsubAck(UnsubAck::new(5))));
+        tracker.on_event(&incoming(Packet::UnsubAck(UnsubAck::new(5))));
+        assert_eq!(
+            statuses(&mut rx),
+            ["a Unsubscribing(Some(5))", "a Unsubscribed"]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use std::io;
+
+use http::{HeaderMap, HeaderName, HeaderValue, Request};
+
+use crate::{
+    message::{TlsOptions, Transport, WsOptions},
+    tls,
+};
+
+pub fn transport(transport: Transport, tls: &TlsOptions) -> io::Result<rumqttc::Transport> {
+    Ok(match transport {
+        Transport::Tcp => rumqttc::Transport::Tcp,
+        Transport::Tls => rumqttc::Transport::Tls(tls::configuration(tls)?),
+        Transport::Ws => rumqttc::Transport::Ws,
+        Transport::Wss => rumqttc::Transport::Wss(tls::configuration(tls)?),
+    })
+}
+
+/// rumqttc takes the full url as broker address for websocket transports
+pub fn broker_addr(transport: Transport, host: &str, port: u16, ws: &WsOptions) -> String {
+    let scheme = match transport {
+        Transport::Tcp | Transport::Tls => return host.to_owned(),
+        Transport::Ws => "ws",
+        Transport::Wss => "wss",
+    };
+    let path = ws.path.trim();
+    let slash = if path.starts_with('/') { "" } else { "/" };
+    // IPv6 literals are bracketed in urls
+    if host.contains(':') && !host.starts_with('[') {
+        format!("{}://[{}]:{}{}{}", scheme, host, port, slash, path)
+    } else {
+        format!("{}://{}:{}{}{}", scheme, host, port, slash, path)
+    }
+}
+
+pub fn headers(headers: &[(String, String)]) -> io::Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers.iter().filter(|(name, _)| !name.is_empty()) {
+        let name = HeaderName::try_from(name.as_str()).map_err(invalid_header)?;
+        let value = HeaderValue::try_from(value.as_str()).map_err(invalid_header)?;
+        map.append(name, value);
+    }
+    Ok(map)
+}
+
+pub async fn with_headers(mut request: Request<()>, headers: HeaderMap) -> Request<()> {
+    request.headers_mut().extend(headers);
+    request
+}
+
+fn invalid_header(e: impl std::error::Error) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("websocket header: {}", e),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Uri;
+
+    use super::*;
+
+    fn ws(path: &str) -> WsOptions {
+        WsOptions {
+            path: path.to_owned(),
+            headers: vec![],
+        }
+    }
+
+    fn host_and_port(url: &str) -> (String, Option<u16>) {
+        let uri: Uri = url.parse().unwrap();
+        (uri.host().unwrap().to_owned(), uri.port_u16())
+    }
+
+    #[test]
+    fn tcp_and_tls_take_the_host() {
+        let default = WsOptions::default();
+        assert_eq!(
+            broker_addr(Transport::Tcp, "broker", 1883, &default),
+            "broker"
+        );
+        assert_eq!(
+            broker_addr(Transport::Tls, "broker", 8883, &default),
+            "broker"
+        );
+    }
+
+    #[test]
+    fn websockets_take_a_url() {
+        let default = WsOptions::default();
+        assert_eq!(
+            broker_addr(Transport::Ws, "broker", 8080, &default),
+            "ws://broker:8080/mqtt"
+        );
+        assert_eq!(
+            broker_addr(Transport::Wss, "broker", 443, &ws(" ws/mqtt ")),
+            "wss://broker:443/ws/mqtt"
+        );
+        assert_eq!(
+            broker_addr(Transport::Ws, "broker", 80, &ws("")),
+            "ws://broker:80/"
+        );
+
+        let url = broker_addr(Transport::Wss, "::1", 8443, &default);
+        assert_eq!(url, "wss://[::1]:8443/mqtt");
+        assert_eq!(host_and_port(&url), ("[::1]".to_owned(), Some(8443)));
+        assert_eq!(
+            broker_addr(Transport::Ws, "[::1]", 80, &default),
+            "ws://[::1]:80/mqtt"
+        );
+    }
+
+    #[test]
+    fn headers_skip_empty_names_and_keep_repeats() {
+        let map = headers(&[
+            ("Authorization".to_owned(), "Bearer t".to_owned()),
+            ("".to_owned(), "ignored".to_owned()),
+            ("x-tag".to_owned(), "a".to_owned()),
+            ("x-tag".to_owned(), "b".to_owned()),
+        ])
+        .unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map["authorization"], "Bearer t");
+        let tags: Vec<_> = map.get_all("x-tag").iter().collect();
+        assert_eq!(tags, ["a", "b"]);
+
+        let e = headers(&[("bad name".to_owned(), "v".to_owned())]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert!(e.to_string().starts_with("websocket header: "));
+        assert!(headers(&[("x".to_owned(), "line\nbreak".to_owned())]).is_err());
+    }
+
+    #[tokio::test]
+    async fn headers_are_added_to_the_upgrade_request() {
+        let request = Request::builder()
+            .uri("ws://broker:8080/mqtt")
+            .header("sec-websocket-protocol", "mqtt")
+            .body(())
+            .unwrap();
+        let extra = headers(&[("x-tag".to_owned(), "a".to_owned())]).unwrap();
+        let request = with_headers(request, extra).await;
+        assert_eq!(request.headers()["sec-websocket-protocol"], "mqtt");
+        assert_eq!(request.headers()["x-tag"], "a");
+    }
+
+    #[test]
+    fn secure_transports_need_their_certificates() {
+        let tls = TlsOptions {
+            ca_path: "/nonexistent/ca.pem".to_owned(),
+            ..TlsOptions::default()
+        };
+        assert!(matches!(
+            transport(Transport::Tcp, &tls),
+            Ok(rumqttc::Transport::Tcp)
+        ));
+        assert!(matches!(
+            transport(Transport::Ws, &tls),
+            Ok(rumqttc::Transport::Ws)
+        ));
+        assert!(transport(Transport::Tls, &tls).is_err());
+        assert!(transport(Transport::Wss, &tls).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    fs::File,
+    future,
+    io::{self, BufWriter, Write},
+    time::Duration,
+};
+
+use backend::{
+    message::{
+        ConnectionState, Event, FromClient, OptionsV3, Packet, QoS, ReconnectOptions, ToClient,
+    },
+    mqtt_client,
+};
+use chrono::Local;
+use tokio::{sync::mpsc, time::Instant};
+
+use crate::ui::client::{
+    client::{ClientPacket, PacketData},
+    export::{Exporter, Format},
+};
+
+const EXPORT_USAGE: &str = "\
+usage: mqtt_v export [options] [OUTPUT]
+
+Subscribes to a broker and writes what arrives to OUTPUT, standard output
+when missing or `-`, until the count or the duration is reached or Ctrl-C.
+
+options:
+  -h, --host HOST        broker address, localhost by default
+  -p, --port PORT        broker port, 1883 by default
+  -i, --id ID            client id
+  -u, --username NAME
+  -P, --password SECRET
+  -t, --topic FILTER     topic filter to subscribe to, repeatable, # by default
+  -q, --qos QOS          QoS of the subscriptions, 0 by default
+  -f, --format FORMAT    ndjson, csv or log, from the OUTPUT extension otherwise
+  -n, --count N          stop after N publishes
+  -d, --duration SECS    stop after SECS seconds
+      --help             this text, -h is the host as with mosquitto_sub";
+
+/// What `mqtt_v export` was asked for
+struct ExportArgs {
+    options: OptionsV3,
+    topics: Vec<String>,
+    qos: QoS,
+    format: Format,
+    output: String,
+    count: Option<u64>,
+    duration: Option<Duration>,
+}
+
+impl ExportArgs {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = OptionsV3 {
+            client_id: format!("mqtt_v-export-{}", std::process::id()),
+            broker_addr: "localhost".to_owned(),
+            port: 1883,
+            keep_alive: true,
+            heatbbeat: 20,
+            clean_session: true,
+            max_packet_size: (u16::MAX, u16::MAX),
+            reconnect: ReconnectOptions {
+                max_attempts: 3,
+                ..ReconnectOptions::default()
+            },
+            ..OptionsV3::default()
+        };
+        let mut topics = vec![];
+        let mut qos = QoS::AtMostOnce;
+        let mut format = None;
+        let mut output = None;
+        let mut count = None;
+        let mut duration = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{} needs a value", arg))
+            };
+            let number = |value: String| {
+                value
+                    .parse::<u64>()
+                    .map_err(|e| format!("{} {}: {}", arg, value, e))
+            };
+            match arg.as_str() {
+                "-h" | "--host" => options.broker_addr = value()?,
+                "-p" | "--port" => {
+                    let port = number(value()?)?;
+                    options.port = u16::try_from(port).map_err(|e| e.to_string())?;
+                }
+                "-i" | "--id" => options.client_id = value()?,
+                "-u" | "--username" => {
+                    options.credentials = true;
+                    options.username = value()?;
+                }
+                "-P" | "--password" => {
+                    options.credentials = true;
+                    options.password = value()?;
+                }
+                "-t" | "--topic" => topics.push(value()?),
+                "-q" | "--qos" => {
+                    let level = number(value()?)?;
+                    qos = qos_level(level).ok_or_else(|| format!("no QoS {}", level))?;
+                }
+                "-f" | "--format" => {
+                    let name = value()?;
+                    let found = Format::from_name(&name);
+                    format = Some(found.ok_or_else(|| format!("unknown format {}", name))?);
+                }
+                "-n" | "--count" => count = Some(number(value()?)?),
+                "-d" | "--duration" => duration = Some(Duration::from_secs(number(value()?)?)),
+                "--help" => return Err(EXPORT_USAGE.to_owned()),
+                path if output.is_none() && (path == "-" || !path.starts_with('-')) => {
+                    output = Some(path.to_owned())
+                }
+                other => return Err(format!("unexpected {}\n\n{}", other, EXPORT_USAGE)),
+            }
+        }
+
+        let output = output.unwrap_or_else(|| "-".to_owned());
+        let format = format.unwrap_or(if output.ends_with(".csv") {
+            Format::Csv
+        } else {
+            Format::Ndjson
+        });
+        if topics.is_empty() {
+            topics.push("#".to_owned());
+        }
+        Ok(Self {
+            options,
+            topics,
+            qos,
+            format,
+            output,
+            count,
+            duration,
+        })
+    }
+}
+
+fn qos_level(level: u64) -> Option<QoS> {
+    match level {
+        0 => Some(QoS::AtMostOnce),
+        1 => Some(QoS::AtLeastOnce),
+        2 => Some(QoS::ExactlyOnce),
+        _ => None,
+    }
+}
+
+/// `mqtt_v export`, the export of the Event tab without the window
+pub async fn export(args: &[String]) -> Result<(), String> {
+    let args = ExportArgs::parse(args)?;
+    let out: Box<dyn Write> = if args.output == "-" {
+        Box::new(io::stdout().lock())
+    } else {
+        let file = File::create(&args.output).map_err(|e| format!("{}: {}", args.output, e))?;
+        Box::new(BufWriter::new(file))
+    };
+    let mut exporter = Exporter::new(args.format, out)?;
+
+    let reconnect = args.options.reconnect.clone();
+    let options = args.options.convert().map_err(|e| e.to_string())?;
+    let (tx, mut rx) = mpsc::channel(100);
+    let (requests, requests_rx) = mpsc::channel(10);
+    tokio::spawn(mqtt_client::new(tx, requests_rx, options, reconnect));
+
+    let deadline = async {
+        match args.duration {
+            Some(duration) => tokio::time::sleep_until(Instant::now() + duration).await,
+            None => future::pending().await,
+        }
+    };
+    tokio::pin!(deadline);
+    let interrupted = tokio::signal::ctrl_c();
+    tokio::pin!(interrupted);
+
+    let mut publishes = 0;
+    let mut failed = None;
+    while args.count.is_none_or(|count| publishes < count) {
+        let msg = tokio::select! {
+            msg = rx.recv() => msg,
+            _ = &mut deadline => break,
+            _ = &mut interrupted => break,
+        };
+        let Some((_, msg)) = msg else {
+            break;
+        };
+        let data = match msg {
+            FromClient::Event(event) => {
+                match &event {
+                    Event::Incoming(Packet::ConnAck(_)) => {
+                        for topic in &args.topics {
+                            let subscribe = ToClient::Subscribe((topic.clone(), args.qos));
+                            let _ = requests.send(subscribe).await;
+                        }
+                    }
+                    Event::Incoming(Packet::Publish(_)) => publishes += 1,
+                    _ => {}
+                }
+                PacketData::Event(event)
+            }
+            FromClient::Reconnect(status) => PacketData::Reconnect(status),
+            FromClient::Dropped(count) => PacketData::Dropped(count),
+            FromClient::Error(error) => {
+                eprintln!("{}", error);
+                PacketData::Error(error)
+            }
+            FromClient::State(ConnectionState::Failed(error)) => {
+                failed = Some(error.to_string());
+                break;
+            }
+            _ => continue,
+        };
+        exporter.write(&ClientPacket {
+            time: Local::now(),
+            data,
+            will_of: None,
+        })?;
+    }
+
+    let _ = requests.send(ToClient::Disconnect).await;
+    let written = exporter.finish()?;
+    eprintln!("{} {} written", written, args.format.name());
+    match failed {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<ExportArgs, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        ExportArgs::parse(&args)
+    }
+
+    #[test]
+    fn defaults() {
+        let args = parse(&[]).unwrap();
+        assert_eq!(args.options.broker_addr, "localhost");
+        assert_eq!(args.options.port, 1883);
+        assert_eq!(args.topics, vec!["#"]);
+        assert_eq!(args.qos, QoS::AtMostOnce);
+        assert!(args.format == Format::Ndjson);
+        assert_eq!(args.output, "-");
+        assert!(args.count.is_none() && args.duration.is_none());
+    }
+
+    #[test]
+    fn options_and_output() {
+        let args = parse(&[
+            "-h", "broker", "-p", "8883", "-t", "a/#", "--topic", "b", "-q", "2", "-n", "5", "-d",
+            "60", "out.csv",
+        ])
+        .unwrap();
+        assert_eq!(args.options.broker_addr, "broker");
+        assert_eq!(args.options.port, 8883);
+        assert_eq!(args.topics, vec!["a/#", "b"]);
+        assert_eq!(args.qos, QoS::ExactlyOnce);
+        assert_eq!(args.count, Some(5));
+        assert_eq!(args.duration, Some(Duration::from_secs(60)));
+        // from the extension unless given
+        assert!(args.format == Format::Csv);
+        assert!(parse(&["-f", "log", "out.csv"]).unwrap().format == Format::Log);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse(&["--help"]).err().unwrap(), EXPORT_USAGE);
+        assert!(parse(&["-h"]).err().unwrap().contains("needs a value"));
+        assert!(parse(&["-q", "3"]).err().unwrap().contains("no QoS 3"));
+        assert!(parse(&["-p", "70000"]).is_err());
+        assert!(parse(&["-f", "xml"])
+            .err()
+            .unwrap()
+            .contains("unknown format"));
+        assert!(parse(&["a.ndjson", "b.ndjson"]).is_err());
+        assert!(parse(&["--verbose"])
+            .err()
+            .unwrap()
+            .starts_with("unexpected"));
+    }
+}
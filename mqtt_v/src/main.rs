@@ -3,12 +3,21 @@
 use eframe::{epaint::Vec2, NativeOptions};
 use ui::MqttAppUI;
 
+mod cli;
 mod ui;
 mod util;
 
 #[tokio::main]
 async fn main() {
     util::enable_tracing();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("export") {
+        if let Err(e) = cli::export(&args[1..]).await {
+            eprintln!("mqtt_v export: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
     let native_options = NativeOptions {
         initial_window_size: Some(Vec2::new(970., 600.)),
         min_window_size: Some(Vec2::new(600., 300.)),
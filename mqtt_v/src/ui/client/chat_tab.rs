@@ -1,48 +1,260 @@
-use backend::message::{Event, QoS};
+use backend::{
+    message::{QoS, SubscriptionStatus},
+    store::StoreLimit,
+};
 use eframe::{
-    egui::{
-        self, style::Margin, Context, Id, InnerResponse, Layout, RichText, ScrollArea, TextEdit, Ui,
-    },
+    egui::{self, style::Margin, DragValue, InnerResponse, Layout, RichText, ScrollArea, TextEdit},
     emath::Align,
     epaint::{text, Color32, FontId},
 };
 
-use crate::ui::widgets::{docking, packet::PacketUI};
+use crate::ui::{
+    decoder::DecoderRule,
+    widgets::{docking, packet_list::PacketList},
+    THEME,
+};
 
-use super::client::{Client, Subcribe};
+use super::{
+    client::{subscription_label, Client, Subcribe},
+    export::{export, Format},
+    filter::{Filter, Filtered, Retain},
+};
 
 pub struct ChatTab {
     filter: Filter,
+    filtered: Filtered,
     subcribe: Subcribe,
-}
-
-#[derive(Default)]
-struct Filter {
-    pub direct: Direct,
-    pub topic: String,
-    pub content: String,
-}
-
-#[derive(Default)]
-struct Direct {
-    published: bool,
-    received: bool,
-}
-impl Filter {
-    fn filter(&self, _event: &Event) -> bool {
-        true
-    }
+    /// overflow file path being edited
+    overflow_path: String,
+    /// protobuf schema about to be loaded
+    schema_path: String,
+    export_format: Format,
+    export_path: String,
+    /// outcome of the last export
+    exported: Option<Result<usize, String>>,
+    list: PacketList,
+    /// the client `list` was shown for, the tab is shared by every client
+    client_id: String,
 }
 
 impl ChatTab {
     pub fn new() -> Self {
         Self {
             filter: Default::default(),
+            filtered: Default::default(),
             subcribe: Subcribe {
                 topic: "#".to_owned(),
                 qos: QoS::AtMostOnce,
                 color: Color32::GREEN,
+                status: SubscriptionStatus::Requested,
+                before_unsubscribe: None,
             },
+            overflow_path: String::new(),
+            schema_path: String::new(),
+            export_format: Format::Ndjson,
+            export_path: "history.ndjson".to_owned(),
+            exported: None,
+            list: PacketList::default(),
+            client_id: String::new(),
+        }
+    }
+
+    /// Packet kinds, QoS and retain flag shown
+    fn filter_menu(&mut self, ui: &mut egui::Ui) {
+        let filter = &mut self.filter;
+        ui.label("packets");
+        ui.checkbox(&mut filter.kinds.publish, "publish");
+        ui.checkbox(&mut filter.kinds.ack, "acks");
+        ui.checkbox(&mut filter.kinds.ping, "pings");
+        ui.checkbox(&mut filter.kinds.control, "connect, subscribe");
+        ui.checkbox(&mut filter.kinds.status, "reconnects, errors");
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("QoS");
+            for (qos, shown) in filter.qos.iter_mut().enumerate() {
+                ui.checkbox(shown, qos.to_string());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("retain");
+            ui.radio_value(&mut filter.retain, Retain::Any, "any");
+            ui.radio_value(&mut filter.retain, Retain::Only, "only");
+            ui.radio_value(&mut filter.retain, Retain::Without, "without");
+        });
+        ui.separator();
+        if ui.button("reset").clicked() {
+            *filter = Filter::default();
+            ui.close_menu();
+        }
+    }
+
+    /// Payload decoder per topic filter
+    fn decoder_menu(&mut self, ui: &mut egui::Ui, client: &mut Client) {
+        ui.label("the first matching filter wins, other topics are auto-detected");
+        ui.separator();
+        let names: Vec<String> = client.decoders.names().map(str::to_owned).collect();
+        let rules = &mut client.decoders.rules;
+        let mut remove = None;
+        for (i, rule) in rules.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.add(
+                    TextEdit::singleline(&mut rule.filter)
+                        .hint_text("topic/+/#")
+                        .desired_width(160.0),
+                );
+                ui.menu_button(format!("{} ⏷", rule.decoder), |ui| {
+                    ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        for name in &names {
+                            if ui
+                                .selectable_value(&mut rule.decoder, name.clone(), name)
+                                .clicked()
+                            {
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                });
+                if ui.button("ｘ").clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove {
+            rules.remove(i);
+        }
+        if ui.button("➕ rule").clicked() {
+            rules.push(DecoderRule {
+                filter: "#".to_owned(),
+                decoder: "json".to_owned(),
+            });
+        }
+
+        ui.separator();
+        ui.label("protobuf schemas, each message type becomes a decoder");
+        let mut remove = None;
+        for (i, schema) in client.decoders.schemas().iter().enumerate() {
+            ui.horizontal(|ui| {
+                match &schema.error {
+                    Some(e) => ui.colored_label(Color32::LIGHT_RED, "⚠").on_hover_text(e),
+                    None => ui.colored_label(Color32::GREEN, "✔"),
+                };
+                ui.label(&schema.path);
+                if ui.button("ｘ").clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove {
+            client.decoders.remove_schema(i);
+        }
+        ui.horizontal(|ui| {
+            ui.add(
+                TextEdit::singleline(&mut self.schema_path)
+                    .hint_text(".proto or descriptor set")
+                    .desired_width(200.0),
+            );
+            if ui.button("load").clicked() && !self.schema_path.trim().is_empty() {
+                client
+                    .decoders
+                    .add_schema(self.schema_path.trim().to_owned());
+                self.schema_path.clear();
+            }
+            if ui
+                .button("⟲")
+                .on_hover_text("load every schema again")
+                .clicked()
+            {
+                client.decoders.reload();
+            }
+        });
+    }
+
+    /// Writes the packets passing the filter to a file
+    fn export_menu(&mut self, ui: &mut egui::Ui, client: &Client) {
+        for format in Format::ALL {
+            let selected = self.export_format == format;
+            if ui.radio(selected, format.name()).clicked() && !selected {
+                // keep the file name, follow the extension
+                if let Some((stem, _)) = self.export_path.rsplit_once('.') {
+                    self.export_path = format!("{}.{}", stem, format.extension());
+                }
+                self.export_format = format;
+            }
+        }
+        ui.label(RichText::new(self.export_format.describe()).color(THEME.colors.lighter_gray));
+        ui.separator();
+        ui.add(TextEdit::singleline(&mut self.export_path).hint_text("file"));
+        let rows = self.filtered.rows();
+        if ui.button(format!("export {} shown", rows.len())).clicked() {
+            let store = &client.packets;
+            let packets = rows
+                .iter()
+                .filter_map(|seq| store.index_of(*seq).and_then(|i| store.get(i)));
+            self.exported = Some(export(self.export_format, &self.export_path, packets));
+        }
+        match &self.exported {
+            Some(Ok(count)) => {
+                ui.colored_label(Color32::LIGHT_GREEN, format!("✔ {} written", count));
+            }
+            Some(Err(e)) => {
+                ui.colored_label(Color32::LIGHT_RED, format!("⚠ {}", e));
+            }
+            None => {}
+        }
+    }
+
+    /// Limit and overflow file of the event history
+    fn store_menu(&mut self, ui: &mut egui::Ui, client: &mut Client) {
+        let store = &client.packets;
+        ui.label(format!(
+            "{} kept, {:.1} MB, {} evicted",
+            store.len(),
+            store.bytes() as f64 / 1e6,
+            store.evicted()
+        ));
+        ui.separator();
+
+        let mut options = store.options().clone();
+        ui.horizontal(|ui| {
+            let count = matches!(options.limit, StoreLimit::Count(_));
+            if ui.radio(count, "messages").clicked() && !count {
+                options.limit = StoreLimit::Count(100_000);
+            }
+            if ui.radio(!count, "MB").clicked() && count {
+                options.limit = StoreLimit::Bytes(256_000_000);
+            }
+        });
+        match &mut options.limit {
+            StoreLimit::Count(count) => {
+                ui.add(DragValue::new(count).clamp_range(1..=10_000_000).speed(100));
+            }
+            StoreLimit::Bytes(bytes) => {
+                let mut mb = *bytes / 1_000_000;
+                ui.add(
+                    DragValue::new(&mut mb)
+                        .clamp_range(1..=16_000)
+                        .suffix(" MB"),
+                );
+                *bytes = mb * 1_000_000;
+            }
+        }
+        ui.label("overflow file");
+        // applied once edited, not on every keystroke
+        let path =
+            ui.add(TextEdit::singleline(&mut self.overflow_path).hint_text("evicted are dropped"));
+        if path.lost_focus() {
+            options.overflow_path = self.overflow_path.trim().to_owned();
+        } else if !path.has_focus() {
+            self.overflow_path = options.overflow_path.clone();
+        }
+        if let Some(e) = store.overflow_error() {
+            ui.colored_label(Color32::LIGHT_RED, format!("⚠ {}", e));
+        }
+
+        if options.limit != store.options().limit
+            || options.overflow_path != store.options().overflow_path
+        {
+            client.packets.set_options(options);
         }
     }
 }
@@ -53,6 +265,15 @@ impl docking::Tab<Client> for ChatTab {
     }
 
     fn ui(&mut self, ui: &mut egui::Ui, client: &mut Client) {
+        let client_id = client.options.client_id();
+        if client_id != self.client_id {
+            self.list = PacketList::default();
+            self.exported = None;
+            self.client_id = client_id;
+        }
+        self.filtered
+            .update(&self.client_id, &self.filter, &client.packets);
+
         ui.push_id("chat_tab", |ui| {
             egui::Frame::default()
                 .outer_margin(Margin::symmetric(2., 6.))
@@ -78,9 +299,18 @@ impl docking::Tab<Client> for ChatTab {
                                                 let scroll = ScrollArea::vertical();
 
                                                 scroll.id_source("subcribtions").show(ui, |ui| {
-                                                    for Subcribe { topic, qos, color } in
-                                                        client.subscriptions.clone()
-                                                    {
+                                                    for sub in client.subscriptions.clone() {
+                                                        let (status, status_color, tooltip) =
+                                                            subscription_label(&sub);
+                                                        let idle = matches!(
+                                                            sub.status,
+                                                            SubscriptionStatus::Inactive
+                                                                | SubscriptionStatus::Refused(_)
+                                                        );
+                                                        let again = sub.clone();
+                                                        let Subcribe {
+                                                            topic, qos, color, ..
+                                                        } = sub;
                                                         egui::Frame::default()
                                                             .outer_margin(Margin::same(4.0))
                                                             .show(ui, |ui| {
@@ -96,7 +326,22 @@ impl docking::Tab<Client> for ChatTab {
                                                                         QoS::AtLeastOnce => "1",
                                                                         QoS::ExactlyOnce => "2",
                                                                     });
+                                                                    ui.colored_label(
+                                                                        status_color,
+                                                                        status,
+                                                                    )
+                                                                    .on_hover_text(tooltip);
 
+                                                                    if idle
+                                                                        && ui
+                                                                            .button("▶")
+                                                                            .on_hover_text(
+                                                                                "subscribe",
+                                                                            )
+                                                                            .clicked()
+                                                                    {
+                                                                        client.subscribe(again)
+                                                                    }
                                                                     if ui.button("ｘ").clicked() {
                                                                         client.unsubscribe(topic_c)
                                                                     }
@@ -136,16 +381,40 @@ impl docking::Tab<Client> for ChatTab {
                                                         client.subscribe(subcribe);
                                                     }
                                                 });
+                                                ui.checkbox(
+                                                    &mut client.auto_subscribe,
+                                                    "subscribe on connect",
+                                                );
                                             });
                                     });
                                 response.on_hover_cursor(egui::CursorIcon::PointingHand);
                             });
+                            ui.label(
+                                RichText::new(format!(
+                                    "{} of {} shown",
+                                    self.filtered.rows().len(),
+                                    client.packets.len()
+                                ))
+                                .color(THEME.colors.lighter_gray),
+                            );
                         });
 
                         ui.with_layout(Layout::right_to_left(Align::Min), |ui| {
                             if ui.button(RichText::new("🗑").color(Color32::RED)).clicked() {
                                 client.packets.clear();
                             };
+                            ui.menu_button("🔣", |ui| self.decoder_menu(ui, client))
+                                .response
+                                .on_hover_text("payload decoders");
+                            ui.menu_button("📤", |ui| self.export_menu(ui, client))
+                                .response
+                                .on_hover_text("export shown messages");
+                            ui.menu_button("💾", |ui| self.store_menu(ui, client))
+                                .response
+                                .on_hover_text("history limit");
+                            ui.menu_button("⚙", |ui| self.filter_menu(ui))
+                                .response
+                                .on_hover_text("more filters");
                             if ui
                                 .selectable_label(self.filter.direct.published, "published")
                                 .clicked()
@@ -162,9 +431,27 @@ impl docking::Tab<Client> for ChatTab {
                             ui.separator();
                             if ui.button("ｘ").clicked() {
                                 self.filter.topic.clear();
+                                self.filter.content.clear();
+                            }
+                            if let Some(e) = self.filtered.error() {
+                                ui.colored_label(Color32::LIGHT_RED, "⚠").on_hover_text(e);
+                            }
+                            if ui
+                                .selectable_label(self.filter.regex, ".*")
+                                .on_hover_text("regular expression")
+                                .clicked()
+                            {
+                                self.filter.regex = !self.filter.regex
                             }
                             ui.add(
-                                TextEdit::singleline(&mut self.filter.topic).desired_width(120.0),
+                                TextEdit::singleline(&mut self.filter.content)
+                                    .hint_text("payload")
+                                    .desired_width(120.0),
+                            );
+                            ui.add(
+                                TextEdit::singleline(&mut self.filter.topic)
+                                    .hint_text("topic/+/#")
+                                    .desired_width(120.0),
                             );
 
                             ui.colored_label(Color32::YELLOW, "🔭");
@@ -172,34 +459,11 @@ impl docking::Tab<Client> for ChatTab {
                     });
                 });
             ui.add_space(12.);
-            ScrollArea::vertical()
-                .stick_to_bottom(true)
-                // .max_width(ui.available_width())
-                .show(ui, |ui| {
-                    for pkt in &client.packets {
-                        PacketUI::show(ui, pkt, &client.subscriptions)
-                    }
-                });
+            self.filtered
+                .update(&self.client_id, &self.filter, &client.packets);
+            if let Some(plot) = self.list.show(ui, client, self.filtered.rows()) {
+                client.add_plot(plot);
+            }
         });
     }
 }
-
-#[derive(Clone, Debug, Default)]
-pub struct State {
-    open: bool,
-}
-
-impl State {
-    pub fn load(ctx: &Context, id: Id) -> Self {
-        ctx.data().get_temp(id).unwrap_or(Self { open: true })
-    }
-
-    pub fn store(self, ctx: &Context, id: Id) {
-        ctx.data().insert_temp(id, self);
-    }
-
-    pub fn toggle(&mut self, ui: &Ui) {
-        self.open = !self.open;
-        ui.ctx().request_repaint();
-    }
-}
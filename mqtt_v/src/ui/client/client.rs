@@ -1,90 +1,252 @@
-use backend::message::{
-    Event, FromClient, MqttOpts, Outgoing, Packet, Publish, QoS, ToBackend, ToClient, Topic,
+use backend::{
+    message::{
+        qos_serde, ConnectionState, Delivery, Event, FromClient, MqttError, MqttOpts, Outgoing,
+        Packet, Publish, PublishRef, QoS, ReconnectStatus, SubscriptionStatus, ToBackend, ToClient,
+        Topic,
+    },
+    record::Record,
+    store::{self, MessageStore, StoreItem},
 };
 use chrono::{DateTime, Local};
 use eframe::{
-    egui::{style::Margin, CursorIcon, Frame, Label, Layout, RichText, Sense, Ui},
+    egui::{style::Margin, Button, CursorIcon, Frame, Label, Layout, RichText, Sense, Ui},
     emath::Align,
     epaint::Color32,
 };
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    io, mem,
+    time::Duration,
+};
 use tokio::sync::mpsc::{error::TrySendError, Sender};
 
-use crate::ui::{widgets::status_led::StatusLed, THEME};
+use crate::ui::{decoder::Decoders, widgets::status_led::StatusLed, THEME};
+
+use super::{
+    dashboard::Dashboard,
+    import::{Import, Sent},
+    plot::Plot,
+    profile::Profile,
+    publish_tab::PublishDraft,
+    session::{Recorder, Replay},
+    sparkplug::Sparkplug,
+    stats::{Direction, Stats},
+    topic_tree::TopicTree,
+};
 
 pub enum PacketData {
     Event(Event),
-    PublishPacket(Publish),
+    PublishPacket(PublishRef, Publish),
+    Reconnect(ReconnectStatus),
+    Error(MqttError),
+    /// events the backend dropped while the UI was behind
+    Dropped(u64),
 }
 pub struct ClientPacket {
     pub time: DateTime<Local>,
     pub data: PacketData,
+    /// the client whose last will this publish is
+    pub will_of: Option<String>,
 }
 
+impl StoreItem for ClientPacket {
+    fn size(&self) -> usize {
+        let publish = match &self.data {
+            PacketData::Event(Event::Incoming(Packet::Publish(p))) => p.size(),
+            PacketData::PublishPacket(_, p) => p.size(),
+            _ => 0,
+        };
+        mem::size_of::<ClientPacket>() + publish + self.will_of.as_ref().map_or(0, String::len)
+    }
+
+    /// Only publishes are kept, as the records of a session file
+    fn spill(&self, out: &mut dyn io::Write) -> io::Result<()> {
+        match &self.data {
+            PacketData::Event(Event::Incoming(Packet::Publish(p)))
+            | PacketData::PublishPacket(_, p) => store::spill(out, &Record::new(self.time, p)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// deliveries kept before the first prune
+const PRUNE_MIN: usize = 1024;
+
 pub struct Client {
-    pub connected: bool,
+    pub state: ConnectionState,
     pub options: MqttOpts,
-    pub packets: Vec<ClientPacket>,
+    pub packets: MessageStore<ClientPacket>,
     pub publish_tx: Option<Sender<ToClient>>,
     pub subscriptions: Vec<Subcribe>,
+    /// subscribe to every saved subscription on the first connect
+    pub auto_subscribe: bool,
+    /// a ConnAck was seen, later ones are reconnects
+    connected_before: bool,
+    pub draft: PublishDraft,
     pub recv: u32,
+    /// latest step of the reconnect policy
+    pub reconnect: Option<ReconnectStatus>,
+    /// last connection error and when it happened
+    pub error: Option<(DateTime<Local>, MqttError)>,
+    /// delivery progress of the publishes sent from this client
+    pub deliveries: HashMap<PublishRef, Delivery>,
+    /// size `deliveries` may reach before the finished ones are pruned
+    prune_at: usize,
+    pub topics: TopicTree,
+    pub stats: Stats,
+    pub decoders: Decoders,
+    /// Sparkplug B state, `None` outside of Sparkplug mode
+    pub sparkplug: Option<Sparkplug>,
+    /// payload fields followed by the plot tab
+    pub plots: Vec<Plot>,
+    /// bring the plot tab to front on the next frame
+    pub show_plots: bool,
+    pub dashboard: Dashboard,
+    /// session file the incoming publishes are written to
+    pub recorder: Option<Recorder>,
+    /// session file being republished through this client
+    pub replay: Option<Replay>,
+    /// file of publishes being sent through this client
+    pub import: Option<Import>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Subcribe {
     pub topic: String,
+    #[serde(with = "qos_serde")]
     pub qos: QoS,
     pub color: Color32,
+    #[serde(skip)]
+    pub status: SubscriptionStatus,
+    /// status to go back to if the UNSUBSCRIBE can not be sent
+    #[serde(skip)]
+    pub before_unsubscribe: Option<SubscriptionStatus>,
 }
 
 impl Subcribe {
     pub fn matches(&self, topic: &str) -> bool {
-        let mut topics = topic.split('/');
-        let mut filters = self.topic.split('/');
+        topic_matches(&self.topic, topic)
+    }
+}
 
-        for f in filters.by_ref() {
-            if f == "#" {
-                return true;
-            }
+/// Whether `topic` matches the MQTT topic filter `filter`, `+` and `#` included
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut topics = topic.split('/');
+    let mut filters = filter.split('/');
 
-            let top = topics.next();
-            match top {
-                Some(t) if t == "#" => return false,
-                Some(_) if f == "+" => continue,
-                Some(t) if f != t => return false,
-                Some(_) => continue,
-                None => return false,
-            }
+    for f in filters.by_ref() {
+        if f == "#" {
+            return true;
         }
 
-        topics.next().is_none()
+        let top = topics.next();
+        match top {
+            Some("#") => return false,
+            Some(_) if f == "+" => continue,
+            Some(t) if f != t => return false,
+            Some(_) => continue,
+            None => return false,
+        }
     }
+
+    topics.next().is_none()
 }
 
-pub fn create_client(options: MqttOpts, tx: Sender<ToBackend>) -> Client {
+pub fn create_client(profile: Profile, tx: Sender<ToBackend>) -> Client {
+    let Profile {
+        options,
+        subscriptions,
+        auto_subscribe,
+        draft,
+        store,
+        decoders,
+        schemas,
+        sparkplug,
+        dashboard,
+    } = profile;
     let _ = tx.try_send(ToBackend::NewClient(options.clone()));
     Client {
-        connected: false,
+        state: ConnectionState::Idle,
         options,
-        packets: vec![],
+        packets: MessageStore::new(store),
         publish_tx: None,
-        subscriptions: vec![],
+        subscriptions,
+        auto_subscribe,
+        connected_before: false,
+        draft,
         recv: 0,
+        reconnect: None,
+        error: None,
+        deliveries: HashMap::new(),
+        prune_at: PRUNE_MIN,
+        topics: TopicTree::default(),
+        stats: Stats::default(),
+        decoders: Decoders::new(decoders, schemas),
+        sparkplug: sparkplug.then(Sparkplug::default),
+        plots: vec![],
+        show_plots: false,
+        dashboard,
+        recorder: None,
+        replay: None,
+        import: None,
     }
 }
 
 impl Client {
-    pub fn handle_msg(&mut self, msg: FromClient) {
+    pub fn profile(&self) -> Profile {
+        Profile {
+            options: self.options.clone(),
+            subscriptions: self.subscriptions.clone(),
+            auto_subscribe: self.auto_subscribe,
+            draft: self.draft.clone(),
+            store: self.packets.options().clone(),
+            decoders: self.decoders.rules.clone(),
+            schemas: self
+                .decoders
+                .schemas()
+                .iter()
+                .map(|schema| schema.path.clone())
+                .collect(),
+            sparkplug: self.sparkplug.is_some(),
+            dashboard: self.dashboard.clone(),
+        }
+    }
+
+    /// `will_of` names the profile whose last will an incoming publish matches
+    pub fn handle_msg(&mut self, msg: FromClient, will_of: Option<String>) {
         match msg {
             FromClient::Event(event) => {
+                self.stats.on_event(&event);
                 match &event {
                     Event::Incoming(income) => match income {
                         Packet::Connect(_) => {}
-                        Packet::ConnAck(_) => {
-                            self.connected = true;
-                            self.subcribe_fresh();
+                        Packet::ConnAck(ack) => {
+                            self.error = None;
+                            let first = !mem::replace(&mut self.connected_before, true);
+                            if first && self.auto_subscribe {
+                                self.subcribe_fresh();
+                            } else if first && !ack.session_present {
+                                for s in &mut self.subscriptions {
+                                    s.status = SubscriptionStatus::Inactive;
+                                }
+                            } else if !ack.session_present {
+                                self.subscribe_again();
+                            }
                         }
-                        Packet::Publish(_p) => {
+                        Packet::Publish(p) => {
                             self.recv += 1;
+                            self.topics.insert(Local::now(), p);
+                            if let Some(sparkplug) = &mut self.sparkplug {
+                                sparkplug.on_publish(Local::now(), p);
+                            }
+                            for plot in &mut self.plots {
+                                plot.on_publish(Local::now(), p, &self.decoders);
+                            }
+                            self.dashboard.on_publish(Local::now(), p, &self.decoders);
+                            if let Some(recorder) = &mut self.recorder {
+                                recorder.record(Local::now(), p);
+                            }
                         }
                         Packet::PubAck(_) => {}
                         Packet::PubRec(_) => {}
@@ -96,7 +258,7 @@ impl Client {
                         Packet::UnsubAck(_) => {}
                         Packet::PingReq => {}
                         Packet::PingResp => {}
-                        Packet::Disconnect => self.connected = false,
+                        Packet::Disconnect => {}
                     },
                     Event::Outgoing(outgoing) => match outgoing {
                         Outgoing::Publish(_) => {}
@@ -116,36 +278,221 @@ impl Client {
                 self.packets.push(ClientPacket {
                     time: Local::now(),
                     data: PacketData::Event(event),
+                    will_of,
                 });
             }
-            FromClient::PublishReslt(_timestamp, _result) => {
-                // println!("pub result: {},{:#?}", timestamp, result);
+            FromClient::PublishReslt(id, result) => match result {
+                // the event loop may already have reported it sent
+                Ok(()) => {
+                    self.deliveries.entry(id).or_insert(Delivery::Queued);
+                }
+                Err(e) => {
+                    self.deliveries.insert(id, Delivery::Failed(e.to_string()));
+                }
+            },
+            FromClient::Delivery(id, delivery) => {
+                self.deliveries.insert(id, delivery);
             }
-            FromClient::Disconnected => self.connected = false,
+            FromClient::Subscription(topic, SubscriptionStatus::Unsubscribed) => {
+                self.subscriptions.retain(|x| x.topic != topic);
+            }
+            FromClient::Subscription(topic, status) => {
+                if let Some(sub) = self.subscriptions.iter_mut().find(|x| x.topic == topic) {
+                    match (&sub.status, status) {
+                        // the SubAck of a subscription being dropped, kept in case
+                        // the UNSUBSCRIBE fails
+                        (
+                            SubscriptionStatus::Unsubscribing(_),
+                            status @ (SubscriptionStatus::Pending(_)
+                            | SubscriptionStatus::Granted(_)
+                            | SubscriptionStatus::Refused(_)),
+                        ) => sub.before_unsubscribe = Some(status),
+                        (_, status) => sub.status = status,
+                    }
+                }
+            }
+            FromClient::UnsubscribeFailed(topic, error) => {
+                if let Some(sub) = self.subscriptions.iter_mut().find(|x| x.topic == topic) {
+                    if let Some(status) = sub.before_unsubscribe.take() {
+                        sub.status = status;
+                    }
+                }
+                self.report(error);
+            }
+            FromClient::State(state) => self.state = state,
+            FromClient::Reconnect(status) => {
+                self.reconnect = Some(status.clone());
+                self.packets.push(ClientPacket {
+                    time: Local::now(),
+                    data: PacketData::Reconnect(status),
+                    will_of: None,
+                });
+            }
+            FromClient::Error(error) => self.report(error),
+            FromClient::Dropped(count) => self.packets.push(ClientPacket {
+                time: Local::now(),
+                data: PacketData::Dropped(count),
+                will_of: None,
+            }),
+        }
+    }
+
+    /// Shows `error` in the status bar and the event history
+    fn report(&mut self, error: MqttError) {
+        let time = Local::now();
+        self.error = Some((time, error.clone()));
+        self.packets.push(ClientPacket {
+            time,
+            data: PacketData::Error(error),
+            will_of: None,
+        });
+    }
+
+    /// Drops the acked and failed deliveries no packet in the history and no
+    /// import row shows anymore. Runs each time the map doubled, so the scan
+    /// costs little per publish
+    fn prune_deliveries(&mut self) {
+        if self.deliveries.len() < self.prune_at {
+            return;
+        }
+        let mut shown: HashSet<PublishRef> = self
+            .packets
+            .iter()
+            .filter_map(|pkt| match pkt.data {
+                PacketData::PublishPacket(id, _) => Some(id),
+                _ => None,
+            })
+            .collect();
+        if let Some(import) = &self.import {
+            shown.extend(import.rows.iter().filter_map(|(_, sent)| match sent {
+                Sent::Queued(id) => Some(*id),
+                _ => None,
+            }));
         }
+        self.deliveries.retain(|id, delivery| {
+            shown.contains(id) || !matches!(delivery, Delivery::Acked(_) | Delivery::Failed(_))
+        });
+        self.prune_at = (self.deliveries.len() * 2).max(PRUNE_MIN);
     }
 
-    pub fn subscribe(&mut self, subcribe: Subcribe) {
+    /// Subscribing again to a known filter replaces it, as the broker does
+    pub fn subscribe(&mut self, mut subcribe: Subcribe) {
         if let Some(tx) = &self.publish_tx {
             let _ = tx.try_send(ToClient::Subscribe((subcribe.topic.clone(), subcribe.qos)));
         }
 
-        self.subscriptions.push(subcribe)
+        subcribe.status = SubscriptionStatus::Requested;
+        match self
+            .subscriptions
+            .iter_mut()
+            .find(|x| x.topic == subcribe.topic)
+        {
+            Some(existing) => *existing = subcribe,
+            None => self.subscriptions.push(subcribe),
+        }
+    }
+
+    /// Hands `publish` to the connection and follows its delivery, `None`
+    /// when there is no connection or its queue is full
+    pub fn publish(&mut self, publish: Publish) -> Option<PublishRef> {
+        let tx = self.publish_tx.as_ref()?;
+        let id = PublishRef::new_v4();
+        tx.try_send(ToClient::Publish(id, publish.clone())).ok()?;
+        self.deliveries.insert(id, Delivery::Queued);
+        self.prune_deliveries();
+        self.stats.publish(Direction::Out, &publish);
+        self.packets.push(ClientPacket {
+            time: Local::now(),
+            data: PacketData::PublishPacket(id, publish),
+            will_of: None,
+        });
+        Some(id)
+    }
+
+    /// Sends the replayed and imported publishes that are due, returns when
+    /// the next one is
+    pub fn tick(&mut self) -> Option<Duration> {
+        self.publish_tx.as_ref()?;
+        let mut replay = self.replay.take();
+        let replayed = replay
+            .as_mut()
+            .and_then(|replay| replay.tick(|publish| self.publish(publish).is_some()));
+        self.replay = replay;
+        let mut import = self.import.take();
+        let imported = import
+            .as_mut()
+            .and_then(|import| import.tick(|publish| self.publish(publish)));
+        self.import = import;
+        replayed.into_iter().chain(imported).min()
+    }
+
+    /// Starts following a payload field, filled from the publishes still in
+    /// the history
+    pub fn add_plot(&mut self, mut plot: Plot) {
+        for pkt in self.packets.iter() {
+            if let PacketData::Event(Event::Incoming(Packet::Publish(p))) = &pkt.data {
+                plot.on_publish(pkt.time, p, &self.decoders);
+            }
+        }
+        self.plots.push(plot);
+        self.show_plots = true;
     }
 
     fn subcribe_fresh(&mut self) {
-        for s in &self.subscriptions {
+        for s in &mut self.subscriptions {
             if let Some(tx) = &self.publish_tx {
                 let _ = tx.try_send(ToClient::Subscribe((s.topic.clone(), s.qos)));
             }
+            s.status = SubscriptionStatus::Requested;
         }
     }
 
+    /// After a reconnect without a session the broker forgot every subscription,
+    /// the active ones are asked for again and pending unsubscribes are done
+    fn subscribe_again(&mut self) {
+        self.subscriptions
+            .retain(|s| !matches!(s.status, SubscriptionStatus::Unsubscribing(_)));
+        for s in &mut self.subscriptions {
+            if matches!(
+                s.status,
+                SubscriptionStatus::Requested
+                    | SubscriptionStatus::Pending(_)
+                    | SubscriptionStatus::Granted(_)
+            ) {
+                if let Some(tx) = &self.publish_tx {
+                    let _ = tx.try_send(ToClient::Subscribe((s.topic.clone(), s.qos)));
+                }
+                s.status = SubscriptionStatus::Requested;
+            }
+        }
+    }
+
+    /// The subscription is dropped once the broker acknowledged the UNSUBSCRIBE,
+    /// right away when there is no broker session to ask. A requested one is
+    /// asked for as well, its SUBSCRIBE is already on its way
     pub fn unsubscribe(&mut self, topic: Topic) {
-        if let Some(tx) = &self.publish_tx {
-            if let Ok(()) = tx.try_send(ToClient::Unsubscribe(topic.to_string())) {
+        let Some(sub) = self.subscriptions.iter_mut().find(|x| x.topic == topic) else {
+            return;
+        };
+        let subscribed = matches!(
+            sub.status,
+            SubscriptionStatus::Requested
+                | SubscriptionStatus::Pending(_)
+                | SubscriptionStatus::Granted(_)
+        );
+        let tx = match &self.publish_tx {
+            Some(tx) if subscribed && matches!(self.state, ConnectionState::Connected) => tx,
+            _ => {
                 self.subscriptions.retain(|x| x.topic != topic);
+                return;
+            }
+        };
+        match tx.try_send(ToClient::Unsubscribe(topic.clone())) {
+            Ok(()) => {
+                let status = mem::replace(&mut sub.status, SubscriptionStatus::Unsubscribing(None));
+                sub.before_unsubscribe = Some(status);
             }
+            Err(e) => self.report(MqttError::Request(format!("unsubscribe {}: {}", topic, e))),
         }
     }
 }
@@ -174,28 +521,34 @@ impl Client {
         .show(ui, |ui| {
             ui.set_width(ui.available_width());
             ui.horizontal(|ui| {
-                ui.add(StatusLed::new(&self.connected));
+                ui.add(StatusLed::new(&self.state));
                 let cli_id = Label::new(RichText::new(client_id).color(title_color));
 
                 ui.add(cli_id);
                 ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                    if self.connected {
-                        let disconn_btn = ui.button(RichText::new("🚫").color(Color32::LIGHT_RED));
-                        if disconn_btn.clicked() {
-                            if let Some(tx) = &self.publish_tx {
-                                // channel closed
-                                if let Err(TrySendError::Closed(_)) =
-                                    tx.try_send(ToClient::Disconnect)
-                                {
-                                    self.connected = false
-                                }
+                    let can_connect = matches!(
+                        self.state,
+                        ConnectionState::Idle | ConnectionState::Failed(_)
+                    );
+                    let disconn_btn = ui.add_enabled(
+                        self.state.is_active(),
+                        Button::new(RichText::new("🚫").color(Color32::LIGHT_RED)),
+                    );
+                    if disconn_btn.clicked() {
+                        if let Some(tx) = &self.publish_tx {
+                            // channel closed
+                            if let Err(TrySendError::Closed(_)) = tx.try_send(ToClient::Disconnect)
+                            {
+                                self.state = ConnectionState::Idle
                             }
                         }
-                    } else {
-                        let conn_btn = ui.button(RichText::new("⚡").color(Color32::YELLOW));
-                        if conn_btn.clicked() {
-                            let _ = front_tx.try_send(ToBackend::NewClient(self.options.clone()));
-                        }
+                    }
+                    let conn_btn = ui.add_enabled(
+                        can_connect,
+                        Button::new(RichText::new("⚡").color(Color32::YELLOW)),
+                    );
+                    if conn_btn.clicked() {
+                        let _ = front_tx.try_send(ToBackend::NewClient(self.options.clone()));
                     }
                 });
             });
@@ -204,6 +557,18 @@ impl Client {
                 ui.label("recv: ");
                 ui.colored_label(Color32::YELLOW, self.recv.to_string());
             });
+
+            if let Some((time, error)) = &self.error {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(time.format("%H:%M:%S").to_string()).small());
+                    ui.colored_label(Color32::LIGHT_RED, format!("⚠ {}", error.kind()))
+                        .on_hover_text(error.to_string());
+                });
+            }
+            if let Some(status) = &self.reconnect {
+                let (text, color) = reconnect_label(status);
+                ui.colored_label(color, text);
+            }
         });
         let response = client_frame
             .response
@@ -217,3 +582,216 @@ impl Client {
         }
     }
 }
+
+/// Delivery state shown under a published message
+pub fn delivery_label(delivery: &Delivery) -> (String, Color32) {
+    match delivery {
+        Delivery::Queued => ("⏳ queued".to_owned(), THEME.colors.lighter_gray),
+        Delivery::Sent(0) => ("➡ sent".to_owned(), Color32::LIGHT_BLUE),
+        Delivery::Sent(pkid) => (format!("➡ sent #{}", pkid), Color32::LIGHT_BLUE),
+        Delivery::Received => ("⇄ received".to_owned(), Color32::LIGHT_BLUE),
+        Delivery::Acked(latency) => (
+            format!("✔ acked {} ms", latency.as_millis()),
+            Color32::LIGHT_GREEN,
+        ),
+        Delivery::Failed(_) => ("✖ failed".to_owned(), Color32::LIGHT_RED),
+    }
+}
+
+/// Status icon shown next to a subscription, with its tooltip
+pub fn subscription_label(sub: &Subcribe) -> (String, Color32, String) {
+    match &sub.status {
+        SubscriptionStatus::Inactive => (
+            "○".to_owned(),
+            THEME.colors.lighter_gray,
+            "not subscribed in this session".to_owned(),
+        ),
+        SubscriptionStatus::Requested => (
+            "⏳".to_owned(),
+            THEME.colors.lighter_gray,
+            "requested".to_owned(),
+        ),
+        SubscriptionStatus::Pending(pkid) => (
+            "⏳".to_owned(),
+            Color32::LIGHT_BLUE,
+            format!("waiting for SubAck #{}", pkid),
+        ),
+        SubscriptionStatus::Granted(qos) => {
+            let color = if *qos == sub.qos {
+                Color32::LIGHT_GREEN
+            } else {
+                Color32::YELLOW
+            };
+            (
+                format!("✔ {}", *qos as u8),
+                color,
+                format!("granted QoS {}", *qos as u8),
+            )
+        }
+        SubscriptionStatus::Refused(reason) => ("✖".to_owned(), Color32::LIGHT_RED, reason.clone()),
+        SubscriptionStatus::Unsubscribing(_) => (
+            "⏳".to_owned(),
+            THEME.colors.lighter_gray,
+            "waiting for UnsubAck".to_owned(),
+        ),
+        SubscriptionStatus::Unsubscribed => {
+            ("".to_owned(), THEME.colors.lighter_gray, "".to_owned())
+        }
+    }
+}
+
+/// One line summary of a reconnect step
+pub fn reconnect_label(status: &ReconnectStatus) -> (String, Color32) {
+    match status {
+        ReconnectStatus::Waiting { attempt, delay } => (
+            format!("retry {} in {:.1}s", attempt, delay.as_secs_f32()),
+            Color32::YELLOW,
+        ),
+        ReconnectStatus::Succeeded { attempt } => (
+            format!("reconnected after {} attempt(s)", attempt),
+            Color32::LIGHT_GREEN,
+        ),
+        ReconnectStatus::GaveUp { attempts } => (
+            format!("gave up after {} attempt(s)", attempts),
+            Color32::RED,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::message::{ConnAck, ConnectReturnCode};
+    use tokio::sync::mpsc::{self, Receiver};
+
+    use super::*;
+
+    fn subscription(topic: &str, status: SubscriptionStatus) -> Subcribe {
+        Subcribe {
+            topic: topic.to_owned(),
+            qos: QoS::AtLeastOnce,
+            color: Color32::WHITE,
+            status,
+            before_unsubscribe: None,
+        }
+    }
+
+    fn client(auto_subscribe: bool) -> (Client, Receiver<ToClient>) {
+        let (backend, _) = mpsc::channel(1);
+        let mut client = create_client(
+            Profile {
+                auto_subscribe,
+                ..Profile::default()
+            },
+            backend,
+        );
+        let (tx, rx) = mpsc::channel(16);
+        client.publish_tx = Some(tx);
+        (client, rx)
+    }
+
+    fn conn_ack(client: &mut Client, session_present: bool) {
+        let ack = ConnAck {
+            session_present,
+            code: ConnectReturnCode::Success,
+        };
+        let event = Event::Incoming(Packet::ConnAck(ack));
+        client.handle_msg(FromClient::Event(event), None);
+    }
+
+    fn subscribed(rx: &mut Receiver<ToClient>) -> Vec<String> {
+        let mut topics = vec![];
+        while let Ok(msg) = rx.try_recv() {
+            if let ToClient::Subscribe((topic, _)) = msg {
+                topics.push(topic);
+            }
+        }
+        topics
+    }
+
+    #[test]
+    fn reconnects_without_a_session_subscribe_again() {
+        let (mut client, mut rx) = client(false);
+        client.subscriptions = vec![subscription("saved", SubscriptionStatus::Inactive)];
+        conn_ack(&mut client, false);
+        assert!(subscribed(&mut rx).is_empty());
+
+        client.subscriptions = vec![
+            subscription("saved", SubscriptionStatus::Inactive),
+            subscription("granted", SubscriptionStatus::Granted(QoS::AtLeastOnce)),
+            subscription("pending", SubscriptionStatus::Pending(3)),
+            subscription("refused", SubscriptionStatus::Refused("no".to_owned())),
+            subscription("leaving", SubscriptionStatus::Unsubscribing(Some(4))),
+        ];
+        conn_ack(&mut client, true);
+        assert!(subscribed(&mut rx).is_empty());
+
+        conn_ack(&mut client, false);
+        assert_eq!(subscribed(&mut rx), ["granted", "pending"]);
+        let statuses: Vec<_> = client
+            .subscriptions
+            .iter()
+            .map(|s| {
+                (
+                    s.topic.as_str(),
+                    matches!(s.status, SubscriptionStatus::Requested),
+                )
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("saved", false),
+                ("granted", true),
+                ("pending", true),
+                ("refused", false)
+            ]
+        );
+    }
+
+    #[test]
+    fn auto_subscribe_is_for_the_first_connect() {
+        let (mut client, mut rx) = client(true);
+        client.subscriptions = vec![
+            subscription("a", SubscriptionStatus::Inactive),
+            subscription("b", SubscriptionStatus::Inactive),
+        ];
+        conn_ack(&mut client, false);
+        assert_eq!(subscribed(&mut rx), ["a", "b"]);
+
+        client.subscriptions[1].status = SubscriptionStatus::Refused("no".to_owned());
+        conn_ack(&mut client, false);
+        assert_eq!(subscribed(&mut rx), ["a"]);
+    }
+
+    fn packet(data: PacketData) -> ClientPacket {
+        ClientPacket {
+            time: Local::now(),
+            data,
+            will_of: None,
+        }
+    }
+
+    #[test]
+    fn only_publishes_spill_and_keep_their_payload() {
+        let publish = Publish::new("a/b", QoS::AtLeastOnce, vec![0xff, 0x01]);
+        let mut out = vec![];
+        packet(PacketData::Event(Event::Outgoing(Outgoing::PingReq)))
+            .spill(&mut out)
+            .unwrap();
+        packet(PacketData::Event(Event::Incoming(Packet::Publish(
+            publish.clone(),
+        ))))
+        .spill(&mut out)
+        .unwrap();
+        packet(PacketData::Dropped(3)).spill(&mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let records: Vec<Record> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 1);
+        let read = records[0].publish().unwrap();
+        assert_eq!((read.topic, read.payload), (publish.topic, publish.payload));
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::VecDeque;
+
+use backend::message::{qos_serde, Publish, QoS};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::ui::decoder::Decoders;
+
+use super::{client::topic_matches, topic_tree::TopicTree};
+
+/// values kept for a sparkline
+pub const SPARKLINE: usize = 120;
+
+/// Widgets placed on the dashboard tab, saved with the profile
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Dashboard {
+    pub widgets: Vec<Widget>,
+}
+
+/// A display bound to a field of the payloads of a topic, or a control
+/// publishing to it
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Widget {
+    pub label: String,
+    /// topic or topic filter the value is read from, controls publish to it
+    pub topic: String,
+    /// JSON pointer into the decoded payload, empty for the whole payload
+    pub path: String,
+    pub kind: Kind,
+    /// QoS and retain flag of what controls publish
+    #[serde(with = "qos_serde")]
+    pub qos: QoS,
+    pub retain: bool,
+    #[serde(skip)]
+    pub state: State,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum Kind {
+    Text {
+        unit: String,
+    },
+    Gauge {
+        min: f64,
+        max: f64,
+        unit: String,
+    },
+    Sparkline,
+    /// lit by `true`, a non zero number or `on`
+    Led,
+    /// publishes `payload` when clicked
+    Button {
+        payload: String,
+    },
+    /// publishes `on` or `off`, shown on while the topic holds `on`
+    Toggle {
+        on: String,
+        off: String,
+    },
+    /// publishes the number once released
+    Slider {
+        min: f64,
+        max: f64,
+        step: f64,
+    },
+}
+
+/// What was received for a widget
+#[derive(Clone, Default)]
+pub struct State {
+    pub value: Option<Value>,
+    /// numbers received, oldest first
+    pub history: VecDeque<f64>,
+    pub updated: Option<DateTime<Local>>,
+    /// value being dragged on a slider, published once released
+    pub slider: Option<f64>,
+    /// why the last control publish did not go out
+    pub error: Option<String>,
+}
+
+impl Default for Widget {
+    fn default() -> Self {
+        Self {
+            label: String::new(),
+            topic: String::new(),
+            path: String::new(),
+            kind: Kind::Text {
+                unit: String::new(),
+            },
+            qos: QoS::AtLeastOnce,
+            retain: false,
+            state: State::default(),
+        }
+    }
+}
+
+impl Kind {
+    /// One of each, for the menu adding widgets
+    pub fn all() -> [Kind; 7] {
+        [
+            Kind::Text {
+                unit: String::new(),
+            },
+            Kind::Gauge {
+                min: 0.0,
+                max: 100.0,
+                unit: String::new(),
+            },
+            Kind::Sparkline,
+            Kind::Led,
+            Kind::Button {
+                payload: "1".to_owned(),
+            },
+            Kind::Toggle {
+                on: "ON".to_owned(),
+                off: "OFF".to_owned(),
+            },
+            Kind::Slider {
+                min: 0.0,
+                max: 100.0,
+                step: 1.0,
+            },
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Text { .. } => "text",
+            Kind::Gauge { .. } => "gauge",
+            Kind::Sparkline => "sparkline",
+            Kind::Led => "led",
+            Kind::Button { .. } => "button",
+            Kind::Toggle { .. } => "toggle",
+            Kind::Slider { .. } => "slider",
+        }
+    }
+
+    /// Whether the widget publishes
+    pub fn is_control(&self) -> bool {
+        matches!(
+            self,
+            Kind::Button { .. } | Kind::Toggle { .. } | Kind::Slider { .. }
+        )
+    }
+}
+
+impl Dashboard {
+    pub fn on_publish(&mut self, time: DateTime<Local>, publish: &Publish, decoders: &Decoders) {
+        for widget in &mut self.widgets {
+            widget.on_publish(time, publish, decoders);
+        }
+    }
+}
+
+impl Widget {
+    pub fn new(kind: Kind) -> Self {
+        Self {
+            label: kind.name().to_owned(),
+            kind,
+            ..Self::default()
+        }
+    }
+
+    pub fn on_publish(&mut self, time: DateTime<Local>, publish: &Publish, decoders: &Decoders) {
+        if self.topic.is_empty() || !topic_matches(&self.topic, &publish.topic) {
+            return;
+        }
+        // payloads no decoder makes a tree of, `ON` or `21.5`, are taken as text
+        let tree = decoders
+            .for_topic(&publish.topic)
+            .value(&publish.payload)
+            .unwrap_or_else(|| Value::String(String::from_utf8_lossy(&publish.payload).into()));
+        let Some(value) = tree.pointer(&self.path) else {
+            return;
+        };
+        if let Some(number) = number(value) {
+            if self.state.history.len() == SPARKLINE {
+                self.state.history.pop_front();
+            }
+            self.state.history.push_back(number);
+        }
+        self.state.value = Some(value.clone());
+        self.state.updated = Some(time);
+    }
+
+    /// Starts over from the publishes the topic tree still holds, after the
+    /// topic or the path changed
+    pub fn refill(&mut self, topics: &TopicTree, decoders: &Decoders) {
+        self.state = State::default();
+        if let Some(node) = topics.get(&self.topic) {
+            for (time, publish) in &node.history {
+                self.on_publish(*time, publish, decoders);
+            }
+        }
+    }
+
+    /// The received value as a number, numeric strings included
+    pub fn number(&self) -> Option<f64> {
+        self.state.value.as_ref().and_then(number)
+    }
+
+    /// The received value as shown by a text widget, strings unquoted
+    pub fn text(&self) -> Option<String> {
+        self.state.value.as_ref().map(|value| match value {
+            Value::String(s) => s.clone(),
+            value => value.to_string(),
+        })
+    }
+
+    pub fn lit(&self) -> bool {
+        match &self.state.value {
+            Some(Value::Bool(b)) => *b,
+            Some(Value::Number(n)) => n.as_f64().is_some_and(|n| n != 0.0),
+            Some(Value::String(s)) => {
+                matches!(
+                    s.trim().to_ascii_lowercase().as_str(),
+                    "on" | "true" | "1" | "online"
+                )
+            }
+            _ => false,
+        }
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        Value::Bool(b) => Some(*b as u8 as f64),
+        _ => None,
+    }
+}
@@ -0,0 +1,436 @@
+use std::f32::consts::PI;
+
+use backend::message::{Publish, QoS};
+use eframe::{
+    egui::{self, style::Margin, DragValue, Frame, RichText, ScrollArea, Sense, TextEdit, Ui},
+    emath::{Pos2, Rect, Vec2},
+    epaint::{Color32, Rounding, Shape, Stroke},
+};
+use serde_json::Value;
+
+use crate::ui::{widgets::docking, THEME};
+
+use super::{
+    client::Client,
+    dashboard::{Kind, Widget, SPARKLINE},
+};
+
+/// width of a widget card
+const CARD_WIDTH: f32 = 200.0;
+
+pub struct DashboardTab {
+    /// widgets show their settings instead of their value
+    editing: bool,
+}
+
+/// What a control asked for, carried out once the widgets are drawn
+enum Action {
+    Publish(usize, String),
+    Move(usize, isize),
+    Remove(usize),
+}
+
+impl DashboardTab {
+    pub fn new() -> Self {
+        Self { editing: false }
+    }
+}
+
+impl docking::Tab<Client> for DashboardTab {
+    fn title(&self) -> &str {
+        "🎛 dashboard"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, client: &mut Client) {
+        ui.push_id("dashboard", |ui| {
+            ui.horizontal(|ui| {
+                if ui.selectable_label(self.editing, "✏ edit").clicked() {
+                    self.editing = !self.editing;
+                }
+                if self.editing {
+                    ui.menu_button("➕ widget", |ui| {
+                        for kind in Kind::all() {
+                            if ui.button(kind.name()).clicked() {
+                                client.dashboard.widgets.push(Widget::new(kind));
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                }
+                if client.publish_tx.is_none() {
+                    ui.label(
+                        RichText::new("not connected, controls are disabled")
+                            .color(THEME.colors.lighter_gray),
+                    );
+                }
+            });
+            ui.separator();
+            if client.dashboard.widgets.is_empty() {
+                ui.centered_and_justified(|ui| {
+                    ui.label(
+                        RichText::new("✏ edit to place widgets bound to topics")
+                            .color(THEME.colors.lighter_gray),
+                    );
+                });
+                return;
+            }
+
+            let connected = client.publish_tx.is_some();
+            let count = client.dashboard.widgets.len();
+            let mut actions = vec![];
+            ScrollArea::vertical()
+                .auto_shrink([false; 2])
+                .show(ui, |ui| {
+                    ui.horizontal_wrapped(|ui| {
+                        for (i, widget) in client.dashboard.widgets.iter_mut().enumerate() {
+                            ui.push_id(i, |ui| {
+                                card(ui, |ui| {
+                                    if self.editing {
+                                        if settings(ui, widget) {
+                                            widget.refill(&client.topics, &client.decoders);
+                                        }
+                                        ui.horizontal(|ui| {
+                                            if ui
+                                                .add_enabled(i > 0, egui::Button::new("⬅"))
+                                                .clicked()
+                                            {
+                                                actions.push(Action::Move(i, -1));
+                                            }
+                                            if ui
+                                                .add_enabled(i + 1 < count, egui::Button::new("➡"))
+                                                .clicked()
+                                            {
+                                                actions.push(Action::Move(i, 1));
+                                            }
+                                            if ui.button("ｘ").clicked() {
+                                                actions.push(Action::Remove(i));
+                                            }
+                                        });
+                                    } else if let Some(payload) = show(ui, widget, connected) {
+                                        actions.push(Action::Publish(i, payload));
+                                    }
+                                });
+                            });
+                        }
+                    });
+                });
+
+            for action in actions {
+                match action {
+                    Action::Publish(i, payload) => publish(client, i, payload),
+                    Action::Move(i, by) => {
+                        let j = i.saturating_add_signed(by);
+                        client.dashboard.widgets.swap(i, j);
+                    }
+                    Action::Remove(i) => {
+                        client.dashboard.widgets.remove(i);
+                    }
+                }
+            }
+        });
+    }
+}
+
+fn card(ui: &mut Ui, add_contents: impl FnOnce(&mut Ui)) {
+    Frame {
+        fill: Color32::BLACK,
+        inner_margin: Margin::same(8.0),
+        rounding: Rounding::same(6.0),
+        ..Frame::default()
+    }
+    .show(ui, |ui| {
+        ui.set_width(CARD_WIDTH);
+        ui.vertical(add_contents);
+    });
+}
+
+/// Sends what a control of widget `i` produced, encoded like the publish tab
+/// would for its topic
+fn publish(client: &mut Client, i: usize, payload: String) {
+    let widget = &client.dashboard.widgets[i];
+    let encoded = client.decoders.for_topic(&widget.topic).encode(&payload);
+    let error = match encoded {
+        Ok(bytes) => {
+            let mut publish = Publish::new(widget.topic.clone(), widget.qos, bytes);
+            publish.retain = widget.retain;
+            client
+                .publish(publish)
+                .is_none()
+                .then(|| "not sent, no connection".to_owned())
+        }
+        Err(e) => Some(e),
+    };
+    let state = &mut client.dashboard.widgets[i].state;
+    if error.is_none() {
+        // shown right away, the broker may never echo it back
+        state.value = Some(Value::String(payload));
+    }
+    state.error = error;
+}
+
+/// The widget with its value, returns the payload a control publishes
+fn show(ui: &mut Ui, widget: &mut Widget, connected: bool) -> Option<String> {
+    ui.horizontal(|ui| {
+        ui.label(RichText::new(&widget.label).strong());
+        ui.label(
+            RichText::new(&widget.topic)
+                .small()
+                .color(THEME.colors.lighter_gray),
+        )
+        .on_hover_text(format!("{} {}", widget.topic, widget.path));
+    });
+    ui.add_space(4.0);
+
+    let value = RichText::new(widget.text().unwrap_or_else(|| "-".to_owned()))
+        .color(Color32::YELLOW)
+        .monospace();
+    let mut payload = None;
+    match &widget.kind {
+        Kind::Text { unit } => {
+            ui.horizontal(|ui| {
+                ui.label(value.size(22.0));
+                ui.label(unit);
+            });
+        }
+        Kind::Gauge { min, max, unit } => gauge(ui, widget.number(), *min, *max, unit),
+        Kind::Sparkline => {
+            sparkline(ui, widget.state.history.iter().copied());
+            ui.label(value);
+        }
+        Kind::Led => {
+            ui.horizontal(|ui| {
+                let (rect, _) = ui.allocate_exact_size(Vec2::splat(28.0), Sense::hover());
+                let color = if widget.lit() {
+                    Color32::GREEN
+                } else {
+                    Color32::DARK_GRAY
+                };
+                ui.painter().circle_filled(rect.center(), 12.0, color);
+                ui.label(value);
+            });
+        }
+        Kind::Button { payload: sent } => {
+            let button = egui::Button::new(RichText::new(format!("▶ {}", sent)).size(18.0))
+                .min_size(Vec2::new(CARD_WIDTH, 32.0));
+            if ui.add_enabled(connected, button).clicked() {
+                payload = Some(sent.clone());
+            }
+        }
+        Kind::Toggle { on, off } => {
+            let is_on = widget.text().as_deref() == Some(on.as_str());
+            ui.horizontal(|ui| {
+                let switch = ui.add_enabled_ui(connected, |ui| switch(ui, is_on)).inner;
+                if switch.clicked() {
+                    payload = Some(if is_on { off.clone() } else { on.clone() });
+                }
+                ui.label(value);
+            });
+        }
+        &Kind::Slider { min, max, step } => {
+            let mut number = widget
+                .state
+                .slider
+                .or_else(|| widget.number())
+                .unwrap_or(min);
+            let slider = ui.add_enabled(
+                connected,
+                egui::Slider::new(&mut number, min..=max).step_by(step),
+            );
+            if slider.changed() {
+                widget.state.slider = Some(number);
+            }
+            // clicks and arrow keys change it without a drag
+            if slider.drag_released() || (slider.changed() && !slider.dragged()) {
+                widget.state.slider = None;
+                payload = Some(format_number(number));
+            }
+        }
+    }
+
+    ui.horizontal(|ui| {
+        if let Some(updated) = widget.state.updated {
+            ui.label(
+                RichText::new(updated.format("%H:%M:%S").to_string())
+                    .small()
+                    .color(THEME.colors.lighter_gray),
+            );
+        }
+        if let Some(error) = &widget.state.error {
+            ui.colored_label(Color32::LIGHT_RED, "⚠")
+                .on_hover_text(error);
+        }
+    });
+    payload
+}
+
+/// Settings of the widget, true when the value has to be read again
+fn settings(ui: &mut Ui, widget: &mut Widget) -> bool {
+    let mut changed = false;
+    egui::Grid::new("widget_settings")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label(RichText::new(widget.kind.name()).strong());
+            ui.add(TextEdit::singleline(&mut widget.label).hint_text("label"));
+            ui.end_row();
+            ui.label("topic");
+            changed |= ui
+                .add(TextEdit::singleline(&mut widget.topic).hint_text("sensors/+/state"))
+                .changed();
+            ui.end_row();
+            ui.label("path");
+            changed |= ui
+                .add(TextEdit::singleline(&mut widget.path).hint_text("/temperature"))
+                .changed();
+            ui.end_row();
+
+            match &mut widget.kind {
+                Kind::Text { unit } => {
+                    ui.label("unit");
+                    ui.text_edit_singleline(unit);
+                    ui.end_row();
+                }
+                Kind::Gauge { min, max, unit } => {
+                    ui.label("range");
+                    ui.horizontal(|ui| {
+                        ui.add(DragValue::new(min));
+                        ui.add(DragValue::new(max));
+                    });
+                    ui.end_row();
+                    ui.label("unit");
+                    ui.text_edit_singleline(unit);
+                    ui.end_row();
+                }
+                Kind::Sparkline | Kind::Led => {}
+                Kind::Button { payload } => {
+                    ui.label("payload");
+                    ui.text_edit_singleline(payload);
+                    ui.end_row();
+                }
+                Kind::Toggle { on, off } => {
+                    ui.label("on");
+                    ui.text_edit_singleline(on);
+                    ui.end_row();
+                    ui.label("off");
+                    ui.text_edit_singleline(off);
+                    ui.end_row();
+                }
+                Kind::Slider { min, max, step } => {
+                    ui.label("range");
+                    ui.horizontal(|ui| {
+                        ui.add(DragValue::new(min));
+                        ui.add(DragValue::new(max));
+                    });
+                    ui.end_row();
+                    ui.label("step");
+                    ui.add(DragValue::new(step).speed(0.1).clamp_range(0.0..=f64::MAX));
+                    ui.end_row();
+                }
+            }
+            if widget.kind.is_control() {
+                ui.label("QoS");
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut widget.qos, QoS::AtMostOnce, "0");
+                    ui.radio_value(&mut widget.qos, QoS::AtLeastOnce, "1");
+                    ui.radio_value(&mut widget.qos, QoS::ExactlyOnce, "2");
+                    ui.checkbox(&mut widget.retain, "retain");
+                });
+                ui.end_row();
+            }
+        });
+    changed
+}
+
+/// Half circle filled up to `value` between `min` and `max`
+fn gauge(ui: &mut Ui, value: Option<f64>, min: f64, max: f64, unit: &str) {
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(CARD_WIDTH, 100.0), Sense::hover());
+    let center = Pos2::new(rect.center().x, rect.bottom() - 10.0);
+    let radius = 70.0;
+    let arc = |from: f32, to: f32| -> Vec<Pos2> {
+        (0..=32)
+            .map(|i| {
+                let angle = PI - (from + (to - from) * i as f32 / 32.0) * PI;
+                center + radius * Vec2::new(angle.cos(), -angle.sin())
+            })
+            .collect()
+    };
+    let painter = ui.painter();
+    painter.add(Shape::line(
+        arc(0.0, 1.0),
+        Stroke::new(12.0, Color32::DARK_GRAY),
+    ));
+    let text = match value {
+        Some(value) => {
+            let fraction = ((value - min) / (max - min)).clamp(0.0, 1.0) as f32;
+            if fraction > 0.0 {
+                painter.add(Shape::line(
+                    arc(0.0, fraction),
+                    Stroke::new(12.0, Color32::LIGHT_GREEN),
+                ));
+            }
+            format!("{} {}", format_number(value), unit)
+        }
+        None => "-".to_owned(),
+    };
+    painter.text(
+        center - Vec2::new(0.0, 16.0),
+        egui::Align2::CENTER_CENTER,
+        text,
+        egui::FontId::monospace(18.0),
+        Color32::YELLOW,
+    );
+}
+
+/// The received numbers as a line scaled to their range
+fn sparkline(ui: &mut Ui, values: impl ExactSizeIterator<Item = f64> + Clone) {
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(CARD_WIDTH, 48.0), Sense::hover());
+    ui.painter()
+        .rect_filled(rect, Rounding::same(2.0), THEME.colors.dark_gray);
+    if values.len() < 2 {
+        return;
+    }
+    let (low, high) = values.clone().fold((f64::MAX, f64::MIN), |(low, high), v| {
+        (low.min(v), high.max(v))
+    });
+    let span = if high > low { high - low } else { 1.0 };
+    let rect = rect.shrink(4.0);
+    let points = values
+        .enumerate()
+        .map(|(i, v)| {
+            Pos2::new(
+                rect.left() + rect.width() * i as f32 / (SPARKLINE - 1) as f32,
+                rect.bottom() - rect.height() * ((v - low) / span) as f32,
+            )
+        })
+        .collect();
+    ui.painter()
+        .add(Shape::line(points, Stroke::new(1.5, Color32::LIGHT_GREEN)));
+}
+
+/// An on/off switch, click it to flip
+fn switch(ui: &mut Ui, on: bool) -> egui::Response {
+    let size = Vec2::new(44.0, 22.0);
+    let (rect, response) = ui.allocate_exact_size(size, Sense::click());
+    let t = ui.ctx().animate_bool(response.id, on);
+    let visuals = ui.style().interact_selectable(&response, on);
+    let radius = rect.height() / 2.0;
+    ui.painter()
+        .rect(rect, radius, visuals.bg_fill, visuals.bg_stroke);
+    let x = egui::lerp((rect.left() + radius)..=(rect.right() - radius), t);
+    let knob = Rect::from_center_size(Pos2::new(x, rect.center().y), Vec2::splat(2.0 * radius));
+    ui.painter().circle(
+        knob.center(),
+        0.8 * radius,
+        visuals.fg_stroke.color,
+        Stroke::NONE,
+    );
+    response
+}
+
+/// Whole numbers without a fraction, as written by hand
+fn format_number(number: f64) -> String {
+    if number.fract() == 0.0 && number.abs() < 1e15 {
+        format!("{}", number as i64)
+    } else {
+        number.to_string()
+    }
+}
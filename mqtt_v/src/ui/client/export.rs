@@ -0,0 +1,280 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use backend::message::{Event, Packet};
+use chrono::SecondsFormat;
+use serde::Serialize;
+
+use super::{
+    client::{ClientPacket, PacketData},
+    filter::publish_of,
+    session::Record,
+    stats::{incoming_kind, outgoing_kind},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// publishes as session records, replayable
+    Ndjson,
+    /// publishes as `topic,time,qos,retain,payload` rows, payloads as text
+    Csv,
+    /// every packet and status change, one JSON object per line
+    Log,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::Ndjson, Format::Csv, Format::Log];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Ndjson => "ndjson",
+            Format::Csv => "csv",
+            Format::Log => "log",
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Format::Ndjson => "publishes as JSON lines, binary payloads in base64",
+            Format::Csv => "publishes as CSV, payloads as text",
+            Format::Log => "every packet as JSON lines, not only publishes",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Ndjson | Format::Log => "ndjson",
+            Format::Csv => "csv",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+}
+
+/// A line of the packet log
+#[derive(Serialize)]
+struct LogEntry<'a> {
+    time: String,
+    /// `in`, `out`, or `client` for reconnects and errors
+    direction: &'static str,
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    publish: Option<Record>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    will_of: Option<&'a str>,
+    /// the packet as printed by the client library
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+/// Writes packets in one of the export formats as they come
+pub struct Exporter<W: Write> {
+    out: Out<W>,
+    /// packets written, publishes only unless the format is `Log`
+    pub count: usize,
+}
+
+enum Out<W: Write> {
+    Ndjson(W),
+    Csv(Box<csv::Writer<W>>),
+    Log(W),
+}
+
+impl Exporter<BufWriter<File>> {
+    pub fn create(format: Format, path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        Exporter::new(format, BufWriter::new(file))
+    }
+}
+
+impl<W: Write> Exporter<W> {
+    pub fn new(format: Format, out: W) -> Result<Self, String> {
+        let out = match format {
+            Format::Ndjson => Out::Ndjson(out),
+            Format::Csv => {
+                let mut csv = csv::Writer::from_writer(out);
+                csv.write_record(["topic", "time", "qos", "retain", "payload"])
+                    .map_err(|e| e.to_string())?;
+                Out::Csv(Box::new(csv))
+            }
+            Format::Log => Out::Log(out),
+        };
+        Ok(Self { out, count: 0 })
+    }
+
+    pub fn write(&mut self, pkt: &ClientPacket) -> Result<(), String> {
+        let written = match &mut self.out {
+            Out::Ndjson(out) => match publish_of(&pkt.data) {
+                Some(publish) => line(out, &Record::new(pkt.time, publish)),
+                None => return Ok(()),
+            },
+            Out::Csv(csv) => match publish_of(&pkt.data) {
+                Some(publish) => csv
+                    .write_record([
+                        publish.topic.as_str(),
+                        &pkt.time.to_rfc3339_opts(SecondsFormat::Millis, false),
+                        &(publish.qos as u8).to_string(),
+                        &publish.retain.to_string(),
+                        &String::from_utf8_lossy(&publish.payload),
+                    ])
+                    .map_err(io::Error::from),
+                None => return Ok(()),
+            },
+            Out::Log(out) => line(out, &log_entry(pkt)),
+        };
+        written.map_err(|e| e.to_string())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Flushes what is buffered, returns the count of packets written
+    pub fn finish(self) -> Result<usize, String> {
+        let flushed = match self.out {
+            Out::Ndjson(mut out) | Out::Log(mut out) => out.flush(),
+            Out::Csv(mut csv) => csv.flush(),
+        };
+        flushed.map_err(|e| e.to_string())?;
+        Ok(self.count)
+    }
+}
+
+/// Writes `packets` to the file at `path`, returns how many went in
+pub fn export<'a>(
+    format: Format,
+    path: &str,
+    packets: impl IntoIterator<Item = &'a ClientPacket>,
+) -> Result<usize, String> {
+    let mut exporter = Exporter::create(format, path)?;
+    for pkt in packets {
+        exporter.write(pkt)?;
+    }
+    exporter.finish()
+}
+
+fn line(out: &mut impl Write, value: &impl Serialize) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, value)?;
+    out.write_all(b"\n")
+}
+
+fn log_entry(pkt: &ClientPacket) -> LogEntry<'_> {
+    let (direction, kind, detail) = match &pkt.data {
+        PacketData::Event(Event::Incoming(Packet::Publish(_))) => ("in", "Publish", None),
+        PacketData::Event(Event::Incoming(packet)) => {
+            ("in", incoming_kind(packet), Some(format!("{:?}", packet)))
+        }
+        PacketData::Event(Event::Outgoing(outgoing)) => (
+            "out",
+            outgoing_kind(outgoing),
+            Some(format!("{:?}", outgoing)),
+        ),
+        PacketData::PublishPacket(id, _) => ("out", "Publish", Some(id.to_string())),
+        PacketData::Reconnect(status) => ("client", "Reconnect", Some(format!("{:?}", status))),
+        PacketData::Error(error) => ("client", "Error", Some(error.to_string())),
+        PacketData::Dropped(count) => ("client", "Dropped", Some(count.to_string())),
+    };
+    LogEntry {
+        time: pkt.time.to_rfc3339_opts(SecondsFormat::Millis, false),
+        direction,
+        kind,
+        publish: publish_of(&pkt.data).map(|publish| Record::new(pkt.time, publish)),
+        will_of: pkt.will_of.as_deref(),
+        detail,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::message::{Outgoing, Publish, QoS};
+    use chrono::{Local, TimeZone};
+
+    use super::*;
+
+    fn packet(data: PacketData) -> ClientPacket {
+        ClientPacket {
+            time: Local.timestamp_millis_opt(1_700_000_000_000).unwrap(),
+            data,
+            will_of: None,
+        }
+    }
+
+    fn packets() -> Vec<ClientPacket> {
+        let text = Publish::new("a/b", QoS::AtLeastOnce, "hi, \"there\"");
+        let mut binary = Publish::new("c", QoS::AtMostOnce, vec![0xff, 0x00]);
+        binary.retain = true;
+        vec![
+            packet(PacketData::Event(Event::Incoming(Packet::Publish(text)))),
+            packet(PacketData::Event(Event::Outgoing(Outgoing::PingReq))),
+            packet(PacketData::PublishPacket(Default::default(), binary)),
+            packet(PacketData::Dropped(3)),
+        ]
+    }
+
+    fn written(format: Format) -> (usize, Vec<String>) {
+        let mut out = vec![];
+        let mut exporter = Exporter::new(format, &mut out).unwrap();
+        for pkt in &packets() {
+            exporter.write(pkt).unwrap();
+        }
+        let count = exporter.finish().unwrap();
+        let text = String::from_utf8(out).unwrap();
+        (count, text.lines().map(str::to_owned).collect())
+    }
+
+    #[test]
+    fn ndjson_is_replayable() {
+        let (count, lines) = written(Format::Ndjson);
+        assert_eq!(count, 2);
+        let records: Vec<Record> = lines
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records[0].payload, "hi, \"there\"");
+        assert!(!records[0].base64);
+        assert!(records[1].base64 && records[1].retain);
+        assert_eq!(records[1].publish().unwrap().payload.as_ref(), [0xff, 0x00]);
+    }
+
+    #[test]
+    fn csv_quotes_payloads() {
+        let (count, lines) = written(Format::Csv);
+        assert_eq!(count, 2);
+        assert_eq!(lines[0], "topic,time,qos,retain,payload");
+        assert!(lines[1].starts_with("a/b,"));
+        assert!(lines[1].ends_with(",1,false,\"hi, \"\"there\"\"\""));
+        assert!(lines[2].starts_with("c,") && lines[2].contains(",0,true,"));
+    }
+
+    #[test]
+    fn log_has_every_packet() {
+        let (count, lines) = written(Format::Log);
+        assert_eq!(count, 4);
+        let entries: Vec<serde_json::Value> = lines
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let kinds: Vec<_> = entries
+            .iter()
+            .map(|e| {
+                (
+                    e["direction"].as_str().unwrap(),
+                    e["kind"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("in", "Publish"),
+                ("out", "PingReq"),
+                ("out", "Publish"),
+                ("client", "Dropped")
+            ]
+        );
+        assert_eq!(entries[0]["publish"]["topic"], "a/b");
+        assert!(entries[1].get("publish").is_none());
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::VecDeque;
+
+use backend::{
+    message::{Event, Outgoing, Packet, Publish},
+    store::MessageStore,
+};
+use regex::bytes::Regex;
+
+use super::client::{topic_matches, ClientPacket, PacketData};
+
+/// What the Event tab shows, topic, payload, QoS and retain only apply to publishes
+#[derive(Clone, PartialEq)]
+pub struct Filter {
+    pub direct: Direct,
+    /// MQTT topic filter, empty shows every topic
+    pub topic: String,
+    /// payload substring, or a regular expression when `regex` is set
+    pub content: String,
+    pub regex: bool,
+    pub kinds: Kinds,
+    pub qos: [bool; 3],
+    pub retain: Retain,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Direct {
+    pub published: bool,
+    pub received: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Kinds {
+    pub publish: bool,
+    /// PubAck, PubRec, PubRel, PubComp, SubAck and UnsubAck
+    pub ack: bool,
+    pub ping: bool,
+    /// connect, subscribe and disconnect packets
+    pub control: bool,
+    /// reconnect steps and connection errors
+    pub status: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Retain {
+    #[default]
+    Any,
+    Only,
+    Without,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            direct: Direct {
+                published: true,
+                received: true,
+            },
+            topic: String::new(),
+            content: String::new(),
+            regex: false,
+            kinds: Kinds {
+                publish: true,
+                ack: true,
+                ping: true,
+                control: true,
+                status: true,
+            },
+            qos: [true; 3],
+            retain: Retain::Any,
+        }
+    }
+}
+
+impl Filter {
+    fn matches(&self, pkt: &ClientPacket, content: &Content) -> bool {
+        let direct = match &pkt.data {
+            PacketData::Event(Event::Incoming(_)) => self.direct.received,
+            PacketData::Event(Event::Outgoing(_)) | PacketData::PublishPacket(..) => {
+                self.direct.published
+            }
+            PacketData::Reconnect(_) | PacketData::Error(_) | PacketData::Dropped(_) => true,
+        };
+        let kind = match &pkt.data {
+            PacketData::Event(Event::Incoming(packet)) => match packet {
+                Packet::Publish(_) => self.kinds.publish,
+                Packet::PubAck(_)
+                | Packet::PubRec(_)
+                | Packet::PubRel(_)
+                | Packet::PubComp(_)
+                | Packet::SubAck(_)
+                | Packet::UnsubAck(_) => self.kinds.ack,
+                Packet::PingReq | Packet::PingResp => self.kinds.ping,
+                _ => self.kinds.control,
+            },
+            PacketData::Event(Event::Outgoing(outgoing)) => match outgoing {
+                Outgoing::Publish(_) => self.kinds.publish,
+                Outgoing::PubAck(_)
+                | Outgoing::PubRec(_)
+                | Outgoing::PubRel(_)
+                | Outgoing::PubComp(_) => self.kinds.ack,
+                Outgoing::PingReq | Outgoing::PingResp => self.kinds.ping,
+                _ => self.kinds.control,
+            },
+            PacketData::PublishPacket(..) => self.kinds.publish,
+            PacketData::Reconnect(_) | PacketData::Error(_) | PacketData::Dropped(_) => {
+                self.kinds.status
+            }
+        };
+        if !direct || !kind {
+            return false;
+        }
+
+        let Some(publish) = publish_of(&pkt.data) else {
+            // rows without a topic or payload can not match a search for one
+            return self.topic.is_empty() && matches!(content, Content::Any);
+        };
+        (self.topic.is_empty() || topic_matches(&self.topic, &publish.topic))
+            && content.matches(&publish.payload)
+            && self.qos[publish.qos as usize]
+            && match self.retain {
+                Retain::Any => true,
+                Retain::Only => publish.retain,
+                Retain::Without => !publish.retain,
+            }
+    }
+}
+
+pub fn publish_of(data: &PacketData) -> Option<&Publish> {
+    match data {
+        PacketData::Event(Event::Incoming(Packet::Publish(p))) => Some(p),
+        PacketData::PublishPacket(_, p) => Some(p),
+        _ => None,
+    }
+}
+
+/// The payload search of a `Filter`, compiled once
+enum Content {
+    Any,
+    Text(Vec<u8>),
+    Regex(Regex),
+    /// an invalid regular expression hides every publish
+    Invalid(String),
+}
+
+impl Content {
+    fn new(filter: &Filter) -> Self {
+        if filter.content.is_empty() {
+            Content::Any
+        } else if filter.regex {
+            match Regex::new(&filter.content) {
+                Ok(regex) => Content::Regex(regex),
+                Err(e) => Content::Invalid(e.to_string()),
+            }
+        } else {
+            Content::Text(filter.content.as_bytes().to_vec())
+        }
+    }
+
+    fn matches(&self, payload: &[u8]) -> bool {
+        match self {
+            Content::Any => true,
+            Content::Text(text) => payload.windows(text.len()).any(|w| w == text),
+            Content::Regex(regex) => regex.is_match(payload),
+            Content::Invalid(_) => false,
+        }
+    }
+}
+
+/// Sequence numbers of the stored packets passing a `Filter`.
+///
+/// Only packets stored since the last update are checked, everything is
+/// checked again when the filter or the client changes.
+pub struct Filtered {
+    applied: Option<(String, Filter)>,
+    content: Content,
+    rows: VecDeque<u64>,
+    /// sequence number of the first packet not checked yet
+    next: u64,
+}
+
+impl Default for Filtered {
+    fn default() -> Self {
+        Self {
+            applied: None,
+            content: Content::Any,
+            rows: VecDeque::new(),
+            next: 0,
+        }
+    }
+}
+
+impl Filtered {
+    pub fn update(&mut self, client_id: &str, filter: &Filter, store: &MessageStore<ClientPacket>) {
+        let changed = match &self.applied {
+            Some((id, applied)) => id != client_id || applied != filter,
+            None => true,
+        };
+        if changed {
+            self.content = Content::new(filter);
+            self.rows.clear();
+            self.next = 0;
+            self.applied = Some((client_id.to_owned(), filter.clone()));
+        }
+
+        let first = store.seq(0);
+        while matches!(self.rows.front(), Some(seq) if *seq < first) {
+            self.rows.pop_front();
+        }
+
+        let end = store.seq(store.len());
+        for seq in self.next.max(first)..end {
+            let Some(pkt) = store.index_of(seq).and_then(|i| store.get(i)) else {
+                continue;
+            };
+            if filter.matches(pkt, &self.content) {
+                self.rows.push_back(seq);
+            }
+        }
+        self.next = end;
+    }
+
+    pub fn rows(&self) -> &VecDeque<u64> {
+        &self.rows
+    }
+
+    /// Why the payload regular expression does not compile
+    pub fn error(&self) -> Option<&str> {
+        match &self.content {
+            Content::Invalid(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::{
+        message::{MqttError, QoS},
+        store::{StoreLimit, StoreOptions},
+    };
+    use chrono::Local;
+
+    use super::*;
+
+    fn incoming(topic: &str, payload: &str, qos: QoS, retain: bool) -> ClientPacket {
+        let mut publish = Publish::new(topic, qos, payload);
+        publish.retain = retain;
+        packet(PacketData::Event(Event::Incoming(Packet::Publish(publish))))
+    }
+
+    fn packet(data: PacketData) -> ClientPacket {
+        ClientPacket {
+            time: Local::now(),
+            data,
+            will_of: None,
+        }
+    }
+
+    fn history(limit: usize) -> MessageStore<ClientPacket> {
+        MessageStore::new(StoreOptions {
+            limit: StoreLimit::Count(limit),
+            overflow_path: String::new(),
+        })
+    }
+
+    fn rows(filtered: &Filtered) -> Vec<u64> {
+        filtered.rows().iter().copied().collect()
+    }
+
+    #[test]
+    fn topic_filters() {
+        assert!(topic_matches("#", "a/b"));
+        assert!(topic_matches("a/#", "a"));
+        assert!(topic_matches("a/#", "a/b/c"));
+        assert!(topic_matches("a/+/c", "a/b/c"));
+        assert!(topic_matches("+/+", "a/b"));
+        assert!(!topic_matches("a/+", "a/b/c"));
+        assert!(!topic_matches("a/b", "a"));
+        assert!(!topic_matches("a/b", "a/b/c"));
+        assert!(!topic_matches("b/#", "a/b"));
+    }
+
+    #[test]
+    fn only_new_packets_are_checked_and_evicted_rows_go() {
+        let mut store = history(3);
+        let mut filtered = Filtered::default();
+        let filter = Filter {
+            topic: "a/#".to_owned(),
+            ..Filter::default()
+        };
+        store.push(incoming("a/1", "", QoS::AtMostOnce, false));
+        store.push(incoming("b", "", QoS::AtMostOnce, false));
+        filtered.update("client", &filter, &store);
+        assert_eq!(rows(&filtered), [0]);
+
+        store.push(incoming("a/2", "", QoS::AtMostOnce, false));
+        store.push(incoming("a/3", "", QoS::AtMostOnce, false));
+        filtered.update("client", &filter, &store);
+        // a/1 was evicted
+        assert_eq!(rows(&filtered), [2, 3]);
+
+        // another client starts over
+        let mut other = history(3);
+        other.push(incoming("a/x", "", QoS::AtMostOnce, false));
+        filtered.update("other", &filter, &other);
+        assert_eq!(rows(&filtered), [0]);
+    }
+
+    #[test]
+    fn payload_qos_and_retain() {
+        let mut store = history(10);
+        store.push(incoming("t", "temperature 21", QoS::AtMostOnce, false));
+        store.push(incoming("t", "humidity 40", QoS::AtLeastOnce, true));
+        store.push(packet(PacketData::Error(MqttError::Timeout)));
+        let mut filtered = Filtered::default();
+
+        let text = Filter {
+            content: "humid".to_owned(),
+            ..Filter::default()
+        };
+        filtered.update("c", &text, &store);
+        assert_eq!(rows(&filtered), [1]);
+
+        let regex = Filter {
+            content: r"\d{2}$".to_owned(),
+            regex: true,
+            ..Filter::default()
+        };
+        filtered.update("c", &regex, &store);
+        assert_eq!(rows(&filtered), [0, 1]);
+
+        let invalid = Filter {
+            content: "(".to_owned(),
+            ..regex
+        };
+        filtered.update("c", &invalid, &store);
+        assert!(rows(&filtered).is_empty());
+        assert!(filtered.error().is_some());
+
+        let retained = Filter {
+            retain: Retain::Only,
+            ..Filter::default()
+        };
+        filtered.update("c", &retained, &store);
+        // status rows have no retain flag to compare
+        assert_eq!(rows(&filtered), [1, 2]);
+
+        let qos0 = Filter {
+            qos: [true, false, false],
+            kinds: Kinds {
+                status: false,
+                ..Filter::default().kinds
+            },
+            ..Filter::default()
+        };
+        filtered.update("c", &qos0, &store);
+        assert_eq!(rows(&filtered), [0]);
+    }
+}
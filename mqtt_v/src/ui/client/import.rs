@@ -0,0 +1,284 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    time::{Duration, Instant},
+};
+
+use backend::{
+    message::{qos_serde, Publish, PublishRef, QoS},
+    record::Record,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// rows published in one frame at most, the rest wait for the next one
+const IMPORT_BATCH: usize = 200;
+
+/// A publish to send, read from a line of NDJSON or a CSV row. Exported
+/// history reads back as well, its extra fields are ignored
+#[derive(Deserialize)]
+pub struct Row {
+    pub topic: String,
+    #[serde(default)]
+    pub payload: String,
+    /// `payload` is base64, for binary payloads
+    #[serde(default)]
+    pub base64: bool,
+    #[serde(default = "at_least_once", with = "qos_serde")]
+    pub qos: QoS,
+    #[serde(default)]
+    pub retain: bool,
+    /// wait before this row is published
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+fn at_least_once() -> QoS {
+    QoS::AtLeastOnce
+}
+
+/// What became of a row
+pub enum Sent {
+    Waiting,
+    /// handed to the client, the delivery is followed by the client
+    Queued(PublishRef),
+    Failed(String),
+}
+
+/// A file of publishes being sent in order through a client
+pub struct Import {
+    pub path: String,
+    pub rows: Vec<(Row, Sent)>,
+    /// next row to send
+    pub next: usize,
+    pub paused: bool,
+    /// when the next row is due, its delay counts from the previous row
+    due: Option<Instant>,
+}
+
+impl Row {
+    /// Decoded as a session record, rows have no time of their own
+    fn publish(&self) -> Result<Publish, String> {
+        Record {
+            time: String::new(),
+            topic: self.topic.clone(),
+            qos: self.qos,
+            retain: self.retain,
+            payload: self.payload.clone(),
+            base64: self.base64,
+        }
+        .publish()
+    }
+}
+
+impl Import {
+    /// Reads `.csv` files as CSV with a header row, anything else as NDJSON
+    pub fn load(path: String) -> Result<Self, String> {
+        let file = File::open(&path).map_err(|e| format!("{}: {}", path, e))?;
+        let rows = if path.ends_with(".csv") {
+            csv::Reader::from_reader(file)
+                .deserialize()
+                .enumerate()
+                // the header is line 1
+                .map(|(i, row)| row.map_err(|e| format!("line {}: {}", i + 2, e)))
+                .collect::<Result<Vec<Row>, _>>()?
+        } else {
+            let mut rows = vec![];
+            for (i, line) in BufReader::new(file).lines().enumerate() {
+                let line = line.map_err(|e| e.to_string())?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                rows.push(ndjson_row(&line).map_err(|e| format!("line {}: {}", i + 1, e))?);
+            }
+            rows
+        };
+        if rows.is_empty() {
+            return Err(format!("{}: no rows", path));
+        }
+        Ok(Self {
+            path,
+            rows: rows.into_iter().map(|row| (row, Sent::Waiting)).collect(),
+            next: 0,
+            paused: true,
+            due: None,
+        })
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next >= self.rows.len()
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.due = None;
+    }
+
+    /// Starts over, the results of the previous run are dropped
+    pub fn restart(&mut self) {
+        for (_, sent) in &mut self.rows {
+            *sent = Sent::Waiting;
+        }
+        self.next = 0;
+        self.resume();
+    }
+
+    /// Hands the rows that are due to `publish` until it refuses one.
+    /// Returns how long until the next one is due, `None` once paused or done
+    pub fn tick(
+        &mut self,
+        mut publish: impl FnMut(Publish) -> Option<PublishRef>,
+    ) -> Option<Duration> {
+        if self.paused {
+            return None;
+        }
+        for _ in 0..IMPORT_BATCH {
+            let (row, sent) = self.rows.get_mut(self.next)?;
+            let due = *self
+                .due
+                .get_or_insert_with(|| Instant::now() + Duration::from_millis(row.delay_ms));
+            let now = Instant::now();
+            if due > now {
+                return Some(due - now);
+            }
+            *sent = match row.publish() {
+                Ok(p) => match publish(p) {
+                    Some(id) => Sent::Queued(id),
+                    // the client queue is full, try again shortly
+                    None => return Some(Duration::from_millis(10)),
+                },
+                Err(e) => Sent::Failed(e),
+            };
+            self.next += 1;
+            self.due = None;
+        }
+        Some(Duration::ZERO)
+    }
+}
+
+/// A line of NDJSON, payloads that are JSON rather than a string are sent as
+/// compact JSON
+fn ndjson_row(line: &str) -> Result<Row, String> {
+    let mut value: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    match value.get_mut("payload") {
+        Some(payload @ Value::Null) => *payload = Value::String(String::new()),
+        Some(payload) if !payload.is_string() => *payload = Value::String(payload.to_string()),
+        _ => {}
+    }
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("mqtt_v-{}-{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn load(name: &str, content: &str) -> Result<Import, String> {
+        let path = file(name, content);
+        let import = Import::load(path.clone());
+        std::fs::remove_file(path).unwrap();
+        import
+    }
+
+    #[test]
+    fn csv_rows_by_header() {
+        // an exported history, with its time column
+        let import = load(
+            "rows.csv",
+            "topic,time,qos,retain,payload\n\
+             a/b,2024-01-01T00:00:00Z,2,true,\"hi, there\"\n\
+             c,,0,false,\n",
+        )
+        .unwrap();
+        let rows: Vec<_> = import.rows.iter().map(|(row, _)| row).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].topic, "a/b");
+        assert_eq!(rows[0].payload, "hi, there");
+        assert_eq!((rows[0].qos, rows[0].retain), (QoS::ExactlyOnce, true));
+        assert_eq!((rows[1].payload.as_str(), rows[1].delay_ms), ("", 0));
+        assert!(import.paused && import.next == 0);
+    }
+
+    #[test]
+    fn ndjson_payloads() {
+        let import = load(
+            "rows.ndjson",
+            r#"{"topic":"j","payload":{"a":[1,2]},"delay_ms":5}
+
+{"topic":"n","payload":null}
+{"topic":"b","payload":"AAE=","base64":true}
+{"topic":"t","payload":"text","qos":0}
+"#,
+        )
+        .unwrap();
+        let publishes: Vec<_> = import
+            .rows
+            .iter()
+            .map(|(row, _)| row.publish().unwrap())
+            .collect();
+        assert_eq!(publishes[0].payload.as_ref(), br#"{"a":[1,2]}"#);
+        assert_eq!(import.rows[0].0.delay_ms, 5);
+        assert!(publishes[1].payload.is_empty());
+        assert_eq!(publishes[2].payload.as_ref(), [0, 1]);
+        // QoS 1 unless given
+        assert_eq!(publishes[2].qos, QoS::AtLeastOnce);
+        assert_eq!(publishes[3].qos, QoS::AtMostOnce);
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let e = load("bad.ndjson", "{\"topic\":\"a\"}\n{\"payload\":\"x\"}\n").err();
+        assert!(e.unwrap().starts_with("line 2:"));
+        let e = load("bad.csv", "topic,qos\na,1\nb,7\n").err();
+        assert!(e.unwrap().starts_with("line 3:"));
+        assert!(load("empty.ndjson", "\n")
+            .err()
+            .unwrap()
+            .ends_with("no rows"));
+        assert!(Import::load("/nonexistent/rows.ndjson".to_owned()).is_err());
+    }
+
+    #[test]
+    fn sends_in_order_with_delays_and_retries() {
+        let mut import = load(
+            "send.ndjson",
+            r#"{"topic":"1"}
+{"topic":"2","payload":"%","base64":true}
+{"topic":"3","delay_ms":60000}
+"#,
+        )
+        .unwrap();
+        assert!(import.tick(|_| unreachable!()).is_none(), "paused");
+
+        import.resume();
+        // the client queue is full
+        assert_eq!(import.tick(|_| None), Some(Duration::from_millis(10)));
+        assert_eq!(import.next, 0);
+
+        let mut sent = vec![];
+        let wait = import
+            .tick(|p| {
+                sent.push(p.topic);
+                Some(PublishRef::new_v4())
+            })
+            .unwrap();
+        assert_eq!(sent, ["1"]);
+        assert!(wait > Duration::from_secs(59));
+        assert!(matches!(import.rows[0].1, Sent::Queued(_)));
+        // an undecodable payload fails its row only
+        assert!(matches!(&import.rows[1].1, Sent::Failed(e) if e.starts_with("payload")));
+        assert!(matches!(import.rows[2].1, Sent::Waiting));
+        assert_eq!(import.next, 2);
+
+        import.next = 3;
+        assert!(import.finished());
+        import.restart();
+        assert!(import.next == 0 && !import.paused);
+        assert!(matches!(import.rows[0].1, Sent::Waiting));
+    }
+}
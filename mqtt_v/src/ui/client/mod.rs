@@ -1,4 +1,21 @@
 pub(crate) mod chat_tab;
+#[allow(clippy::module_inception)]
 pub(crate) mod client;
+pub(crate) mod dashboard;
+pub(crate) mod dashboard_tab;
+pub(crate) mod export;
+pub(crate) mod filter;
+pub(crate) mod import;
+pub(crate) mod options;
+pub(crate) mod plot;
+pub(crate) mod plot_tab;
+pub(crate) mod profile;
 pub(crate) mod publish_tab;
+pub(crate) mod session;
+pub(crate) mod session_tab;
+pub(crate) mod sparkplug;
+pub(crate) mod sparkplug_tab;
+pub(crate) mod stat_tab;
+pub(crate) mod stats;
+pub(crate) mod topic_tree;
 pub(crate) mod tree_tab;
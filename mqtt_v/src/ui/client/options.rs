@@ -0,0 +1,315 @@
+use backend::message::{
+    OptionsV3, OptionsV5, QoS, ReconnectOptions, ReconnectPolicy, TlsOptions, Transport,
+    WillOptions, WsOptions,
+};
+use eframe::{
+    egui::{Checkbox, DragValue, RichText, Slider, TextEdit, Ui},
+    epaint::Color32,
+};
+
+use crate::ui::THEME;
+
+pub fn show_v3(ui: &mut Ui, v3: &mut OptionsV3) {
+    broker_fields(ui, &mut v3.client_id, &mut v3.broker_addr, &mut v3.port);
+    transport_fields(ui, &mut v3.transport, &mut v3.tls, &mut v3.ws, &mut v3.port);
+    ui.separator();
+    keep_alive_fields(ui, &mut v3.keep_alive, &mut v3.heatbbeat);
+    reconnect_fields(ui, &mut v3.reconnect);
+    ui.separator();
+    credentials_fields(ui, &mut v3.credentials, &mut v3.username, &mut v3.password);
+    will_fields(ui, &mut v3.last_will, &mut v3.will);
+    ui.group(|ui| {
+        ui.label("max packet size");
+        ui.label("incoming");
+        ui.add(Slider::new(&mut v3.max_packet_size.0, 5..=u16::MAX).suffix(" bytes"));
+        ui.label("outgoing");
+        ui.add(Slider::new(&mut v3.max_packet_size.1, 5..=u16::MAX).suffix(" bytes"));
+    });
+    ui.end_row();
+    ui.add(Checkbox::new(&mut v3.clean_session, "clean_session"));
+}
+
+pub fn show_v5(ui: &mut Ui, v5: &mut OptionsV5) {
+    broker_fields(ui, &mut v5.client_id, &mut v5.broker_addr, &mut v5.port);
+    transport_fields(ui, &mut v5.transport, &mut v5.tls, &mut v5.ws, &mut v5.port);
+    ui.separator();
+    keep_alive_fields(ui, &mut v5.keep_alive, &mut v5.heatbbeat);
+    reconnect_fields(ui, &mut v5.reconnect);
+    ui.separator();
+    credentials_fields(ui, &mut v5.credentials, &mut v5.username, &mut v5.password);
+    will_fields(ui, &mut v5.last_will, &mut v5.will);
+    ui.group(|ui| {
+        ui.label("connect properties");
+        optional_value(ui, "session expiry", &mut v5.session_expiry, 3600, " s");
+        optional_value(ui, "receive maximum", &mut v5.receive_maximum, 100, "");
+        optional_value(
+            ui,
+            "max packet size",
+            &mut v5.max_packet_size,
+            u16::MAX.into(),
+            " bytes",
+        );
+        optional_value(ui, "topic alias max", &mut v5.topic_alias_max, 10, "");
+    });
+    ui.group(|ui| {
+        key_value_list(ui, "user properties", &mut v5.user_properties);
+    });
+    ui.end_row();
+    ui.add(Checkbox::new(&mut v5.clean_start, "clean_start"));
+}
+
+fn broker_fields(ui: &mut Ui, client_id: &mut String, broker_addr: &mut String, port: &mut u16) {
+    ui.horizontal(|ui| {
+        ui.label("client_id");
+        let client_id = TextEdit::singleline(client_id)
+            .hint_text(RichText::new("client_id").color(THEME.colors.gray));
+
+        ui.add(client_id)
+    });
+
+    ui.horizontal(|ui| {
+        let addr = TextEdit::singleline(broker_addr)
+            .hint_text(RichText::new("broker_address").color(THEME.colors.gray));
+        let port_widget = DragValue::new(port).clamp_range(0..=65535);
+
+        ui.label("broker_address:");
+        ui.add(addr);
+        ui.separator();
+        ui.add(port_widget);
+    });
+}
+
+fn transport_fields(
+    ui: &mut Ui,
+    transport: &mut Transport,
+    tls: &mut TlsOptions,
+    ws: &mut WsOptions,
+    port: &mut u16,
+) {
+    ui.horizontal(|ui| {
+        ui.label("transport:");
+        for (value, label, default_port) in [
+            (Transport::Tcp, "tcp", 1883),
+            (Transport::Tls, "tls", 8883),
+            (Transport::Ws, "ws", 8083),
+            (Transport::Wss, "wss", 8084),
+        ] {
+            if ui.selectable_label(*transport == value, label).clicked() && *transport != value {
+                // follow the well-known port unless a custom one was set
+                if matches!(*port, 1883 | 8883 | 8083 | 8084) {
+                    *port = default_port;
+                }
+                *transport = value;
+            }
+        }
+    });
+
+    if transport.is_websocket() {
+        ui.group(|ui| {
+            path_field(ui, "url path", &mut ws.path, "/mqtt");
+            key_value_list(ui, "http headers", &mut ws.headers);
+        });
+    }
+
+    if transport.is_tls() {
+        ui.group(|ui| {
+            path_field(ui, "CA bundle", &mut tls.ca_path, "system roots");
+            ui.checkbox(&mut tls.client_auth, "client certificate (mTLS)");
+            if tls.client_auth {
+                path_field(ui, "certificate", &mut tls.client_cert_path, "client.crt");
+                path_field(ui, "private key", &mut tls.client_key_path, "client.key");
+            }
+            ui.horizontal(|ui| {
+                ui.label("ALPN");
+                if ui.button("✚").clicked() {
+                    tls.alpn.push(String::new());
+                }
+            });
+            let mut remove = None;
+            for (i, protocol) in tls.alpn.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        TextEdit::singleline(protocol)
+                            .desired_width(140.0)
+                            .hint_text(RichText::new("x-amzn-mqtt-ca").color(THEME.colors.gray)),
+                    );
+                    if ui.button("ｘ").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                tls.alpn.remove(i);
+            }
+            let color = if tls.accept_invalid_certs {
+                Color32::LIGHT_RED
+            } else {
+                Color32::WHITE
+            };
+            ui.checkbox(
+                &mut tls.accept_invalid_certs,
+                RichText::new("accept invalid certificates").color(color),
+            );
+        });
+    }
+}
+
+fn key_value_list(ui: &mut Ui, label: &str, list: &mut Vec<(String, String)>) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        if ui.button("✚").clicked() {
+            list.push(Default::default());
+        }
+    });
+    let mut remove = None;
+    for (i, (key, value)) in list.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.add(
+                TextEdit::singleline(key)
+                    .desired_width(100.0)
+                    .hint_text(RichText::new("key").color(THEME.colors.gray)),
+            );
+            ui.add(
+                TextEdit::singleline(value)
+                    .desired_width(140.0)
+                    .hint_text(RichText::new("value").color(THEME.colors.gray)),
+            );
+            if ui.button("ｘ").clicked() {
+                remove = Some(i);
+            }
+        });
+    }
+    if let Some(i) = remove {
+        list.remove(i);
+    }
+}
+
+fn path_field(ui: &mut Ui, label: &str, path: &mut String, hint: &str) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(
+            TextEdit::singleline(path)
+                .desired_width(220.0)
+                .hint_text(RichText::new(hint).color(THEME.colors.gray)),
+        );
+    });
+}
+
+fn keep_alive_fields(ui: &mut Ui, keep_alive: &mut bool, heatbbeat: &mut u64) {
+    ui.horizontal(|ui| {
+        ui.add(Checkbox::new(keep_alive, "keep_alive"));
+
+        if *keep_alive {
+            ui.separator();
+            ui.add(Slider::new(heatbbeat, 5..=30).suffix("s"));
+        }
+    });
+}
+
+fn reconnect_fields(ui: &mut Ui, reconnect: &mut ReconnectOptions) {
+    ui.horizontal(|ui| {
+        ui.label("reconnect:");
+        ui.selectable_value(&mut reconnect.policy, ReconnectPolicy::Off, "off");
+        ui.selectable_value(&mut reconnect.policy, ReconnectPolicy::Fixed, "fixed");
+        ui.selectable_value(
+            &mut reconnect.policy,
+            ReconnectPolicy::Exponential,
+            "exponential",
+        );
+    });
+    if reconnect.policy == ReconnectPolicy::Off {
+        return;
+    }
+    ui.horizontal(|ui| {
+        ui.label("every");
+        ui.add(
+            DragValue::new(&mut reconnect.interval_ms)
+                .clamp_range(100..=3_600_000)
+                .speed(100)
+                .suffix(" ms"),
+        );
+        if reconnect.policy == ReconnectPolicy::Exponential {
+            ui.label("up to");
+            ui.add(
+                DragValue::new(&mut reconnect.max_interval_ms)
+                    .clamp_range(100..=3_600_000)
+                    .speed(100)
+                    .suffix(" ms"),
+            );
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut reconnect.jitter, "jitter");
+        ui.separator();
+        ui.label("max attempts");
+        ui.add(DragValue::new(&mut reconnect.max_attempts).clamp_range(0..=10_000))
+            .on_hover_text("0 retries forever");
+    });
+}
+
+fn credentials_fields(
+    ui: &mut Ui,
+    credentials: &mut bool,
+    username: &mut String,
+    password: &mut String,
+) {
+    ui.group(|ui| {
+        if ui.selectable_label(*credentials, "credentials").clicked() {
+            *credentials = !*credentials
+        }
+        if *credentials {
+            ui.label("username");
+            ui.add(TextEdit::singleline(username));
+            ui.label("password");
+            ui.add(TextEdit::singleline(password).password(true));
+        }
+    });
+}
+
+fn will_fields(ui: &mut Ui, last_will: &mut bool, will: &mut WillOptions) {
+    ui.group(|ui| {
+        if ui.selectable_label(*last_will, "last will").clicked() {
+            *last_will = !*last_will
+        }
+        if *last_will {
+            ui.horizontal(|ui| {
+                ui.label("topic");
+                ui.add(
+                    TextEdit::singleline(&mut will.topic)
+                        .desired_width(220.0)
+                        .hint_text(RichText::new("clients/offline").color(THEME.colors.gray)),
+                );
+            });
+            ui.label("payload");
+            ui.add(
+                TextEdit::multiline(&mut will.payload)
+                    .desired_rows(2)
+                    .code_editor(),
+            );
+            ui.horizontal(|ui| {
+                ui.label("qos");
+                ui.selectable_value(&mut will.qos, QoS::AtMostOnce, "0");
+                ui.selectable_value(&mut will.qos, QoS::AtLeastOnce, "1");
+                ui.selectable_value(&mut will.qos, QoS::ExactlyOnce, "2");
+                ui.separator();
+                ui.checkbox(&mut will.retain, "retain");
+            });
+        }
+    });
+}
+
+/// A checkbox enabling the property, followed by its value when it is set
+fn optional_value<T>(ui: &mut Ui, label: &str, value: &mut Option<T>, default: T, suffix: &str)
+where
+    T: eframe::emath::Numeric,
+{
+    ui.horizontal(|ui| {
+        let mut enabled = value.is_some();
+        if ui.checkbox(&mut enabled, label).changed() {
+            *value = if enabled { Some(default) } else { None };
+        }
+        if let Some(v) = value {
+            ui.add(DragValue::new(v).suffix(suffix));
+        }
+    });
+}
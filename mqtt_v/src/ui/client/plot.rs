@@ -0,0 +1,258 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::File,
+};
+
+use backend::message::Publish;
+use chrono::{DateTime, Local, TimeZone};
+use serde_json::Value;
+
+use crate::ui::decoder::Decoders;
+
+use super::client::topic_matches;
+
+/// points kept per topic, the oldest are dropped first
+const POINTS: usize = 20_000;
+
+/// topics plotted at most, the first ones to match keep their series
+pub const SERIES: usize = 16;
+
+/// A numeric field of decoded payloads followed on every topic matching a
+/// filter, one series per topic
+pub struct Plot {
+    pub filter: String,
+    /// JSON pointer of the field, `/temperature` or `/sensors/0/value`
+    pub path: String,
+    /// seconds since the Unix epoch and value, oldest first
+    pub series: BTreeMap<String, VecDeque<[f64; 2]>>,
+    /// values of matching topics left out once `SERIES` were plotted
+    pub dropped: u64,
+    /// file the plotted points are exported to
+    pub csv_path: String,
+    /// outcome of the last export
+    pub exported: Option<Result<usize, String>>,
+}
+
+impl Plot {
+    pub fn new(filter: String, path: String) -> Self {
+        let csv_path = format!("{}.csv", path.trim_start_matches('/').replace('/', "_"));
+        Self {
+            filter,
+            path,
+            series: BTreeMap::new(),
+            dropped: 0,
+            csv_path,
+            exported: None,
+        }
+    }
+
+    pub fn on_publish(&mut self, time: DateTime<Local>, publish: &Publish, decoders: &Decoders) {
+        if !topic_matches(&self.filter, &publish.topic) {
+            return;
+        }
+        let Some(value) = decoders
+            .for_topic(&publish.topic)
+            .value(&publish.payload)
+            .and_then(|tree| tree.pointer(&self.path).and_then(Value::as_f64))
+        else {
+            return;
+        };
+        if self.series.len() == SERIES && !self.series.contains_key(&publish.topic) {
+            self.dropped += 1;
+            return;
+        }
+        let points = self.series.entry(publish.topic.clone()).or_default();
+        if points.len() == POINTS {
+            points.pop_front();
+        }
+        points.push_back([time.timestamp_millis() as f64 / 1e3, value]);
+    }
+
+    /// Points of every series at or after `since`, seconds since the Unix epoch
+    pub fn points(&self, since: f64) -> impl Iterator<Item = (&str, &[f64; 2])> {
+        self.series.iter().flat_map(move |(topic, points)| {
+            let start = points.partition_point(|[t, _]| *t < since);
+            points
+                .range(start..)
+                .map(move |point| (topic.as_str(), point))
+        })
+    }
+
+    /// Writes the points at or after `since` as `time,topic,value` rows
+    pub fn export(&mut self, since: f64) {
+        let export = || -> Result<usize, String> {
+            let file = File::create(&self.csv_path).map_err(|e| e.to_string())?;
+            let mut csv = csv::Writer::from_writer(file);
+            csv.write_record(["time", "topic", "value"])
+                .map_err(|e| e.to_string())?;
+            let mut rows = 0;
+            for (topic, [time, value]) in self.points(since) {
+                let time = Local.timestamp_millis_opt((time * 1e3) as i64).unwrap();
+                csv.write_record([&time.to_rfc3339(), topic, &value.to_string()])
+                    .map_err(|e| e.to_string())?;
+                rows += 1;
+            }
+            csv.flush().map_err(|e| e.to_string())?;
+            Ok(rows)
+        };
+        self.exported = Some(export());
+    }
+}
+
+/// JSON pointer of `key` below `parent`
+pub fn pointer(parent: &str, key: &str) -> String {
+    format!("{}/{}", parent, key.replace('~', "~0").replace('/', "~1"))
+}
+
+/// Pointers and values of every number in `tree`
+pub fn numbers(tree: &Value) -> Vec<(String, f64)> {
+    fn walk(value: &Value, path: String, found: &mut Vec<(String, f64)>) {
+        match value {
+            Value::Number(n) => found.extend(n.as_f64().map(|n| (path, n))),
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    walk(item, pointer(&path, &i.to_string()), found);
+                }
+            }
+            Value::Object(fields) => {
+                for (key, item) in fields {
+                    walk(item, pointer(&path, key), found);
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut found = vec![];
+    walk(tree, String::new(), &mut found);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::message::QoS;
+    use chrono::Duration;
+
+    use super::*;
+
+    fn publish(topic: &str, payload: &str) -> Publish {
+        Publish::new(topic, QoS::AtMostOnce, payload.to_owned())
+    }
+
+    fn plot(filter: &str, path: &str) -> (Plot, Decoders) {
+        (
+            Plot::new(filter.to_owned(), path.to_owned()),
+            Decoders::new(vec![], vec![]),
+        )
+    }
+
+    #[test]
+    fn follows_the_field_on_matching_topics() {
+        let (mut plot, decoders) = plot("room/+", "/sensors/0/t");
+        let start = Local.timestamp_opt(1_700_000_000, 0).unwrap();
+        let at = |s| start + Duration::seconds(s);
+        plot.on_publish(
+            at(0),
+            &publish("room/a", r#"{"sensors": [{"t": 20.5}]}"#),
+            &decoders,
+        );
+        plot.on_publish(
+            at(1),
+            &publish("room/b", r#"{"sensors": [{"t": 19}]}"#),
+            &decoders,
+        );
+        plot.on_publish(
+            at(2),
+            &publish("room/a", r#"{"sensors": [{"t": "x"}]}"#),
+            &decoders,
+        );
+        plot.on_publish(at(3), &publish("room/a", "not json"), &decoders);
+        plot.on_publish(
+            at(4),
+            &publish("hall/a", r#"{"sensors": [{"t": 1}]}"#),
+            &decoders,
+        );
+        plot.on_publish(
+            at(5),
+            &publish("room/a", r#"{"sensors": [{"t": 21}]}"#),
+            &decoders,
+        );
+
+        let t = 1_700_000_000.0;
+        let points: Vec<_> = plot.points(0.0).map(|(topic, p)| (topic, *p)).collect();
+        assert_eq!(
+            points,
+            [
+                ("room/a", [t, 20.5]),
+                ("room/a", [t + 5.0, 21.0]),
+                ("room/b", [t + 1.0, 19.0])
+            ]
+        );
+        let recent: Vec<_> = plot.points(t + 1.0).map(|(_, [_, v])| *v).collect();
+        assert_eq!(recent, [21.0, 19.0]);
+    }
+
+    #[test]
+    fn series_are_bounded() {
+        let (mut plot, decoders) = plot("#", "/v");
+        for i in 0..SERIES + 2 {
+            plot.on_publish(
+                Local::now(),
+                &publish(&format!("t/{:02}", i), r#"{"v": 1}"#),
+                &decoders,
+            );
+        }
+        plot.on_publish(Local::now(), &publish("t/00", r#"{"v": 2}"#), &decoders);
+        assert_eq!(plot.series.len(), SERIES);
+        assert_eq!(plot.series["t/00"].len(), 2);
+        assert!(!plot.series.contains_key(&format!("t/{:02}", SERIES)));
+        assert_eq!(plot.dropped, 2);
+
+        let points = plot.series.get_mut("t/00").unwrap();
+        points.extend(std::iter::repeat_n([0.0, 0.0], POINTS - 2));
+        plot.on_publish(Local::now(), &publish("t/00", r#"{"v": 3}"#), &decoders);
+        assert_eq!(plot.series["t/00"].len(), POINTS);
+        assert_eq!(plot.series["t/00"].back().unwrap()[1], 3.0);
+    }
+
+    #[test]
+    fn exports_the_window_as_csv() {
+        let (mut plot, decoders) = plot("a", "/v");
+        let start = Local.timestamp_opt(1_700_000_000, 0).unwrap();
+        plot.on_publish(start, &publish("a", r#"{"v": 1}"#), &decoders);
+        plot.on_publish(
+            start + Duration::seconds(10),
+            &publish("a", r#"{"v": 2.5}"#),
+            &decoders,
+        );
+
+        let path = std::env::temp_dir().join(format!("mqtt_v-{}-plot.csv", std::process::id()));
+        plot.csv_path = path.to_string_lossy().into_owned();
+        plot.export(1_700_000_005.0);
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(plot.exported, Some(Ok(1))));
+        let expected = format!(
+            "time,topic,value\n{},a,2.5\n",
+            (start + Duration::seconds(10)).to_rfc3339()
+        );
+        assert_eq!(csv, expected);
+
+        plot.csv_path = "/nonexistent/dir/plot.csv".to_owned();
+        plot.export(0.0);
+        assert!(matches!(plot.exported, Some(Err(_))));
+    }
+
+    #[test]
+    fn pointers_of_every_number() {
+        let tree = serde_json::json!({"a/b": 1, "list": [2, {"~x": 3}], "text": "4"});
+        let found = numbers(&tree);
+        assert_eq!(
+            found,
+            [
+                ("/a~1b".to_owned(), 1.0),
+                ("/list/0".to_owned(), 2.0),
+                ("/list/1/~0x".to_owned(), 3.0)
+            ]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use chrono::Local;
+use eframe::{
+    egui::{
+        self,
+        plot::{Legend, Line, Plot, PlotPoints},
+        RichText, ScrollArea, TextEdit,
+    },
+    epaint::Color32,
+};
+
+use crate::ui::{widgets::docking, THEME};
+
+use super::{client::Client, plot::SERIES};
+
+/// windows offered, in seconds
+const WINDOWS: [(i64, &str); 4] = [
+    (60, "1 min"),
+    (300, "5 min"),
+    (900, "15 min"),
+    (3600, "1 h"),
+];
+
+pub struct PlotTab {
+    /// seconds shown
+    window: i64,
+}
+
+impl PlotTab {
+    pub fn new() -> Self {
+        Self { window: 300 }
+    }
+}
+
+impl docking::Tab<Client> for PlotTab {
+    fn title(&self) -> &str {
+        "📊 plot"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, client: &mut Client) {
+        ui.push_id("plots", |ui| {
+            ui.horizontal(|ui| {
+                for (window, label) in WINDOWS {
+                    ui.selectable_value(&mut self.window, window, label);
+                }
+            });
+            ui.separator();
+            if client.plots.is_empty() {
+                ui.centered_and_justified(|ui| {
+                    ui.label(
+                        RichText::new("right-click a number of a payload to plot it")
+                            .color(THEME.colors.lighter_gray),
+                    );
+                });
+                return;
+            }
+            ui.ctx().request_repaint_after(Duration::from_secs(1));
+
+            let now = Local::now().timestamp_millis() as f64 / 1e3;
+            let since = now - self.window as f64;
+            let height = (ui.available_height() / client.plots.len() as f32 - 48.0).max(160.0);
+            let mut remove = None;
+            ScrollArea::vertical().show(ui, |ui| {
+                for (i, plot) in client.plots.iter_mut().enumerate() {
+                    ui.push_id(i, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(&plot.path).color(Color32::KHAKI).monospace());
+                            ui.label("on");
+                            ui.label(RichText::new(&plot.filter).color(Color32::KHAKI));
+                            if ui.button("ｘ").on_hover_text("stop plotting").clicked() {
+                                remove = Some(i);
+                            }
+                            ui.separator();
+                            ui.add(TextEdit::singleline(&mut plot.csv_path).desired_width(160.0));
+                            if ui.button("export csv").clicked() {
+                                plot.export(since);
+                            }
+                            match &plot.exported {
+                                Some(Ok(rows)) => {
+                                    ui.colored_label(
+                                        Color32::LIGHT_GREEN,
+                                        format!("✔ {} rows", rows),
+                                    );
+                                }
+                                Some(Err(e)) => {
+                                    ui.colored_label(Color32::LIGHT_RED, format!("⚠ {}", e));
+                                }
+                                None => {}
+                            }
+                            if plot.dropped > 0 {
+                                ui.colored_label(
+                                    Color32::YELLOW,
+                                    format!("⚠ only the first {} topics", SERIES),
+                                )
+                                .on_hover_text(format!("{} values left out", plot.dropped));
+                            }
+                        });
+                        Plot::new(("plot", i))
+                            .height(height)
+                            .legend(Legend::default())
+                            .include_x(-self.window as f64)
+                            .include_x(0.0)
+                            .show(ui, |plot_ui| {
+                                for (topic, points) in &plot.series {
+                                    let start = points.partition_point(|[t, _]| *t < since);
+                                    let points: PlotPoints =
+                                        points.range(start..).map(|[t, v]| [t - now, *v]).collect();
+                                    plot_ui.line(Line::new(points).name(topic));
+                                }
+                            });
+                    });
+                    ui.add_space(8.0);
+                }
+            });
+            if let Some(i) = remove {
+                client.plots.remove(i);
+            }
+        });
+    }
+}
@@ -0,0 +1,239 @@
+use backend::{message::MqttOpts, store::StoreOptions};
+use eframe::Storage;
+use serde::{Deserialize, Serialize};
+
+use crate::ui::decoder::DecoderRule;
+
+use super::{client::Subcribe, dashboard::Dashboard, publish_tab::PublishDraft};
+
+/// storage key of the versioned profiles, `eframe::APP_KEY` holds the bare
+/// `Vec<MqttOpts>` written before profiles existed until the first save
+const PROFILES_KEY: &str = "profiles";
+/// where profiles that could not be read are moved before they are
+/// overwritten, those of a newer build for instance
+const BACKUP_KEY: &str = "profiles.backup";
+
+/// Everything kept about a connection across restarts
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub options: MqttOpts,
+    pub subscriptions: Vec<Subcribe>,
+    /// subscribe to `subscriptions` on the first ConnAck, reconnects without a
+    /// session always subscribe again to the active ones
+    pub auto_subscribe: bool,
+    pub draft: PublishDraft,
+    /// bounds of the event history
+    pub store: StoreOptions,
+    /// payload decoder per topic filter
+    pub decoders: Vec<DecoderRule>,
+    /// protobuf schemas the decoders are built from
+    pub schemas: Vec<String>,
+    /// follow the Sparkplug B namespace
+    pub sparkplug: bool,
+    pub dashboard: Dashboard,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile::from(MqttOpts::default())
+    }
+}
+
+impl From<MqttOpts> for Profile {
+    fn from(options: MqttOpts) -> Self {
+        Self {
+            options,
+            subscriptions: vec![],
+            auto_subscribe: true,
+            draft: PublishDraft::default(),
+            store: StoreOptions::default(),
+            decoders: vec![],
+            schemas: vec![],
+            sparkplug: false,
+            dashboard: Dashboard::default(),
+        }
+    }
+}
+
+/// Bump when `Profile` changes shape and convert the older versions in `load`.
+/// Fields added with a default do not change it, `#[serde(default)]` fills
+/// them in when reading profiles saved before they existed
+const VERSION: u32 = 1;
+
+/// A plain struct rather than a tagged enum, RON can not read newtypes like
+/// `Color32` back from inside an internally tagged enum
+#[derive(Serialize, Deserialize)]
+struct Stored {
+    version: u32,
+    profiles: Vec<Profile>,
+}
+
+/// Just the version of stored profiles, readable whatever their shape
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+pub fn load(storage: &dyn Storage) -> Vec<Profile> {
+    if storage.get_string(PROFILES_KEY).is_none() {
+        // version 0, connection options only
+        return eframe::get_value::<Vec<MqttOpts>>(storage, eframe::APP_KEY)
+            .unwrap_or_default()
+            .into_iter()
+            .map(Profile::from)
+            .collect();
+    }
+    match read(storage) {
+        Ok(profiles) => profiles,
+        Err(e) => {
+            tracing::warn!("{}, they are moved to {} on save", e, BACKUP_KEY);
+            vec![]
+        }
+    }
+}
+
+fn read(storage: &dyn Storage) -> Result<Vec<Profile>, String> {
+    match eframe::get_value::<Stored>(storage, PROFILES_KEY) {
+        Some(Stored {
+            version: VERSION,
+            profiles,
+        }) => Ok(profiles),
+        _ => match eframe::get_value::<Version>(storage, PROFILES_KEY) {
+            Some(Version { version }) if version != VERSION => Err(format!(
+                "ignoring profiles stored with unknown version {}",
+                version
+            )),
+            _ => Err("ignoring unreadable profiles".to_owned()),
+        },
+    }
+}
+
+pub fn save(storage: &mut dyn Storage, profiles: Vec<Profile>) {
+    if let Some(stored) = storage.get_string(PROFILES_KEY) {
+        if read(storage).is_err() {
+            storage.set_string(BACKUP_KEY, stored);
+        }
+    }
+    eframe::set_value(
+        storage,
+        PROFILES_KEY,
+        &Stored {
+            version: VERSION,
+            profiles,
+        },
+    );
+    // migrated by `load`, stale from now on
+    storage.set_string(eframe::APP_KEY, String::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MemoryStorage(HashMap<String, String>);
+
+    impl Storage for MemoryStorage {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_owned(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    fn client_ids(profiles: &[Profile]) -> Vec<String> {
+        profiles.iter().map(|p| p.options.client_id()).collect()
+    }
+
+    #[test]
+    fn version_0_is_migrated_once() {
+        let mut storage = MemoryStorage::default();
+        let options = vec![MqttOpts::default()];
+        eframe::set_value(&mut storage, eframe::APP_KEY, &options);
+
+        let profiles = load(&storage);
+        assert_eq!(client_ids(&profiles), vec![options[0].client_id()]);
+        assert!(profiles[0].auto_subscribe);
+
+        save(&mut storage, profiles);
+        assert_eq!(storage.get_string(eframe::APP_KEY).unwrap(), "");
+        assert_eq!(client_ids(&load(&storage)), vec![options[0].client_id()]);
+
+        // the old list does not come back once the profiles are gone
+        save(&mut storage, vec![]);
+        assert!(load(&storage).is_empty());
+    }
+
+    #[test]
+    fn fields_added_later_take_their_default() {
+        /// a profile saved before the decoders, Sparkplug and dashboard fields
+        #[derive(Serialize)]
+        struct Early {
+            options: MqttOpts,
+            auto_subscribe: bool,
+        }
+        #[derive(Serialize)]
+        struct EarlyStored {
+            version: u32,
+            profiles: Vec<Early>,
+        }
+        let mut storage = MemoryStorage::default();
+        let early = Early {
+            options: MqttOpts::default(),
+            auto_subscribe: false,
+        };
+        eframe::set_value(
+            &mut storage,
+            PROFILES_KEY,
+            &EarlyStored {
+                version: VERSION,
+                profiles: vec![early],
+            },
+        );
+
+        let profiles = load(&storage);
+        assert_eq!(profiles.len(), 1);
+        assert!(!profiles[0].auto_subscribe);
+        assert!(!profiles[0].sparkplug);
+        assert!(profiles[0].decoders.is_empty());
+        assert!(profiles[0].dashboard.widgets.is_empty());
+    }
+
+    #[test]
+    fn unknown_version_is_backed_up() {
+        let mut storage = MemoryStorage::default();
+        eframe::set_value(&mut storage, eframe::APP_KEY, &vec![MqttOpts::default()]);
+        let newer = "(version: 99, profiles: [], shape: \"unknown\")".to_owned();
+        storage.set_string(PROFILES_KEY, newer.clone());
+
+        assert!(load(&storage).is_empty());
+        save(&mut storage, vec![Profile::default()]);
+        assert_eq!(storage.get_string(BACKUP_KEY), Some(newer));
+        assert_eq!(load(&storage).len(), 1);
+
+        // a readable save leaves the backup alone
+        save(&mut storage, vec![]);
+        assert!(storage.get_string(BACKUP_KEY).unwrap().contains("99"));
+    }
+
+    #[test]
+    fn unreadable_profiles_do_not_bring_back_version_0() {
+        let mut storage = MemoryStorage::default();
+        eframe::set_value(&mut storage, eframe::APP_KEY, &vec![MqttOpts::default()]);
+        storage.set_string(PROFILES_KEY, "(version: 1, profiles: 7)".to_owned());
+
+        assert!(load(&storage).is_empty());
+        save(&mut storage, vec![]);
+        assert_eq!(
+            storage.get_string(BACKUP_KEY).as_deref(),
+            Some("(version: 1, profiles: 7)")
+        );
+    }
+}
@@ -1,28 +1,205 @@
-use backend::message::{Publish, QoS};
+use backend::message::{qos_serde, Delivery, Publish, QoS};
 use eframe::{
-    egui::{self, style::Margin, Layout, TextEdit},
+    egui::{
+        self, style::Margin, CollapsingHeader, Layout, ProgressBar, RichText, ScrollArea, TextEdit,
+    },
     emath::Align,
     epaint::Color32,
 };
 
-use crate::ui::widgets::docking;
+use serde::{Deserialize, Serialize};
 
-use super::client::{Client, ClientPacket, PacketData};
+use crate::ui::{decoder::Decoders, widgets::docking, THEME};
+
+use super::{
+    client::{delivery_label, Client},
+    import::{Import, Sent},
+};
 
 pub struct PubulishTab {
-    topic: String,
-    qos: QoS,
-    retain: bool,
-    payload: String,
+    /// file of publishes about to be imported
+    import_path: String,
+    load_error: Option<String>,
+    /// why the last publish did not reach the client
+    send_error: Option<String>,
 }
 
-impl PubulishTab {
-    pub fn new() -> Self {
+/// The message being written, kept per client
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PublishDraft {
+    pub topic: String,
+    #[serde(with = "qos_serde")]
+    pub qos: QoS,
+    pub retain: bool,
+    pub payload: String,
+    /// format the payload is written in, `None` follows the decoder of the topic
+    pub encoder: Option<String>,
+}
+
+impl Default for PublishDraft {
+    fn default() -> Self {
         Self {
             topic: "".to_owned(),
             qos: QoS::AtLeastOnce,
             retain: false,
             payload: "".to_owned(),
+            encoder: None,
+        }
+    }
+}
+
+impl PubulishTab {
+    pub fn new() -> Self {
+        Self {
+            import_path: "publishes.ndjson".to_owned(),
+            load_error: None,
+            send_error: None,
+        }
+    }
+
+    /// Loads a file of publishes and sends them in order
+    fn import(&mut self, ui: &mut egui::Ui, client: &mut Client) {
+        ui.horizontal(|ui| {
+            ui.add(
+                TextEdit::singleline(&mut self.import_path)
+                    .hint_text("NDJSON or .csv")
+                    .desired_width(240.0),
+            );
+            if ui.button("load").clicked() {
+                match Import::load(self.import_path.clone()) {
+                    Ok(import) => {
+                        client.import = Some(import);
+                        self.load_error = None;
+                    }
+                    Err(e) => self.load_error = Some(e),
+                }
+            }
+            if let Some(e) = &self.load_error {
+                ui.colored_label(Color32::LIGHT_RED, format!("⚠ {}", e));
+            }
+        });
+        let Some(import) = &mut client.import else {
+            ui.label(
+                RichText::new("rows of topic, payload, qos, retain and delay_ms")
+                    .color(THEME.colors.lighter_gray),
+            );
+            return;
+        };
+
+        let mut close = false;
+        ui.horizontal(|ui| {
+            let connected = client.publish_tx.is_some();
+            if import.finished() {
+                if ui
+                    .add_enabled(connected, egui::Button::new("⟲ again"))
+                    .clicked()
+                {
+                    import.restart();
+                }
+            } else if import.paused {
+                let label = if import.next == 0 {
+                    "▶ publish"
+                } else {
+                    "▶ resume"
+                };
+                if ui
+                    .add_enabled(connected, egui::Button::new(label))
+                    .clicked()
+                {
+                    import.resume();
+                }
+            } else if ui.button("⏸ pause").clicked() {
+                import.paused = true;
+            }
+            if ui.button("ｘ").on_hover_text("close").clicked() {
+                close = true;
+            }
+            let (mut acked, mut failed) = (0, 0);
+            for (_, sent) in &import.rows {
+                match sent {
+                    Sent::Queued(id) => match client.deliveries.get(id) {
+                        Some(Delivery::Acked(_)) => acked += 1,
+                        Some(Delivery::Failed(_)) => failed += 1,
+                        _ => {}
+                    },
+                    Sent::Failed(_) => failed += 1,
+                    Sent::Waiting => {}
+                }
+            }
+            ui.colored_label(Color32::YELLOW, &import.path);
+            ui.label(format!("{} acked", acked));
+            if failed > 0 {
+                ui.colored_label(Color32::LIGHT_RED, format!("{} failed", failed));
+            }
+            if !connected {
+                ui.label(RichText::new("not connected").color(THEME.colors.lighter_gray));
+            }
+        });
+        ui.add(
+            ProgressBar::new(import.next as f32 / import.rows.len() as f32).text(format!(
+                "{} / {}",
+                import.next,
+                import.rows.len()
+            )),
+        );
+
+        let row_height = ui.spacing().interact_size.y;
+        ScrollArea::vertical()
+            .id_source("import_rows")
+            .max_height(200.0)
+            .auto_shrink([false, true])
+            .show_rows(ui, row_height, import.rows.len(), |ui, range| {
+                for i in range {
+                    let (row, sent) = &import.rows[i];
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            RichText::new(format!("{:>4}", i + 1))
+                                .monospace()
+                                .color(THEME.colors.lighter_gray),
+                        );
+                        let (text, color, hover) = match sent {
+                            Sent::Waiting => {
+                                ("waiting".to_owned(), THEME.colors.lighter_gray, None)
+                            }
+                            Sent::Queued(id) => match client.deliveries.get(id) {
+                                Some(delivery) => {
+                                    let (text, color) = delivery_label(delivery);
+                                    let hover = match delivery {
+                                        Delivery::Failed(reason) => Some(reason.clone()),
+                                        _ => None,
+                                    };
+                                    (text, color, hover)
+                                }
+                                None => ("➡ sent".to_owned(), Color32::LIGHT_BLUE, None),
+                            },
+                            Sent::Failed(e) => {
+                                ("✖ failed".to_owned(), Color32::LIGHT_RED, Some(e.clone()))
+                            }
+                        };
+                        let label = ui.add_sized(
+                            [110.0, row_height],
+                            egui::Label::new(RichText::new(text).color(color)),
+                        );
+                        if let Some(hover) = hover {
+                            label.on_hover_text(hover);
+                        }
+                        ui.label(format!("QoS {}", row.qos as u8));
+                        if row.retain {
+                            ui.colored_label(Color32::YELLOW, "retain");
+                        }
+                        if row.delay_ms > 0 {
+                            ui.label(
+                                RichText::new(format!("+{} ms", row.delay_ms))
+                                    .color(THEME.colors.lighter_gray),
+                            );
+                        }
+                        ui.label(RichText::new(&row.topic).color(Color32::KHAKI));
+                    });
+                }
+            });
+        if close {
+            client.import = None;
         }
     }
 }
@@ -33,6 +210,8 @@ impl docking::Tab<Client> for PubulishTab {
     }
 
     fn ui(&mut self, ui: &mut egui::Ui, client: &mut Client) {
+        let draft = &mut client.draft;
+        let mut send = None;
         ui.push_id("pubulish_tab", |ui| {
             egui::Frame::default()
                 .outer_margin(Margin::symmetric(2., 6.))
@@ -43,13 +222,13 @@ impl docking::Tab<Client> for PubulishTab {
                     ui.horizontal(|ui| {
                         ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                             ui.group(|ui| {
-                                if ui.selectable_label(self.retain, "retain").clicked() {
-                                    self.retain = !self.retain;
+                                if ui.selectable_label(draft.retain, "retain").clicked() {
+                                    draft.retain = !draft.retain;
                                 }
                                 ui.separator();
-                                ui.radio_value(&mut self.qos, QoS::AtMostOnce, "0");
-                                ui.radio_value(&mut self.qos, QoS::AtLeastOnce, "1");
-                                ui.radio_value(&mut self.qos, QoS::ExactlyOnce, "2");
+                                ui.radio_value(&mut draft.qos, QoS::AtMostOnce, "0");
+                                ui.radio_value(&mut draft.qos, QoS::AtLeastOnce, "1");
+                                ui.radio_value(&mut draft.qos, QoS::ExactlyOnce, "2");
                                 ui.label("Qos:");
                             });
                             ui.group(|ui| {
@@ -57,7 +236,7 @@ impl docking::Tab<Client> for PubulishTab {
                                 let topic_edit_w = if w > 0.0 { w } else { 20.0 };
 
                                 ui.add(
-                                    TextEdit::singleline(&mut self.topic)
+                                    TextEdit::singleline(&mut draft.topic)
                                         .desired_width(topic_edit_w),
                                 );
                                 ui.label("Topic:");
@@ -68,36 +247,49 @@ impl docking::Tab<Client> for PubulishTab {
                     ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                         ui.group(|ui| {
                             ui.set_width(200.);
-                            if ui.button("publish").clicked() {
-                                if let Some(tx) = &client.publish_tx {
+                            // payloads of binary formats are written as JSON
+                            let encoder = match &draft.encoder {
+                                Some(name) => client.decoders.get(name),
+                                None => client.decoders.for_topic(&draft.topic),
+                            };
+                            let encoded = encoder.encode(&draft.payload);
+                            ui.horizontal(|ui| {
+                                let publish =
+                                    ui.add_enabled(encoded.is_ok(), egui::Button::new("publish"));
+                                if let (true, Ok(payload)) = (publish.clicked(), &encoded) {
                                     let mut publish = Publish::new(
-                                        self.topic.clone(),
-                                        self.qos,
-                                        self.payload.as_bytes(),
+                                        draft.topic.clone(),
+                                        draft.qos,
+                                        payload.clone(),
                                     );
-                                    publish.retain = self.retain;
-                                    let send_time = chrono::Local::now();
-                                    // todo maybe replace using uuid as sending id
-                                    if tx
-                                        .try_send(backend::message::ToClient::Publish(
-                                            send_time.to_rfc3339(),
-                                            publish.clone(),
-                                        ))
-                                        .is_ok()
-                                    {
-                                        client.packets.push(ClientPacket {
-                                            time: chrono::Local::now(),
-                                            data: PacketData::PublishPacket(publish),
-                                        })
-                                    }
+                                    publish.retain = draft.retain;
+                                    send = Some(publish);
+                                }
+                                let current = if encoder.encodes() {
+                                    encoder.name()
                                 } else {
-                                    println!("no tx")
+                                    "text"
+                                };
+                                ui.menu_button(format!("as {} ⏷", current), |ui| {
+                                    encoder_menu(ui, &mut draft.encoder, &client.decoders)
+                                });
+                            });
+                            match &encoded {
+                                Ok(payload) if encoder.encodes() => {
+                                    ui.label(format!("{} bytes", payload.len()));
+                                }
+                                Err(e) => {
+                                    ui.colored_label(Color32::LIGHT_RED, format!("⚠ {}", e));
                                 }
+                                _ => {}
+                            }
+                            if let Some(e) = &self.send_error {
+                                ui.colored_label(Color32::LIGHT_RED, format!("⚠ {}", e));
                             }
                         });
                         ui.group(|ui| {
                             ui.add(
-                                egui::TextEdit::multiline(&mut self.payload)
+                                egui::TextEdit::multiline(&mut draft.payload)
                                     .code_editor()
                                     .desired_width(f32::INFINITY),
                             );
@@ -105,5 +297,48 @@ impl docking::Tab<Client> for PubulishTab {
                     });
                 });
         });
+        if let Some(publish) = send {
+            let connected = client.publish_tx.is_some();
+            self.send_error = client.publish(publish).is_none().then(|| {
+                if connected {
+                    "not sent, the request queue is full".to_owned()
+                } else {
+                    "not sent, no connection".to_owned()
+                }
+            });
+        }
+        CollapsingHeader::new("📥 import")
+            .id_source("publish_import")
+            .show(ui, |ui| self.import(ui, client));
     }
 }
+
+/// Picks the format the written payload is encoded to before publishing
+fn encoder_menu(ui: &mut egui::Ui, encoder: &mut Option<String>, decoders: &Decoders) {
+    if ui
+        .selectable_label(encoder.is_none(), "topic default")
+        .clicked()
+    {
+        *encoder = None;
+        ui.close_menu();
+    }
+    if ui
+        .selectable_label(encoder.as_deref() == Some("text"), "text, as written")
+        .clicked()
+    {
+        *encoder = Some("text".to_owned());
+        ui.close_menu();
+    }
+    ui.separator();
+    ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+        for name in decoders.encoders() {
+            if ui
+                .selectable_label(encoder.as_deref() == Some(name), name)
+                .clicked()
+            {
+                *encoder = Some(name.to_owned());
+                ui.close_menu();
+            }
+        }
+    });
+}
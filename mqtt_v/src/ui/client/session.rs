@@ -0,0 +1,320 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    time::{Duration, Instant},
+};
+
+use backend::message::Publish;
+pub use backend::record::Record;
+use chrono::{DateTime, Local};
+
+/// publishes replayed in one frame at most, the rest wait for the next one
+const REPLAY_BATCH: usize = 500;
+
+/// Writes every incoming publish of a client to a session file
+pub struct Recorder {
+    pub path: String,
+    file: BufWriter<File>,
+    pub count: u64,
+    /// first write error, recording stops there
+    pub error: Option<String>,
+    flushed: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: String) -> Result<Self, String> {
+        let file = File::create(&path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Self {
+            path,
+            file: BufWriter::new(file),
+            count: 0,
+            error: None,
+            flushed: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, time: DateTime<Local>, publish: &Publish) {
+        if self.error.is_some() {
+            return;
+        }
+        let written = serde_json::to_writer(&mut self.file, &Record::new(time, publish))
+            .map_err(|e| e.to_string())
+            .and_then(|()| self.file.write_all(b"\n").map_err(|e| e.to_string()));
+        // flushed now and then so that a crash loses at most a second
+        let written = written.and_then(|()| {
+            if self.flushed.elapsed() > Duration::from_secs(1) {
+                self.flushed = Instant::now();
+                self.file.flush().map_err(|e| e.to_string())
+            } else {
+                Ok(())
+            }
+        });
+        match written {
+            Ok(()) => self.count += 1,
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    pub fn stop(mut self) -> Result<u64, String> {
+        self.file.flush().map_err(|e| e.to_string())?;
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.count),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Speed {
+    /// the recorded timing divided by the factor, 1.0 is the original
+    Times(f64),
+    /// as fast as the client queue takes them
+    Max,
+}
+
+/// A session file being republished through a client
+pub struct Replay {
+    pub path: String,
+    /// publishes with their offset from the first one
+    records: Vec<(Duration, Publish)>,
+    /// next record to send
+    pub index: usize,
+    pub speed: Speed,
+    /// recorded topics starting with `from` are sent with `to` instead
+    pub from: String,
+    pub to: String,
+    /// start over once the end is reached
+    pub looping: bool,
+    pub loops: u32,
+    /// when the record at `index` was due to start, `None` while paused
+    clock: Option<(Instant, Duration)>,
+    pub sent: u64,
+}
+
+impl Replay {
+    pub fn load(path: String) -> Result<Self, String> {
+        let file = File::open(&path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut records = vec![];
+        let mut first = None;
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let at = |e: String| format!("line {}: {}", i + 1, e);
+            let record: Record = serde_json::from_str(&line).map_err(|e| at(e.to_string()))?;
+            let time = record.time().map_err(at)?;
+            let first = *first.get_or_insert(time);
+            // out of order lines are sent right after the previous one
+            let offset = (time - first).to_std().unwrap_or_default();
+            let offset = records.last().map_or(offset, |(last, _)| offset.max(*last));
+            records.push((offset, record.publish().map_err(at)?));
+        }
+        if records.is_empty() {
+            return Err(format!("{}: no publishes", path));
+        }
+        Ok(Self {
+            path,
+            records,
+            index: 0,
+            speed: Speed::Times(1.0),
+            from: String::new(),
+            to: String::new(),
+            looping: false,
+            loops: 0,
+            clock: None,
+            sent: 0,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// time between the first and the last publish
+    pub fn duration(&self) -> Duration {
+        self.records
+            .last()
+            .map_or(Duration::ZERO, |(offset, _)| *offset)
+    }
+
+    pub fn playing(&self) -> bool {
+        self.clock.is_some()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.index >= self.records.len()
+    }
+
+    pub fn play(&mut self) {
+        if self.finished() {
+            self.index = 0;
+        }
+        self.anchor();
+    }
+
+    pub fn pause(&mut self) {
+        self.clock = None;
+    }
+
+    /// Keeps the timing from the next record on, after a pause or a change
+    /// of speed
+    pub fn anchor(&mut self) {
+        if let Some((offset, _)) = self.records.get(self.index) {
+            self.clock = Some((Instant::now(), *offset));
+        }
+    }
+
+    /// Hands the records that are due to `publish` until it refuses one.
+    /// Returns how long until the next one is due, `None` once stopped
+    pub fn tick(&mut self, mut publish: impl FnMut(Publish) -> bool) -> Option<Duration> {
+        let (started, base) = self.clock?;
+        for _ in 0..REPLAY_BATCH {
+            let Some((offset, record)) = self.records.get(self.index) else {
+                if self.looping {
+                    self.index = 0;
+                    self.loops += 1;
+                    self.anchor();
+                    return Some(Duration::ZERO);
+                }
+                self.clock = None;
+                return None;
+            };
+            if let Speed::Times(factor) = self.speed {
+                let due = started + offset.saturating_sub(base).div_f64(factor.max(0.01));
+                let now = Instant::now();
+                if due > now {
+                    return Some(due - now);
+                }
+            }
+            if !publish(self.rewrite(record)) {
+                // the client queue is full or gone, try again shortly
+                return Some(Duration::from_millis(10));
+            }
+            self.index += 1;
+            self.sent += 1;
+        }
+        Some(Duration::ZERO)
+    }
+
+    fn rewrite(&self, record: &Publish) -> Publish {
+        let mut publish = record.clone();
+        if let Some(rest) = publish.topic.strip_prefix(&self.from) {
+            publish.topic = format!("{}{}", self.to, rest);
+        }
+        publish
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::message::QoS;
+
+    use super::*;
+
+    fn session_file(name: &str, times: &[&str]) -> String {
+        let path = std::env::temp_dir().join(format!("mqtt_v-{}-{}", std::process::id(), name));
+        let lines: Vec<String> = times
+            .iter()
+            .map(|time| {
+                format!(
+                    r#"{{"time":"{}","topic":"a/b","qos":0,"retain":false,"payload":"x"}}"#,
+                    time
+                )
+            })
+            .collect();
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn records_round_trip_binary_payloads() {
+        let time = Local::now();
+        let mut binary = Publish::new("a/b", QoS::ExactlyOnce, vec![0xff, 0xfe, 0x00]);
+        binary.retain = true;
+        let record = Record::new(time, &binary);
+        assert!(record.base64);
+        let line = serde_json::to_string(&record).unwrap();
+        let read: Record = serde_json::from_str(&line).unwrap();
+        let publish = read.publish().unwrap();
+        assert_eq!(publish.payload, binary.payload);
+        assert_eq!((publish.qos, publish.retain), (QoS::ExactlyOnce, true));
+        assert_eq!(
+            read.time().unwrap().timestamp_millis(),
+            time.timestamp_millis()
+        );
+
+        // text stays readable and the flag is left out
+        let text = Record::new(time, &Publish::new("a", QoS::AtMostOnce, "hi"));
+        let line = serde_json::to_string(&text).unwrap();
+        assert!(line.contains(r#""payload":"hi""#) && !line.contains("base64"));
+    }
+
+    #[test]
+    fn replays_at_the_recorded_pace_with_prefix_and_loops() {
+        let path = session_file(
+            "pace.ndjson",
+            &[
+                "2024-01-01T00:00:00.000+00:00",
+                "2024-01-01T00:00:00.200+00:00",
+            ],
+        );
+        let mut replay = Replay::load(path.clone()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        replay.from = "a/".to_owned();
+        replay.to = "z/".to_owned();
+        replay.speed = Speed::Times(2.0);
+        assert!(replay.tick(|_| true).is_none(), "paused");
+
+        replay.play();
+        let mut topics = vec![];
+        let wait = replay.tick(|p| {
+            topics.push(p.topic);
+            true
+        });
+        assert_eq!(topics, ["z/b"]);
+        // 200ms recorded at twice the speed
+        let wait = wait.unwrap();
+        assert!(wait > Duration::from_millis(50) && wait <= Duration::from_millis(100));
+
+        replay.speed = Speed::Max;
+        replay.looping = true;
+        assert_eq!(replay.tick(|_| true), Some(Duration::ZERO));
+        assert_eq!((replay.index, replay.loops, replay.sent), (0, 1, 2));
+    }
+
+    #[test]
+    fn out_of_order_lines_survive_pause_and_resume() {
+        let path = session_file(
+            "out_of_order.ndjson",
+            &[
+                "2024-01-01T00:00:00.000+00:00",
+                "2024-01-01T00:00:05.000+00:00",
+                "2024-01-01T00:00:02.000+00:00",
+            ],
+        );
+        let mut replay = Replay::load(path.clone()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(replay.duration(), Duration::from_secs(5));
+
+        replay.speed = Speed::Times(1000.0);
+        replay.play();
+        let mut sent = 0;
+        // the queue refuses the second record, so the replay stops at index 1
+        assert!(replay
+            .tick(|_| {
+                sent += 1;
+                sent == 1
+            })
+            .is_some());
+        assert_eq!(replay.index, 1);
+
+        replay.pause();
+        replay.play();
+        while replay.playing() {
+            replay.tick(|_| true);
+        }
+        assert!(replay.finished());
+        assert_eq!(replay.sent, 3);
+    }
+}
@@ -0,0 +1,231 @@
+use eframe::{
+    egui::{self, DragValue, Grid, ProgressBar, RichText, TextEdit},
+    epaint::Color32,
+};
+
+use crate::ui::{widgets::docking, THEME};
+
+use super::{
+    client::Client,
+    session::{Recorder, Replay, Speed},
+};
+
+pub struct SessionTab {
+    record_path: String,
+    replay_path: String,
+    /// speed factor offered besides the original one
+    factor: f64,
+    /// outcome of the last recording or of starting one
+    recorded: Option<Result<u64, String>>,
+    load_error: Option<String>,
+    /// the client the outcomes belong to, the tab is shared by every client
+    client_id: String,
+}
+
+impl SessionTab {
+    pub fn new() -> Self {
+        Self {
+            record_path: "session.ndjson".to_owned(),
+            replay_path: "session.ndjson".to_owned(),
+            factor: 10.0,
+            recorded: None,
+            load_error: None,
+            client_id: String::new(),
+        }
+    }
+
+    fn recorder(&mut self, ui: &mut egui::Ui, client: &mut Client) {
+        ui.label(RichText::new("record").strong());
+        ui.horizontal(|ui| match client.recorder.take() {
+            Some(recorder) => {
+                if ui.button("⏹ stop").clicked() {
+                    self.recorded = Some(recorder.stop());
+                } else {
+                    ui.colored_label(Color32::LIGHT_RED, "⏺");
+                    ui.label(format!("{} publishes to {}", recorder.count, recorder.path));
+                    if let Some(e) = &recorder.error {
+                        ui.colored_label(Color32::LIGHT_RED, format!("⚠ {}", e));
+                    }
+                    client.recorder = Some(recorder);
+                }
+            }
+            None => {
+                ui.add(TextEdit::singleline(&mut self.record_path).desired_width(240.0));
+                if ui.button("⏺ record").clicked() {
+                    match Recorder::create(self.record_path.clone()) {
+                        Ok(recorder) => {
+                            client.recorder = Some(recorder);
+                            self.recorded = None;
+                        }
+                        Err(e) => self.recorded = Some(Err(e)),
+                    }
+                }
+                match &self.recorded {
+                    Some(Ok(count)) => {
+                        ui.colored_label(
+                            Color32::LIGHT_GREEN,
+                            format!("✔ {} publishes recorded", count),
+                        );
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(Color32::LIGHT_RED, format!("⚠ {}", e));
+                    }
+                    None => {}
+                }
+            }
+        });
+        ui.label(
+            RichText::new("every incoming publish of this client, one JSON object per line")
+                .color(THEME.colors.lighter_gray),
+        );
+    }
+
+    fn replay(&mut self, ui: &mut egui::Ui, client: &mut Client) {
+        ui.label(RichText::new("replay").strong());
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut self.replay_path).desired_width(240.0));
+            if ui.button("load").clicked() {
+                match Replay::load(self.replay_path.clone()) {
+                    Ok(replay) => {
+                        client.replay = Some(replay);
+                        self.load_error = None;
+                    }
+                    Err(e) => self.load_error = Some(e),
+                }
+            }
+            if let Some(e) = &self.load_error {
+                ui.colored_label(Color32::LIGHT_RED, format!("⚠ {}", e));
+            }
+        });
+        let Some(replay) = &mut client.replay else {
+            ui.label(
+                RichText::new("load a recorded session to republish it through this client")
+                    .color(THEME.colors.lighter_gray),
+            );
+            return;
+        };
+
+        ui.add_space(4.0);
+        Grid::new("replay_settings").num_columns(2).show(ui, |ui| {
+            ui.label("session");
+            ui.colored_label(
+                Color32::YELLOW,
+                format!(
+                    "{}, {} publishes over {:.1}s",
+                    replay.path,
+                    replay.len(),
+                    replay.duration().as_secs_f64()
+                ),
+            );
+            ui.end_row();
+
+            ui.label("speed");
+            ui.horizontal(|ui| {
+                let mut choice = match replay.speed {
+                    Speed::Times(1.0) => 0,
+                    Speed::Times(factor) => {
+                        self.factor = factor;
+                        1
+                    }
+                    Speed::Max => 2,
+                };
+                ui.radio_value(&mut choice, 0, "1x");
+                ui.radio_value(&mut choice, 1, "");
+                let factor = DragValue::new(&mut self.factor)
+                    .speed(0.1)
+                    .clamp_range(0.01..=1000.0)
+                    .suffix("x");
+                if ui.add(factor).changed() {
+                    choice = 1;
+                }
+                ui.radio_value(&mut choice, 2, "as fast as possible");
+                let speed = match choice {
+                    0 => Speed::Times(1.0),
+                    1 => Speed::Times(self.factor),
+                    _ => Speed::Max,
+                };
+                if speed != replay.speed {
+                    replay.speed = speed;
+                    if replay.playing() {
+                        replay.anchor();
+                    }
+                }
+            });
+            ui.end_row();
+
+            ui.label("topic prefix");
+            ui.horizontal(|ui| {
+                ui.add(
+                    TextEdit::singleline(&mut replay.from)
+                        .hint_text("recorded")
+                        .desired_width(120.0),
+                );
+                ui.label("➡");
+                ui.add(
+                    TextEdit::singleline(&mut replay.to)
+                        .hint_text("replayed")
+                        .desired_width(120.0),
+                );
+            });
+            ui.end_row();
+
+            ui.label("loop");
+            ui.checkbox(&mut replay.looping, "start over at the end");
+            ui.end_row();
+        });
+
+        ui.add_space(4.0);
+        ui.horizontal(|ui| {
+            let connected = client.publish_tx.is_some();
+            if replay.playing() {
+                if ui.button("⏸ pause").clicked() {
+                    replay.pause();
+                }
+            } else if ui
+                .add_enabled(connected, egui::Button::new("▶ play"))
+                .clicked()
+            {
+                replay.play();
+            }
+            if ui.button("⏹ stop").clicked() {
+                replay.pause();
+                replay.index = 0;
+            }
+            let mut status = format!("{} sent", replay.sent);
+            if replay.loops > 0 {
+                status += &format!(", loop {}", replay.loops + 1);
+            }
+            ui.label(status);
+            if !connected {
+                ui.label(RichText::new("not connected").color(THEME.colors.lighter_gray));
+            }
+        });
+        ui.add(
+            ProgressBar::new(replay.index as f32 / replay.len() as f32).text(format!(
+                "{} / {}",
+                replay.index,
+                replay.len()
+            )),
+        );
+    }
+}
+
+impl docking::Tab<Client> for SessionTab {
+    fn title(&self) -> &str {
+        "⏺ session"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, client: &mut Client) {
+        let client_id = client.options.client_id();
+        if self.client_id != client_id {
+            self.client_id = client_id;
+            self.recorded = None;
+            self.load_error = None;
+        }
+        ui.push_id("session", |ui| {
+            self.recorder(ui, client);
+            ui.separator();
+            self.replay(ui, client);
+        });
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use backend::message::Publish;
+use chrono::{DateTime, Local};
+use prost::Message;
+use serde_json::Value;
+
+use crate::ui::decoder::sparkplug::{MetricValue, Payload, Topic, NAMESPACE};
+
+/// alerts kept, the oldest are dropped first
+const ALERTS: usize = 500;
+
+/// Edge nodes, devices and host applications of the Sparkplug B namespace,
+/// followed from their birth and death certificates
+#[derive(Default)]
+pub struct Sparkplug {
+    /// edge nodes by group and node id
+    pub groups: BTreeMap<String, BTreeMap<String, EdgeNode>>,
+    /// whether each host application announced itself online
+    pub hosts: BTreeMap<String, bool>,
+    /// sequence gaps, stale deaths and the like, oldest first
+    pub alerts: VecDeque<Alert>,
+}
+
+pub struct Alert {
+    pub time: DateTime<Local>,
+    /// `group/node` or `group/node/device`
+    pub source: String,
+    pub text: String,
+}
+
+#[derive(Default)]
+pub struct EdgeNode {
+    pub online: bool,
+    /// birth/death sequence number of the current session, from NBIRTH
+    pub bd_seq: Option<u64>,
+    /// sequence number of the last message of the node or its devices
+    pub seq: Option<u64>,
+    pub gaps: u64,
+    pub births: u64,
+    pub deaths: u64,
+    pub updated: Option<DateTime<Local>>,
+    pub metrics: BTreeMap<String, MetricState>,
+    pub devices: BTreeMap<String, Device>,
+    /// metric names by alias, shared by the node and its devices
+    aliases: HashMap<u64, String>,
+}
+
+#[derive(Default)]
+pub struct Device {
+    pub online: bool,
+    pub births: u64,
+    pub deaths: u64,
+    pub updated: Option<DateTime<Local>>,
+    pub metrics: BTreeMap<String, MetricState>,
+}
+
+pub struct MetricState {
+    pub alias: Option<u64>,
+    pub datatype: &'static str,
+    pub value: Value,
+    pub updated: DateTime<Local>,
+}
+
+impl Sparkplug {
+    pub fn on_publish(&mut self, time: DateTime<Local>, publish: &Publish) {
+        let mut levels = publish.topic.split('/');
+        if levels.next() != Some(NAMESPACE) {
+            return;
+        }
+        if let (Some("STATE"), Some(host)) = (levels.next(), levels.next()) {
+            self.hosts
+                .insert(host.to_owned(), host_online(&publish.payload));
+            return;
+        }
+        let Some(topic) = Topic::parse(&publish.topic) else {
+            return;
+        };
+        let source = match topic.device {
+            Some(device) => format!("{}/{}/{}", topic.group, topic.node, device),
+            None => format!("{}/{}", topic.group, topic.node),
+        };
+        let payload = match Payload::decode(&publish.payload[..]) {
+            Ok(payload) => payload,
+            Err(e) => {
+                self.alert(
+                    time,
+                    source,
+                    format!("{} is not Sparkplug B, {}", topic.kind, e),
+                );
+                return;
+            }
+        };
+
+        let node = self
+            .groups
+            .entry(topic.group.to_owned())
+            .or_default()
+            .entry(topic.node.to_owned())
+            .or_default();
+        let alerts = node.apply(time, &topic, &payload);
+        for alert in alerts {
+            self.alert(time, source.clone(), alert);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn alert(&mut self, time: DateTime<Local>, source: String, text: String) {
+        if self.alerts.len() == ALERTS {
+            self.alerts.pop_front();
+        }
+        self.alerts.push_back(Alert { time, source, text });
+    }
+}
+
+impl EdgeNode {
+    /// Applies a message of the node or one of its devices, returns what
+    /// looks wrong about it
+    fn apply(&mut self, time: DateTime<Local>, topic: &Topic, payload: &Payload) -> Vec<String> {
+        let mut alerts = vec![];
+        self.updated = Some(time);
+
+        match topic.kind {
+            "NBIRTH" => {
+                self.online = true;
+                self.births += 1;
+                self.bd_seq = bd_seq(payload);
+                self.seq = payload.seq;
+                self.metrics.clear();
+                self.aliases.clear();
+                // devices are born again after their node
+                for device in self.devices.values_mut() {
+                    device.online = false;
+                }
+                self.update_metrics(time, payload, None, &mut alerts);
+            }
+            "NDEATH" => {
+                let bd_seq = bd_seq(payload);
+                if self.bd_seq.is_some() && bd_seq != self.bd_seq {
+                    alerts.push(format!(
+                        "NDEATH bdSeq {} does not match the NBIRTH bdSeq {}, ignored",
+                        show(bd_seq),
+                        show(self.bd_seq)
+                    ));
+                    return alerts;
+                }
+                self.online = false;
+                self.deaths += 1;
+                for device in self.devices.values_mut() {
+                    device.online = false;
+                }
+            }
+            "NDATA" | "DBIRTH" | "DDATA" | "DDEATH" => {
+                self.check_seq(topic.kind, payload.seq, &mut alerts);
+                if !self.online {
+                    alerts.push(format!("{} from a node that is not born", topic.kind));
+                }
+                if let Some(id) = topic.device {
+                    let device = self.devices.entry(id.to_owned()).or_default();
+                    match topic.kind {
+                        "DBIRTH" => {
+                            device.online = true;
+                            device.births += 1;
+                            device.metrics.clear();
+                        }
+                        "DDEATH" => {
+                            device.online = false;
+                            device.deaths += 1;
+                        }
+                        "DDATA" if !device.online => {
+                            alerts.push("DDATA from a device that is not born".to_owned())
+                        }
+                        _ => {}
+                    }
+                }
+                self.update_metrics(time, payload, topic.device, &mut alerts);
+            }
+            // commands to the node carry no sequence number
+            _ => {}
+        }
+        alerts
+    }
+
+    /// seq counts every message of a session from 0 after NBIRTH, wrapping at 256
+    fn check_seq(&mut self, kind: &str, seq: Option<u64>, alerts: &mut Vec<String>) {
+        let Some(seq) = seq else {
+            alerts.push(format!("{} without seq", kind));
+            return;
+        };
+        if let Some(last) = self.seq {
+            let expected = (last + 1) % 256;
+            if seq != expected {
+                self.gaps += 1;
+                alerts.push(format!("{} seq {}, expected {}", kind, seq, expected));
+            }
+        }
+        self.seq = Some(seq);
+    }
+
+    fn update_metrics(
+        &mut self,
+        time: DateTime<Local>,
+        payload: &Payload,
+        device: Option<&str>,
+        alerts: &mut Vec<String>,
+    ) {
+        let Self {
+            aliases,
+            metrics,
+            devices,
+            ..
+        } = self;
+        let metrics = match device {
+            Some(id) => {
+                let device = devices.entry(id.to_owned()).or_default();
+                device.updated = Some(time);
+                &mut device.metrics
+            }
+            None => metrics,
+        };
+
+        for metric in &payload.metrics {
+            let name = match (&metric.name, metric.alias) {
+                (Some(name), alias) => {
+                    if let Some(alias) = alias {
+                        aliases.insert(alias, name.clone());
+                    }
+                    name.clone()
+                }
+                (None, Some(alias)) => match aliases.get(&alias) {
+                    Some(name) => name.clone(),
+                    None => {
+                        alerts.push(format!("unknown alias {}", alias));
+                        format!("alias {}", alias)
+                    }
+                },
+                (None, None) => {
+                    alerts.push("metric without name or alias".to_owned());
+                    continue;
+                }
+            };
+            let previous = metrics.get(&name);
+            let state = MetricState {
+                alias: metric.alias.or(previous.and_then(|m| m.alias)),
+                // data messages may leave the type to the birth certificate
+                datatype: match (metric.datatype, previous) {
+                    (None, Some(previous)) => previous.datatype,
+                    _ => metric.datatype_name(),
+                },
+                value: metric.json_value(),
+                updated: time,
+            };
+            metrics.insert(name, state);
+        }
+    }
+}
+
+/// The `bdSeq` metric of NBIRTH and NDEATH
+fn bd_seq(payload: &Payload) -> Option<u64> {
+    payload
+        .metrics
+        .iter()
+        .find(|metric| metric.name.as_deref() == Some("bdSeq"))
+        .and_then(|metric| match metric.value {
+            Some(MetricValue::Long(seq)) => Some(seq),
+            Some(MetricValue::Int(seq)) => Some(seq as u64),
+            _ => None,
+        })
+}
+
+/// Sparkplug 3.0 hosts publish `{"online": true, ..}`, older ones `ONLINE`
+fn host_online(payload: &[u8]) -> bool {
+    match serde_json::from_slice::<Value>(payload) {
+        Ok(state) => state["online"].as_bool().unwrap_or(false),
+        Err(_) => payload == b"ONLINE",
+    }
+}
+
+fn show(seq: Option<u64>) -> String {
+    seq.map_or("-".to_owned(), |seq| seq.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::message::QoS;
+
+    use super::*;
+    use crate::ui::decoder::sparkplug::Metric;
+
+    fn metric(name: Option<&str>, alias: Option<u64>, value: MetricValue) -> Metric {
+        Metric {
+            name: name.map(str::to_owned),
+            alias,
+            value: Some(value),
+            ..Metric::default()
+        }
+    }
+
+    fn bd_seq(seq: u64) -> Metric {
+        Metric {
+            datatype: Some(8),
+            ..metric(Some("bdSeq"), None, MetricValue::Long(seq))
+        }
+    }
+
+    fn send(sparkplug: &mut Sparkplug, topic: &str, seq: Option<u64>, metrics: Vec<Metric>) {
+        let payload = Payload {
+            seq,
+            metrics,
+            ..Payload::default()
+        };
+        let topic = format!("{}/plant/{}", NAMESPACE, topic);
+        let publish = Publish::new(topic, QoS::AtMostOnce, payload.encode_to_vec());
+        sparkplug.on_publish(Local::now(), &publish);
+    }
+
+    /// Alerts raised since the last call
+    fn alerts(sparkplug: &mut Sparkplug) -> Vec<String> {
+        sparkplug.alerts.drain(..).map(|alert| alert.text).collect()
+    }
+
+    fn node(sparkplug: &Sparkplug) -> &EdgeNode {
+        &sparkplug.groups["plant"]["edge"]
+    }
+
+    #[test]
+    fn seq_wraps_at_256() {
+        let mut sparkplug = Sparkplug::default();
+        send(&mut sparkplug, "NBIRTH/edge", Some(0), vec![bd_seq(1)]);
+        for seq in (1..256).chain([0, 1]) {
+            send(&mut sparkplug, "NDATA/edge", Some(seq), vec![]);
+        }
+        assert!(alerts(&mut sparkplug).is_empty());
+
+        send(&mut sparkplug, "NDATA/edge", Some(5), vec![]);
+        send(&mut sparkplug, "NDATA/edge", None, vec![]);
+        assert_eq!(
+            alerts(&mut sparkplug),
+            ["NDATA seq 5, expected 2", "NDATA without seq"]
+        );
+        assert_eq!(node(&sparkplug).gaps, 1);
+    }
+
+    #[test]
+    fn ndeath_needs_the_bd_seq_of_the_birth() {
+        let mut sparkplug = Sparkplug::default();
+        send(&mut sparkplug, "NBIRTH/edge", Some(0), vec![bd_seq(3)]);
+        send(&mut sparkplug, "DBIRTH/edge/pump", Some(1), vec![]);
+        send(&mut sparkplug, "NDEATH/edge", None, vec![bd_seq(2)]);
+        assert_eq!(
+            alerts(&mut sparkplug),
+            ["NDEATH bdSeq 2 does not match the NBIRTH bdSeq 3, ignored"]
+        );
+        assert!(node(&sparkplug).online);
+
+        send(&mut sparkplug, "NDEATH/edge", None, vec![bd_seq(3)]);
+        assert!(alerts(&mut sparkplug).is_empty());
+        let node = node(&sparkplug);
+        assert!(!node.online && !node.devices["pump"].online);
+        assert_eq!((node.births, node.deaths), (1, 1));
+    }
+
+    #[test]
+    fn devices_and_nodes_must_be_born_first() {
+        let mut sparkplug = Sparkplug::default();
+        send(&mut sparkplug, "NBIRTH/edge", Some(0), vec![bd_seq(0)]);
+        send(&mut sparkplug, "DDATA/edge/pump", Some(1), vec![]);
+        send(&mut sparkplug, "DBIRTH/edge/pump", Some(2), vec![]);
+        send(&mut sparkplug, "DDATA/edge/pump", Some(3), vec![]);
+        assert_eq!(
+            alerts(&mut sparkplug),
+            ["DDATA from a device that is not born"]
+        );
+
+        send(&mut sparkplug, "NDATA/other", Some(0), vec![]);
+        assert_eq!(
+            alerts(&mut sparkplug),
+            ["NDATA from a node that is not born"]
+        );
+    }
+
+    #[test]
+    fn aliases_resolve_to_the_birth_names() {
+        let mut sparkplug = Sparkplug::default();
+        let temp = Metric {
+            datatype: Some(10),
+            ..metric(Some("temp"), Some(7), MetricValue::Double(20.5))
+        };
+        send(
+            &mut sparkplug,
+            "NBIRTH/edge",
+            Some(0),
+            vec![bd_seq(0), temp],
+        );
+        send(
+            &mut sparkplug,
+            "DDATA/edge/pump",
+            Some(1),
+            vec![metric(None, Some(7), MetricValue::Double(21.0))],
+        );
+        send(
+            &mut sparkplug,
+            "NDATA/edge",
+            Some(2),
+            vec![
+                metric(None, Some(7), MetricValue::Double(22.0)),
+                metric(None, Some(9), MetricValue::Int(1)),
+            ],
+        );
+        assert_eq!(
+            alerts(&mut sparkplug),
+            ["DDATA from a device that is not born", "unknown alias 9"]
+        );
+
+        let node = node(&sparkplug);
+        let temp = &node.metrics["temp"];
+        assert_eq!((temp.alias, temp.datatype), (Some(7), "Double"));
+        assert_eq!(temp.value, serde_json::json!(22.0));
+        assert!(node.metrics.contains_key("alias 9"));
+        assert_eq!(
+            node.devices["pump"].metrics["temp"].value,
+            serde_json::json!(21.0)
+        );
+    }
+
+    #[test]
+    fn host_states_and_foreign_payloads() {
+        let mut sparkplug = Sparkplug::default();
+        let state = |payload: &str| {
+            Publish::new("spBv1.0/STATE/scada", QoS::AtLeastOnce, payload.to_owned())
+        };
+        sparkplug.on_publish(Local::now(), &state(r#"{"online": true, "timestamp": 1}"#));
+        assert!(sparkplug.hosts["scada"]);
+        sparkplug.on_publish(Local::now(), &state("OFFLINE"));
+        assert!(!sparkplug.hosts["scada"]);
+
+        let garbage = Publish::new("spBv1.0/plant/NDATA/edge", QoS::AtMostOnce, vec![0xff]);
+        sparkplug.on_publish(Local::now(), &garbage);
+        let alerts = alerts(&mut sparkplug);
+        assert!(alerts[0].starts_with("NDATA is not Sparkplug B, "));
+        assert!(sparkplug.groups.is_empty());
+    }
+}
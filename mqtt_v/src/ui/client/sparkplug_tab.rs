@@ -0,0 +1,291 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use chrono::{DateTime, Local};
+use eframe::{
+    egui::{self, CollapsingHeader, Grid, RichText, ScrollArea},
+    epaint::Color32,
+};
+
+use crate::ui::{widgets::docking, THEME};
+
+use super::{
+    client::Client,
+    sparkplug::{MetricState, Sparkplug},
+};
+
+/// What the details column shows, an edge node or one of its devices
+#[derive(Clone, PartialEq)]
+struct Selected {
+    group: String,
+    node: String,
+    device: Option<String>,
+}
+
+pub struct SparkplugTab {
+    selected: Option<Selected>,
+    /// the client `selected` belongs to, the tab is shared by every client
+    client_id: String,
+}
+
+impl SparkplugTab {
+    pub fn new() -> Self {
+        Self {
+            selected: None,
+            client_id: String::new(),
+        }
+    }
+
+    fn inventory(&mut self, ui: &mut egui::Ui, sparkplug: &Sparkplug) {
+        for (group, nodes) in &sparkplug.groups {
+            CollapsingHeader::new(RichText::new(group).color(Color32::KHAKI).strong())
+                .id_source(("sparkplug_group", group))
+                .default_open(true)
+                .show(ui, |ui| {
+                    for (id, node) in nodes {
+                        let selected = Selected {
+                            group: group.clone(),
+                            node: id.clone(),
+                            device: None,
+                        };
+                        ui.horizontal(|ui| {
+                            led(ui, node.online);
+                            let label = RichText::new(id).color(Color32::KHAKI);
+                            if ui
+                                .selectable_label(self.selected.as_ref() == Some(&selected), label)
+                                .clicked()
+                            {
+                                self.selected = Some(selected.clone());
+                            }
+                            if node.gaps > 0 {
+                                ui.colored_label(
+                                    Color32::from_rgb(255, 140, 0),
+                                    format!("⚠ {} gaps", node.gaps),
+                                );
+                            }
+                        });
+                        for (device_id, device) in &node.devices {
+                            let selected = Selected {
+                                device: Some(device_id.clone()),
+                                ..selected.clone()
+                            };
+                            ui.horizontal(|ui| {
+                                ui.add_space(ui.spacing().indent);
+                                led(ui, device.online);
+                                if ui
+                                    .selectable_label(
+                                        self.selected.as_ref() == Some(&selected),
+                                        device_id,
+                                    )
+                                    .clicked()
+                                {
+                                    self.selected = Some(selected);
+                                }
+                            });
+                        }
+                    }
+                });
+        }
+
+        if !sparkplug.hosts.is_empty() {
+            ui.separator();
+            ui.label(RichText::new("host applications").strong());
+            for (host, online) in &sparkplug.hosts {
+                ui.horizontal(|ui| {
+                    led(ui, *online);
+                    ui.label(host);
+                });
+            }
+        }
+
+        ui.separator();
+        ui.label(RichText::new("alerts").strong());
+        if sparkplug.alerts.is_empty() {
+            ui.label(RichText::new("none").color(THEME.colors.lighter_gray));
+        }
+        for alert in sparkplug.alerts.iter().rev() {
+            ui.horizontal_wrapped(|ui| {
+                ui.label(
+                    RichText::new(alert.time.format("%H:%M:%S").to_string())
+                        .color(THEME.colors.lighter_gray),
+                );
+                ui.colored_label(Color32::KHAKI, &alert.source);
+                ui.colored_label(Color32::from_rgb(255, 140, 0), &alert.text);
+            });
+        }
+    }
+
+    fn details(&self, ui: &mut egui::Ui, sparkplug: &Sparkplug) {
+        let Some(selected) = &self.selected else {
+            ui.centered_and_justified(|ui| ui.label("select an edge node or a device"));
+            return;
+        };
+        let Some(node) = sparkplug
+            .groups
+            .get(&selected.group)
+            .and_then(|nodes| nodes.get(&selected.node))
+        else {
+            return;
+        };
+
+        let (title, metrics) = match &selected.device {
+            Some(id) => {
+                let Some(device) = node.devices.get(id) else {
+                    return;
+                };
+                ui.label(
+                    RichText::new(format!("{}/{}/{}", selected.group, selected.node, id))
+                        .color(Color32::KHAKI)
+                        .strong(),
+                );
+                Grid::new("sparkplug_device").num_columns(2).show(ui, |ui| {
+                    row(ui, "state", state(device.online));
+                    row(
+                        ui,
+                        "births / deaths",
+                        format!("{} / {}", device.births, device.deaths),
+                    );
+                    row(ui, "updated", time(device.updated));
+                });
+                ("device metrics", &device.metrics)
+            }
+            None => {
+                ui.label(
+                    RichText::new(format!("{}/{}", selected.group, selected.node))
+                        .color(Color32::KHAKI)
+                        .strong(),
+                );
+                Grid::new("sparkplug_node").num_columns(2).show(ui, |ui| {
+                    row(ui, "state", state(node.online));
+                    row(ui, "bdSeq", number(node.bd_seq));
+                    row(ui, "seq", number(node.seq));
+                    row(ui, "sequence gaps", node.gaps.to_string());
+                    row(
+                        ui,
+                        "births / deaths",
+                        format!("{} / {}", node.births, node.deaths),
+                    );
+                    row(ui, "devices", node.devices.len().to_string());
+                    row(ui, "updated", time(node.updated));
+                });
+                ("node metrics", &node.metrics)
+            }
+        };
+        ui.separator();
+        ui.label(RichText::new(title).strong());
+        metric_grid(ui, metrics);
+    }
+}
+
+impl docking::Tab<Client> for SparkplugTab {
+    fn title(&self) -> &str {
+        "🏭 sparkplug"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, client: &mut Client) {
+        let client_id = client.options.client_id();
+        if client_id != self.client_id {
+            self.selected = None;
+            self.client_id = client_id;
+        }
+        // keeps the metric ages current
+        ui.ctx().request_repaint_after(Duration::from_secs(1));
+
+        ui.push_id("sparkplug", |ui| {
+            ui.horizontal(|ui| {
+                let mut enabled = client.sparkplug.is_some();
+                if ui
+                    .checkbox(&mut enabled, "Sparkplug mode")
+                    .on_hover_text(
+                        "follow edge nodes and devices from their birth and death certificates",
+                    )
+                    .changed()
+                {
+                    client.sparkplug = enabled.then(Sparkplug::default);
+                }
+                if let Some(sparkplug) = &mut client.sparkplug {
+                    let nodes = sparkplug.groups.values().flat_map(BTreeMap::values);
+                    let (total, online) =
+                        nodes.fold((0, 0), |(t, o), node| (t + 1, o + node.online as usize));
+                    ui.label(
+                        RichText::new(format!("{} of {} edge nodes online", online, total))
+                            .color(THEME.colors.lighter_gray),
+                    );
+                    if ui.button(RichText::new("🗑").color(Color32::RED)).clicked() {
+                        sparkplug.clear();
+                        self.selected = None;
+                    }
+                }
+            });
+            ui.separator();
+
+            let Some(sparkplug) = &client.sparkplug else {
+                ui.centered_and_justified(|ui| {
+                    ui.label("turn on Sparkplug mode to follow the spBv1.0 namespace")
+                });
+                return;
+            };
+            ui.columns(2, |columns| {
+                ScrollArea::vertical()
+                    .id_source("sparkplug_inventory")
+                    .auto_shrink([false; 2])
+                    .show(&mut columns[0], |ui| self.inventory(ui, sparkplug));
+                ScrollArea::vertical()
+                    .id_source("sparkplug_details")
+                    .auto_shrink([false; 2])
+                    .show(&mut columns[1], |ui| self.details(ui, sparkplug));
+            });
+        });
+    }
+}
+
+fn metric_grid(ui: &mut egui::Ui, metrics: &BTreeMap<String, MetricState>) {
+    Grid::new("sparkplug_metrics")
+        .num_columns(5)
+        .striped(true)
+        .show(ui, |ui| {
+            for heading in ["name", "alias", "type", "value", "updated"] {
+                ui.label(RichText::new(heading).color(THEME.colors.lighter_gray));
+            }
+            ui.end_row();
+            for (name, metric) in metrics {
+                ui.colored_label(Color32::KHAKI, name);
+                ui.label(number(metric.alias));
+                ui.label(metric.datatype);
+                ui.colored_label(Color32::LIGHT_GREEN, metric.value.to_string());
+                ui.label(
+                    RichText::new(metric.updated.format("%H:%M:%S").to_string())
+                        .color(THEME.colors.lighter_gray),
+                );
+                ui.end_row();
+            }
+        });
+}
+
+fn led(ui: &mut egui::Ui, online: bool) {
+    let color = if online {
+        Color32::GREEN
+    } else {
+        Color32::DARK_GRAY
+    };
+    ui.colored_label(color, "⏺").on_hover_text(state(online));
+}
+
+fn row(ui: &mut egui::Ui, name: &str, value: String) {
+    ui.label(name);
+    ui.colored_label(Color32::YELLOW, value);
+    ui.end_row();
+}
+
+fn state(online: bool) -> String {
+    if online { "born" } else { "dead" }.to_owned()
+}
+
+fn number(value: Option<u64>) -> String {
+    value.map_or("-".to_owned(), |value| value.to_string())
+}
+
+fn time(time: Option<DateTime<Local>>) -> String {
+    time.map_or("-".to_owned(), |time| {
+        time.format("%Y-%m-%d %H:%M:%S").to_string()
+    })
+}
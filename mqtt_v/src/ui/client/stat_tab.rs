@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+use chrono::Local;
+use eframe::{
+    egui::{
+        self,
+        plot::{Legend, Line, Plot, PlotPoints},
+        Grid, RichText, ScrollArea,
+    },
+    epaint::Color32,
+};
+
+use crate::ui::{widgets::docking, THEME};
+
+use super::{
+    client::Client,
+    stats::{Bucket, Direction, Stats, HISTORY},
+};
+
+/// windows offered, in seconds
+const WINDOWS: [(i64, &str); 4] = [
+    (60, "1 min"),
+    (300, "5 min"),
+    (900, "15 min"),
+    (HISTORY, "1 h"),
+];
+
+const TOP_TOPICS: usize = 10;
+
+pub struct StatTab {
+    /// seconds shown
+    window: i64,
+}
+
+impl StatTab {
+    pub fn new() -> Self {
+        Self { window: 300 }
+    }
+
+    fn charts(&self, ui: &mut egui::Ui, stats: &Stats) {
+        let now = Local::now().timestamp();
+        let height = (ui.available_height() / 2.0 - 24.0).max(120.0);
+        let series = |value: fn(&Bucket) -> f64| -> PlotPoints {
+            stats
+                .window(self.window)
+                .map(|b| [(b.second - now) as f64, value(b)])
+                .collect()
+        };
+
+        ui.label("messages / s");
+        Plot::new("stat_messages")
+            .height(height)
+            .legend(Legend::default())
+            .include_x(-self.window as f64)
+            .include_x(0.0)
+            .include_y(0.0)
+            .allow_drag(false)
+            .allow_zoom(false)
+            .show(ui, |plot| {
+                plot.line(
+                    Line::new(series(|b| b.msgs_in as f64))
+                        .color(Color32::LIGHT_GREEN)
+                        .name("in"),
+                );
+                plot.line(
+                    Line::new(series(|b| b.msgs_out as f64))
+                        .color(Color32::LIGHT_BLUE)
+                        .name("out"),
+                );
+            });
+
+        ui.label("payload bytes / s");
+        Plot::new("stat_bytes")
+            .height(height)
+            .legend(Legend::default())
+            .include_x(-self.window as f64)
+            .include_x(0.0)
+            .include_y(0.0)
+            .allow_drag(false)
+            .allow_zoom(false)
+            .show(ui, |plot| {
+                plot.line(
+                    Line::new(series(|b| b.bytes_in as f64))
+                        .color(Color32::LIGHT_GREEN)
+                        .name("in"),
+                );
+                plot.line(
+                    Line::new(series(|b| b.bytes_out as f64))
+                        .color(Color32::LIGHT_BLUE)
+                        .name("out"),
+                );
+            });
+    }
+
+    fn figures(&self, ui: &mut egui::Ui, stats: &Stats) {
+        let summary = stats.summary(self.window);
+        let value = |text: String| RichText::new(text).color(Color32::YELLOW);
+
+        Grid::new("stat_summary")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("messages in / out");
+                ui.label(value(format!("{} / {}", summary.msgs_in, summary.msgs_out)));
+                ui.end_row();
+                ui.label("bytes in / out");
+                ui.label(value(format!(
+                    "{} / {}",
+                    bytes(summary.bytes_in),
+                    bytes(summary.bytes_out)
+                )));
+                ui.end_row();
+                ui.label("peak rate");
+                ui.label(value(format!("{} msg/s", summary.peak_rate)));
+                ui.end_row();
+                ui.label("average payload");
+                ui.label(value(
+                    summary
+                        .avg_payload
+                        .map_or("-".to_owned(), |avg| bytes(avg as u64)),
+                ));
+                ui.end_row();
+                ui.label("ping round trip");
+                ui.label(value(match (stats.last_rtt, stats.avg_rtt()) {
+                    (Some(last), Some(avg)) => format!("{} (avg {})", ms(last), ms(avg)),
+                    _ => "-".to_owned(),
+                }));
+                ui.end_row();
+            });
+
+        ui.separator();
+        ui.label(RichText::new("top topics").strong());
+        Grid::new("stat_topics")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for (topic, count) in stats.top_topics(self.window, TOP_TOPICS) {
+                    ui.label(RichText::new(topic).color(Color32::KHAKI));
+                    ui.label(value(format!(
+                        "{:.2} msg/s",
+                        count as f64 / self.window as f64
+                    )))
+                    .on_hover_text(format!("{} messages", count));
+                    ui.end_row();
+                }
+            });
+
+        ui.separator();
+        ui.label(RichText::new("packets").strong());
+        Grid::new("stat_packets")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for ((direction, kind), count) in &stats.packets {
+                    let (arrow, color) = match direction {
+                        Direction::In => ("⬊", Color32::LIGHT_GREEN),
+                        Direction::Out => ("⬈", Color32::LIGHT_BLUE),
+                    };
+                    ui.colored_label(color, format!("{} {}", arrow, kind));
+                    ui.label(value(count.to_string()));
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+impl docking::Tab<Client> for StatTab {
+    fn title(&self) -> &str {
+        "📈 stat"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, client: &mut Client) {
+        client.stats.tick();
+        ui.ctx().request_repaint_after(Duration::from_secs(1));
+
+        ui.push_id("statistics", |ui| {
+            ui.horizontal(|ui| {
+                for (window, label) in WINDOWS {
+                    ui.selectable_value(&mut self.window, window, label);
+                }
+                ui.separator();
+                ui.label(
+                    RichText::new(format!("{} received since start", client.recv))
+                        .color(THEME.colors.lighter_gray),
+                );
+            });
+            ui.separator();
+            ui.columns(2, |columns| {
+                self.charts(&mut columns[0], &client.stats);
+                ScrollArea::vertical()
+                    .id_source("stat_figures")
+                    .show(&mut columns[1], |ui| self.figures(ui, &client.stats));
+            });
+        });
+    }
+}
+
+fn bytes(bytes: u64) -> String {
+    match bytes {
+        0..=9_999 => format!("{} B", bytes),
+        10_000..=9_999_999 => format!("{:.1} kB", bytes as f64 / 1e3),
+        _ => format!("{:.1} MB", bytes as f64 / 1e6),
+    }
+}
+
+fn ms(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1e3)
+}
@@ -0,0 +1,286 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use backend::message::{Event, Outgoing, Packet, Publish};
+use chrono::Local;
+
+/// seconds of counters kept, the longest window the statistics tab offers
+pub const HISTORY: i64 = 3600;
+
+/// Counters of one second
+#[derive(Clone, Copy, Default)]
+pub struct Bucket {
+    /// unix time of the second
+    pub second: i64,
+    pub msgs_in: u32,
+    pub msgs_out: u32,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+/// Time bucketed traffic counters of a client
+#[derive(Default)]
+pub struct Stats {
+    /// one bucket per second, oldest first, without gaps
+    buckets: VecDeque<Bucket>,
+    /// publishes per topic and second, oldest first
+    topics: HashMap<String, VecDeque<(i64, u32)>>,
+    /// packets seen since the client was created, by direction and type
+    pub packets: BTreeMap<(Direction, &'static str), u64>,
+    ping_sent: Option<Instant>,
+    pub last_rtt: Option<Duration>,
+    rtt_total: Duration,
+    rtt_count: u32,
+    /// second of the last `tick`
+    ticked: i64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+/// Figures over a window of the counters
+pub struct Summary {
+    pub msgs_in: u64,
+    pub msgs_out: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// busiest second, in and out together
+    pub peak_rate: u32,
+    pub avg_payload: Option<f64>,
+}
+
+impl Stats {
+    /// Counts the packet type of every event and the round trip of pings
+    pub fn on_event(&mut self, event: &Event) {
+        let key = match event {
+            Event::Incoming(packet) => (Direction::In, incoming_kind(packet)),
+            Event::Outgoing(outgoing) => (Direction::Out, outgoing_kind(outgoing)),
+        };
+        *self.packets.entry(key).or_default() += 1;
+
+        match event {
+            Event::Incoming(Packet::Publish(publish)) => self.publish(Direction::In, publish),
+            Event::Outgoing(Outgoing::PingReq) => self.ping_sent = Some(Instant::now()),
+            Event::Incoming(Packet::PingResp) => {
+                if let Some(sent) = self.ping_sent.take() {
+                    let rtt = sent.elapsed();
+                    self.last_rtt = Some(rtt);
+                    self.rtt_total += rtt;
+                    self.rtt_count += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Counts a publish, outgoing ones are counted when handed to the client
+    pub fn publish(&mut self, direction: Direction, publish: &Publish) {
+        let second = Local::now().timestamp();
+        let bucket = self.bucket(second);
+        let bytes = publish.payload.len() as u64;
+        match direction {
+            Direction::In => {
+                bucket.msgs_in += 1;
+                bucket.bytes_in += bytes;
+            }
+            Direction::Out => {
+                bucket.msgs_out += 1;
+                bucket.bytes_out += bytes;
+            }
+        }
+
+        let topic = self.topics.entry(publish.topic.clone()).or_default();
+        match topic.back_mut() {
+            Some((s, count)) if *s == second => *count += 1,
+            _ => topic.push_back((second, 1)),
+        }
+    }
+
+    /// Adds the empty seconds up to now and drops what is older than `HISTORY`
+    pub fn tick(&mut self) {
+        let now = Local::now().timestamp();
+        if now == self.ticked {
+            return;
+        }
+        self.ticked = now;
+        self.bucket(now);
+        let oldest = now - HISTORY;
+        self.topics.retain(|_, seconds| {
+            while matches!(seconds.front(), Some((s, _)) if *s <= oldest) {
+                seconds.pop_front();
+            }
+            !seconds.is_empty()
+        });
+    }
+
+    /// Buckets of the last `window` seconds, oldest first
+    pub fn window(&self, window: i64) -> impl Iterator<Item = &Bucket> {
+        let skip = self.buckets.len().saturating_sub(window as usize);
+        self.buckets.iter().skip(skip)
+    }
+
+    pub fn summary(&self, window: i64) -> Summary {
+        let mut summary = Summary {
+            msgs_in: 0,
+            msgs_out: 0,
+            bytes_in: 0,
+            bytes_out: 0,
+            peak_rate: 0,
+            avg_payload: None,
+        };
+        for b in self.window(window) {
+            summary.msgs_in += b.msgs_in as u64;
+            summary.msgs_out += b.msgs_out as u64;
+            summary.bytes_in += b.bytes_in;
+            summary.bytes_out += b.bytes_out;
+            summary.peak_rate = summary.peak_rate.max(b.msgs_in + b.msgs_out);
+        }
+        let msgs = summary.msgs_in + summary.msgs_out;
+        if msgs > 0 {
+            summary.avg_payload = Some((summary.bytes_in + summary.bytes_out) as f64 / msgs as f64);
+        }
+        summary
+    }
+
+    /// The `n` busiest topics over the last `window` seconds with their publish count
+    pub fn top_topics(&self, window: i64, n: usize) -> Vec<(&str, u64)> {
+        let since = Local::now().timestamp() - window;
+        let mut topics: Vec<_> = self
+            .topics
+            .iter()
+            .map(|(topic, seconds)| {
+                let count = seconds
+                    .iter()
+                    .rev()
+                    .take_while(|(s, _)| *s > since)
+                    .map(|(_, c)| *c as u64)
+                    .sum::<u64>();
+                (topic.as_str(), count)
+            })
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        topics.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        topics.truncate(n);
+        topics
+    }
+
+    pub fn avg_rtt(&self) -> Option<Duration> {
+        (self.rtt_count > 0).then(|| self.rtt_total / self.rtt_count)
+    }
+
+    fn bucket(&mut self, second: i64) -> &mut Bucket {
+        match self.buckets.back() {
+            // the clock went back or the gap is longer than what is kept
+            Some(last) if second < last.second || second - last.second > HISTORY => {
+                self.buckets.clear()
+            }
+            _ => {}
+        }
+        let mut next = self.buckets.back().map_or(second, |last| last.second + 1);
+        while next <= second {
+            self.buckets.push_back(Bucket {
+                second: next,
+                ..Bucket::default()
+            });
+            next += 1;
+        }
+        while self.buckets.len() > HISTORY as usize {
+            self.buckets.pop_front();
+        }
+        self.buckets.back_mut().unwrap()
+    }
+}
+
+pub fn incoming_kind(packet: &Packet) -> &'static str {
+    match packet {
+        Packet::Connect(_) => "Connect",
+        Packet::ConnAck(_) => "ConnAck",
+        Packet::Publish(_) => "Publish",
+        Packet::PubAck(_) => "PubAck",
+        Packet::PubRec(_) => "PubRec",
+        Packet::PubRel(_) => "PubRel",
+        Packet::PubComp(_) => "PubComp",
+        Packet::Subscribe(_) => "Subscribe",
+        Packet::SubAck(_) => "SubAck",
+        Packet::Unsubscribe(_) => "Unsubscribe",
+        Packet::UnsubAck(_) => "UnsubAck",
+        Packet::PingReq => "PingReq",
+        Packet::PingResp => "PingResp",
+        Packet::Disconnect => "Disconnect",
+    }
+}
+
+pub fn outgoing_kind(outgoing: &Outgoing) -> &'static str {
+    match outgoing {
+        Outgoing::Publish(_) => "Publish",
+        Outgoing::Subscribe(_) => "Subscribe",
+        Outgoing::Unsubscribe(_) => "Unsubscribe",
+        Outgoing::PubAck(_) => "PubAck",
+        Outgoing::PubRec(_) => "PubRec",
+        Outgoing::PubRel(_) => "PubRel",
+        Outgoing::PubComp(_) => "PubComp",
+        Outgoing::PingReq => "PingReq",
+        Outgoing::PingResp => "PingResp",
+        Outgoing::Disconnect => "Disconnect",
+        Outgoing::AwaitAck(_) => "AwaitAck",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_fill_gaps_and_keep_an_hour() {
+        let mut stats = Stats::default();
+        stats.bucket(100).msgs_in += 1;
+        stats.bucket(103).msgs_out += 2;
+        let seconds: Vec<_> = stats.window(HISTORY).map(|b| b.second).collect();
+        assert_eq!(seconds, [100, 101, 102, 103]);
+
+        stats.bucket(100 + HISTORY);
+        assert_eq!(stats.buckets.len(), HISTORY as usize);
+        assert_eq!(stats.buckets.front().unwrap().second, 101);
+        assert_eq!(stats.summary(HISTORY).msgs_out, 2);
+        assert_eq!(stats.summary(HISTORY).msgs_in, 0);
+    }
+
+    #[test]
+    fn long_gaps_and_a_clock_going_back_start_over() {
+        let mut stats = Stats::default();
+        stats.bucket(100).msgs_in += 1;
+        stats.bucket(101 + HISTORY);
+        assert_eq!(stats.buckets.len(), 1);
+
+        stats.bucket(50).msgs_in += 1;
+        let seconds: Vec<_> = stats.window(HISTORY).map(|b| b.second).collect();
+        assert_eq!(seconds, [50]);
+    }
+
+    #[test]
+    fn summary_of_a_window() {
+        let mut stats = Stats::default();
+        let bucket = stats.bucket(10);
+        bucket.msgs_in = 3;
+        bucket.bytes_in = 30;
+        let bucket = stats.bucket(11);
+        bucket.msgs_in = 1;
+        bucket.msgs_out = 1;
+        bucket.bytes_out = 50;
+
+        let summary = stats.summary(1);
+        assert_eq!((summary.msgs_in, summary.msgs_out), (1, 1));
+        assert_eq!(summary.avg_payload, Some(25.0));
+
+        let summary = stats.summary(60);
+        assert_eq!(summary.msgs_in, 4);
+        assert_eq!(summary.peak_rate, 3);
+        assert_eq!(summary.avg_payload, Some(16.0));
+        assert!(Stats::default().summary(60).avg_payload.is_none());
+    }
+}
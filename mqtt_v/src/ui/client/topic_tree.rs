@@ -0,0 +1,213 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use backend::message::Publish;
+use chrono::{DateTime, Local};
+
+/// publishes kept per topic for its history
+const HISTORY: usize = 32;
+
+/// publishes kept over every topic, the oldest go first whatever their topic
+const TOTAL: usize = 100_000;
+
+/// payload bytes kept over every topic
+const TOTAL_BYTES: usize = 64 << 20;
+
+/// Incoming publishes arranged by topic level, counted independently of the
+/// bounded event history
+pub struct TopicTree {
+    pub root: TopicNode,
+    /// topic and per-topic number of the kept publishes, oldest first. Those
+    /// the topic's own `HISTORY` already dropped stay until they reach the front
+    order: VecDeque<(String, u64)>,
+    /// payload bytes of the kept publishes
+    bytes: usize,
+    max_publishes: usize,
+    max_bytes: usize,
+}
+
+impl Default for TopicTree {
+    fn default() -> Self {
+        Self {
+            root: TopicNode::default(),
+            order: VecDeque::new(),
+            bytes: 0,
+            max_publishes: TOTAL,
+            max_bytes: TOTAL_BYTES,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TopicNode {
+    pub children: BTreeMap<String, TopicNode>,
+    /// publishes on exactly this topic
+    pub count: u64,
+    /// publishes on this topic and every topic below it
+    pub total: u64,
+    /// last publish on this topic or below it
+    pub updated: Option<DateTime<Local>>,
+    /// latest publishes on exactly this topic, oldest first, emptied once
+    /// they are the oldest of the whole tree
+    pub history: VecDeque<(DateTime<Local>, Publish)>,
+}
+
+impl TopicTree {
+    pub fn insert(&mut self, time: DateTime<Local>, publish: &Publish) {
+        let mut node = &mut self.root;
+        node.total += 1;
+        node.updated = Some(time);
+        for level in publish.topic.split('/') {
+            node = node.children.entry(level.to_owned()).or_default();
+            node.total += 1;
+            node.updated = Some(time);
+        }
+        node.count += 1;
+        if node.history.len() == HISTORY {
+            if let Some((_, oldest)) = node.history.pop_front() {
+                self.bytes -= oldest.payload.len();
+            }
+        }
+        node.history.push_back((time, publish.clone()));
+        self.bytes += publish.payload.len();
+        self.order
+            .push_back((publish.topic.clone(), node.count - 1));
+        self.evict();
+    }
+
+    /// The node of `topic`, if anything was published on or below it
+    pub fn get(&self, topic: &str) -> Option<&TopicNode> {
+        topic
+            .split('/')
+            .try_fold(&self.root, |node, level| node.children.get(level))
+    }
+
+    pub fn clear(&mut self) {
+        self.root = TopicNode::default();
+        self.order.clear();
+        self.bytes = 0;
+    }
+
+    /// Drops the oldest publishes of the tree until it is within its bounds
+    fn evict(&mut self) {
+        while self.order.len() > self.max_publishes || self.bytes > self.max_bytes {
+            let Some((topic, number)) = self.order.pop_front() else {
+                break;
+            };
+            let node = topic
+                .split('/')
+                .try_fold(&mut self.root, |node, level| node.children.get_mut(level));
+            let Some(node) = node else {
+                continue;
+            };
+            // still kept, it is then the oldest of its topic
+            if number + node.history.len() as u64 >= node.count {
+                if let Some((_, oldest)) = node.history.pop_front() {
+                    self.bytes -= oldest.payload.len();
+                }
+            }
+        }
+    }
+}
+
+impl TopicNode {
+    pub fn last(&self) -> Option<&(DateTime<Local>, Publish)> {
+        self.history.back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backend::message::QoS;
+
+    use super::*;
+
+    fn tree(max_publishes: usize, max_bytes: usize) -> TopicTree {
+        TopicTree {
+            max_publishes,
+            max_bytes,
+            ..TopicTree::default()
+        }
+    }
+
+    fn insert(tree: &mut TopicTree, topic: &str, payload: &str) {
+        tree.insert(Local::now(), &Publish::new(topic, QoS::AtMostOnce, payload));
+    }
+
+    fn payloads(tree: &TopicTree, topic: &str) -> Vec<String> {
+        tree.get(topic)
+            .unwrap()
+            .history
+            .iter()
+            .map(|(_, p)| String::from_utf8_lossy(&p.payload).into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn counts_every_level() {
+        let mut tree = TopicTree::default();
+        insert(&mut tree, "a/b", "1");
+        insert(&mut tree, "a/b", "2");
+        insert(&mut tree, "a/c", "3");
+        insert(&mut tree, "a", "4");
+        let a = tree.get("a").unwrap();
+        assert_eq!((a.count, a.total), (1, 4));
+        assert_eq!(tree.get("a/b").unwrap().count, 2);
+        assert_eq!(tree.root.total, 4);
+        assert!(tree.get("a/d").is_none());
+        assert_eq!(payloads(&tree, "a/b"), ["1", "2"]);
+    }
+
+    #[test]
+    fn every_topic_keeps_its_latest() {
+        let mut tree = TopicTree::default();
+        for i in 0..HISTORY + 3 {
+            insert(&mut tree, "t", &i.to_string());
+        }
+        let history = payloads(&tree, "t");
+        assert_eq!(history.len(), HISTORY);
+        assert_eq!(history[0], "3");
+        assert_eq!(tree.bytes, history.iter().map(String::len).sum::<usize>());
+    }
+
+    #[test]
+    fn the_oldest_publishes_of_the_tree_go_first() {
+        let mut tree = tree(3, usize::MAX);
+        insert(&mut tree, "a", "1");
+        insert(&mut tree, "b", "2");
+        insert(&mut tree, "a", "3");
+        insert(&mut tree, "c", "4");
+        insert(&mut tree, "c", "5");
+        assert_eq!(payloads(&tree, "a"), ["3"]);
+        assert!(payloads(&tree, "b").is_empty());
+        assert_eq!(payloads(&tree, "c"), ["4", "5"]);
+        // counts outlive the history
+        assert_eq!(tree.get("a").unwrap().count, 2);
+        assert_eq!(tree.order.len(), 3);
+    }
+
+    #[test]
+    fn payload_bytes_are_bounded() {
+        let mut tree = tree(usize::MAX, 10);
+        for topic in ["a", "b", "c"] {
+            insert(&mut tree, topic, "xxxx");
+        }
+        assert!(payloads(&tree, "a").is_empty());
+        assert_eq!(payloads(&tree, "c"), ["xxxx"]);
+        assert_eq!(tree.bytes, 8);
+    }
+
+    #[test]
+    fn entries_dropped_by_their_topic_are_skipped() {
+        let mut tree = tree(HISTORY + 1, usize::MAX);
+        for i in 0..HISTORY + 1 {
+            insert(&mut tree, "a", &i.to_string());
+        }
+        insert(&mut tree, "b", "b");
+        // the front entry of `a` was already gone, nothing else is dropped
+        assert_eq!(payloads(&tree, "a").len(), HISTORY);
+        assert_eq!(payloads(&tree, "b"), ["b"]);
+
+        tree.clear();
+        assert_eq!((tree.bytes, tree.order.len()), (0, 0));
+    }
+}
@@ -1,23 +1,211 @@
-use eframe::egui::{self};
+use std::time::Duration;
 
-use crate::ui::widgets::docking;
+use chrono::{DateTime, Local};
+use eframe::{
+    egui::{self, collapsing_header::CollapsingState, style::Margin, Frame, RichText, ScrollArea},
+    epaint::{Color32, Rounding},
+};
 
-use super::client::Client;
+use crate::ui::{
+    widgets::{docking, packet::preview},
+    THEME,
+};
 
-pub struct StatTab {}
+use super::{
+    client::Client,
+    topic_tree::{TopicNode, TopicTree},
+};
 
-impl StatTab {
+pub struct TreeTab {
+    search: String,
+    /// topic whose history is shown
+    selected: Option<String>,
+    /// the client `selected` belongs to, the tab is shared by every client
+    client_id: String,
+}
+
+impl TreeTab {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            search: String::new(),
+            selected: None,
+            client_id: String::new(),
+        }
+    }
+
+    fn show_node(&mut self, ui: &mut egui::Ui, path: &str, name: &str, node: &TopicNode) {
+        let query = self.search.to_lowercase();
+        if !query.is_empty() && !matches_below(path, node, &query) {
+            return;
+        }
+        if node.children.is_empty() {
+            ui.horizontal(|ui| {
+                ui.add_space(ui.spacing().indent);
+                self.row(ui, path, name, node);
+            });
+            return;
+        }
+
+        let id = ui.make_persistent_id(("topic_tree", path));
+        let mut state = CollapsingState::load_with_default_open(ui.ctx(), id, false);
+        if !query.is_empty() {
+            state.set_open(true);
+        }
+        state
+            .show_header(ui, |ui| self.row(ui, path, name, node))
+            .body(|ui| {
+                for (level, child) in &node.children {
+                    self.show_node(ui, &format!("{}/{}", path, level), level, child);
+                }
+            });
+    }
+
+    fn row(&mut self, ui: &mut egui::Ui, path: &str, name: &str, node: &TopicNode) {
+        let selected = self.selected.as_deref() == Some(path);
+        let name = if name.is_empty() { "∅" } else { name };
+        if ui
+            .selectable_label(selected, RichText::new(name).color(Color32::KHAKI))
+            .clicked()
+        {
+            self.selected = Some(path.to_owned());
+        }
+        ui.label(RichText::new(node.total.to_string()).color(Color32::from_rgb(128, 140, 255)))
+            .on_hover_text(format!(
+                "{} on this topic, {} including subtopics",
+                node.count, node.total
+            ));
+        if let Some(updated) = node.updated {
+            ui.label(RichText::new(ago(updated)).color(THEME.colors.lighter_gray))
+                .on_hover_text(updated.format("%Y-%m-%d %H:%M:%S").to_string());
+        }
+        if let Some((_, last)) = node.last() {
+            if last.retain {
+                ui.colored_label(Color32::GOLD, "R")
+                    .on_hover_text("last message was retained");
+            }
+            ui.add(egui::Label::new(preview(&last.payload, Color32::LIGHT_GREEN)).wrap(false));
+        }
+    }
+
+    fn show_history(&self, ui: &mut egui::Ui, tree: &TopicTree) {
+        let Some(topic) = &self.selected else {
+            ui.centered_and_justified(|ui| ui.label("select a topic"));
+            return;
+        };
+        ui.label(RichText::new(topic).color(Color32::KHAKI).strong());
+        let Some(node) = tree.get(topic) else {
+            return;
+        };
+        if node.count == 0 {
+            ui.label("nothing published on this exact topic");
+            return;
+        }
+        if node.history.is_empty() {
+            ui.label(format!("{} published, none of them kept", node.count));
+            return;
+        }
+        ui.label(format!("last {} of {}", node.history.len(), node.count));
+        ScrollArea::vertical()
+            .id_source("topic_history")
+            .show(ui, |ui| {
+                for (time, publish) in node.history.iter().rev() {
+                    Frame {
+                        fill: Color32::BLACK,
+                        inner_margin: Margin::same(6.0),
+                        rounding: Rounding::same(6.0),
+                        ..Frame::default()
+                    }
+                    .show(ui, |ui| {
+                        ui.set_width(ui.available_width());
+                        ui.horizontal(|ui| {
+                            ui.label(time.format("%H:%M:%S%.3f").to_string());
+                            ui.colored_label(
+                                Color32::from_rgb(128, 140, 255),
+                                format!("QoS {}", publish.qos as u8),
+                            );
+                            if publish.retain {
+                                ui.colored_label(Color32::GOLD, "R");
+                            }
+                        });
+                        match std::str::from_utf8(&publish.payload) {
+                            Ok(text) => ui
+                                .label(RichText::new(text).color(Color32::LIGHT_GREEN).monospace()),
+                            Err(_) => ui.label(preview(&publish.payload, Color32::LIGHT_GREEN)),
+                        };
+                    });
+                    ui.add_space(4.0);
+                }
+            });
     }
 }
 
-impl docking::Tab<Client> for StatTab {
+impl docking::Tab<Client> for TreeTab {
     fn title(&self) -> &str {
-        "📈 stat"
+        "🌲 topics"
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, client: &mut Client) {
+        let client_id = client.options.client_id();
+        if client_id != self.client_id {
+            self.selected = None;
+            self.client_id = client_id;
+        }
+        // keeps the time since the last message current
+        ui.ctx().request_repaint_after(Duration::from_secs(1));
+
+        ui.push_id("topic_tree", |ui| {
+            ui.horizontal(|ui| {
+                ui.colored_label(Color32::YELLOW, "🔍");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.search)
+                        .hint_text("search topics")
+                        .desired_width(200.0),
+                );
+                if ui.button("ｘ").clicked() {
+                    self.search.clear();
+                }
+                ui.label(
+                    RichText::new(format!("{} messages", client.topics.root.total))
+                        .color(THEME.colors.lighter_gray),
+                );
+                if ui.button(RichText::new("🗑").color(Color32::RED)).clicked() {
+                    client.topics.clear();
+                    self.selected = None;
+                }
+            });
+            ui.separator();
+
+            ui.columns(2, |columns| {
+                ScrollArea::both()
+                    .id_source("topics")
+                    .auto_shrink([false; 2])
+                    .show(&mut columns[0], |ui| {
+                        for (level, node) in &client.topics.root.children {
+                            self.show_node(ui, level, level, node);
+                        }
+                    });
+                self.show_history(&mut columns[1], &client.topics);
+            });
+        });
     }
+}
+
+/// Whether the search matches `path` or a topic below it
+fn matches_below(path: &str, node: &TopicNode, query: &str) -> bool {
+    path.to_lowercase().contains(query)
+        || node
+            .children
+            .iter()
+            .any(|(level, child)| matches_below(&format!("{}/{}", path, level), child, query))
+}
 
-    fn ui(&mut self, ui: &mut egui::Ui, _client: &mut Client) {
-        ui.push_id("statistics", |_ui| {});
+/// Short time since `time`
+fn ago(time: DateTime<Local>) -> String {
+    let secs = (Local::now() - time).num_seconds().max(0);
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
     }
 }
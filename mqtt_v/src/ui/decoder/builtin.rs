@@ -0,0 +1,206 @@
+use std::fmt::Write;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::Value;
+
+use super::{json::Json, Decoded, PayloadDecoder, AUTO};
+
+/// bytes per line of the hex dump
+const HEX_LINE: usize = 16;
+
+/// base64 is wrapped like MIME does
+const BASE64_LINE: usize = 76;
+
+/// JSON when it parses, text when it is UTF-8, a hex dump otherwise
+pub struct Auto;
+
+impl PayloadDecoder for Auto {
+    fn name(&self) -> &str {
+        AUTO
+    }
+
+    fn detect(&self, _payload: &[u8]) -> bool {
+        true
+    }
+
+    fn decode(&self, payload: &[u8]) -> Result<Decoded, String> {
+        let candidates: [&dyn PayloadDecoder; 2] = [&Json, &Text];
+        candidates
+            .into_iter()
+            .find(|d| d.detect(payload))
+            .unwrap_or(&Hex)
+            .decode(payload)
+    }
+
+    fn value(&self, payload: &[u8]) -> Option<Value> {
+        Json.value(payload)
+    }
+}
+
+/// The payload as UTF-8, invalid sequences replaced
+pub struct Text;
+
+impl PayloadDecoder for Text {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    fn detect(&self, payload: &[u8]) -> bool {
+        std::str::from_utf8(payload).is_ok()
+    }
+
+    fn decode(&self, payload: &[u8]) -> Result<Decoded, String> {
+        Ok(Decoded::Text(String::from_utf8_lossy(payload).into_owned()))
+    }
+}
+
+/// Offsets, hex bytes and their ASCII, like `hexdump -C`
+pub struct Hex;
+
+impl PayloadDecoder for Hex {
+    fn name(&self) -> &str {
+        "hex"
+    }
+
+    fn detect(&self, _payload: &[u8]) -> bool {
+        true
+    }
+
+    fn decode(&self, payload: &[u8]) -> Result<Decoded, String> {
+        let mut dump = String::with_capacity(payload.len() / HEX_LINE * 80 + 80);
+        for (line, bytes) in payload.chunks(HEX_LINE).enumerate() {
+            let _ = write!(dump, "{:08x} ", line * HEX_LINE);
+            for i in 0..HEX_LINE {
+                if i % 8 == 0 {
+                    dump.push(' ');
+                }
+                match bytes.get(i) {
+                    Some(b) => {
+                        let _ = write!(dump, "{:02x} ", b);
+                    }
+                    None => dump.push_str("   "),
+                }
+            }
+            dump.push_str(" |");
+            dump.extend(bytes.iter().map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            }));
+            dump.push_str("|\n");
+        }
+        let _ = write!(dump, "{:08x}", payload.len());
+        Ok(Decoded::Text(dump))
+    }
+}
+
+pub struct Base64;
+
+impl PayloadDecoder for Base64 {
+    fn name(&self) -> &str {
+        "base64"
+    }
+
+    fn detect(&self, _payload: &[u8]) -> bool {
+        true
+    }
+
+    fn decode(&self, payload: &[u8]) -> Result<Decoded, String> {
+        let encoded = STANDARD.encode(payload);
+        let lines: Vec<&str> = encoded
+            .as_bytes()
+            .chunks(BASE64_LINE)
+            // the alphabet is ASCII
+            .map(|line| std::str::from_utf8(line).unwrap())
+            .collect();
+        Ok(Decoded::Text(lines.join("\n")))
+    }
+}
+
+/// Only the size and the kind of bytes, for payloads too big or too opaque
+/// to be worth showing
+pub struct Summary;
+
+impl PayloadDecoder for Summary {
+    fn name(&self) -> &str {
+        "length"
+    }
+
+    fn detect(&self, _payload: &[u8]) -> bool {
+        true
+    }
+
+    fn decode(&self, payload: &[u8]) -> Result<Decoded, String> {
+        let printable = payload
+            .iter()
+            .filter(|b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+            .count();
+        let zeros = payload.iter().filter(|&&b| b == 0).count();
+        let head: String = payload
+            .iter()
+            .take(8)
+            .map(|b| format!("{:02x} ", b))
+            .collect();
+        Ok(Decoded::Text(format!(
+            "{} bytes, {} printable, {} zero\nstarts with {}",
+            payload.len(),
+            printable,
+            zeros,
+            head.trim_end()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(decoded: Result<Decoded, String>) -> String {
+        match decoded {
+            Ok(Decoded::Text(text)) => text,
+            _ => panic!("not text"),
+        }
+    }
+
+    #[test]
+    fn auto_picks_json_then_text_then_hex() {
+        assert!(matches!(
+            Auto.decode(br#"{"a":1}"#),
+            Ok(Decoded::Highlighted(_))
+        ));
+        assert_eq!(text(Auto.decode(b"plain")), "plain");
+        assert!(text(Auto.decode(&[0xff, 0x00])).starts_with("00000000  ff 00"));
+        assert_eq!(Auto.value(br#"{"a":1}"#), Some(serde_json::json!({"a": 1})));
+        assert_eq!(Auto.value(b"plain"), None);
+    }
+
+    #[test]
+    fn hex_dump_lines() {
+        let payload: Vec<u8> = (0x41..0x41 + 17).collect();
+        let dump = text(Hex.decode(&payload));
+        let lines: Vec<_> = dump.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "00000000  41 42 43 44 45 46 47 48  49 4a 4b 4c 4d 4e 4f 50  |ABCDEFGHIJKLMNOP|",
+                "00000010  51                                                |Q|",
+                "00000011",
+            ]
+        );
+        assert_eq!(text(Hex.decode(b"")), "00000000");
+    }
+
+    #[test]
+    fn base64_wraps_and_summary_counts() {
+        let encoded = text(Base64.decode(&[0; 60]));
+        let lines: Vec<_> = encoded.lines().map(str::len).collect();
+        assert_eq!(lines, [BASE64_LINE, 80 - BASE64_LINE]);
+
+        assert_eq!(
+            text(Summary.decode(b"ab\0\xff")),
+            "4 bytes, 2 printable, 1 zero\nstarts with 61 62 00 ff"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use ciborium::value::Value as CborValue;
+use serde_json::Value;
+
+use super::{byte_string, number, Decoded, PayloadDecoder};
+
+/// RFC 8949 Concise Binary Object Representation
+pub struct Cbor;
+
+impl Cbor {
+    /// The single item making up the whole payload
+    fn parse(payload: &[u8]) -> Result<CborValue, String> {
+        let mut rest = payload;
+        let value: CborValue = ciborium::from_reader(&mut rest).map_err(|e| match e {
+            ciborium::de::Error::Io(e) => format!("truncated, {}", e),
+            ciborium::de::Error::Syntax(at) => format!("invalid at byte {}", at),
+            ciborium::de::Error::Semantic(_, e) => e,
+            ciborium::de::Error::RecursionLimitExceeded => "nested too deep".to_owned(),
+        })?;
+        if !rest.is_empty() {
+            return Err(format!("{} bytes after the first item", rest.len()));
+        }
+        Ok(value)
+    }
+}
+
+impl PayloadDecoder for Cbor {
+    fn name(&self) -> &str {
+        "cbor"
+    }
+
+    fn detect(&self, payload: &[u8]) -> bool {
+        Self::parse(payload).is_ok()
+    }
+
+    fn decode(&self, payload: &[u8]) -> Result<Decoded, String> {
+        Self::parse(payload).map(|value| Decoded::Tree(tree(value)))
+    }
+
+    fn encodes(&self) -> bool {
+        true
+    }
+
+    fn encode(&self, json: &str) -> Result<Vec<u8>, String> {
+        let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let mut bytes = vec![];
+        ciborium::into_writer(&value, &mut bytes).map_err(|e| e.to_string())?;
+        Ok(bytes)
+    }
+}
+
+/// Byte strings become `h'..'` strings, tags an object keyed by the tag and
+/// map keys their diagnostic notation
+fn tree(value: CborValue) -> Value {
+    match value {
+        CborValue::Integer(i) => {
+            let i = i128::from(i);
+            match (i64::try_from(i), u64::try_from(i)) {
+                (Ok(i), _) => Value::from(i),
+                (_, Ok(u)) => Value::from(u),
+                _ => Value::String(i.to_string()),
+            }
+        }
+        CborValue::Bytes(bytes) => Value::String(byte_string(&bytes)),
+        CborValue::Float(f) => number(f),
+        CborValue::Text(text) => Value::String(text),
+        CborValue::Bool(b) => Value::Bool(b),
+        CborValue::Null => Value::Null,
+        CborValue::Tag(tag, value) => Value::Object(
+            [(format!("tag {}", tag), tree(*value))]
+                .into_iter()
+                .collect(),
+        ),
+        CborValue::Array(items) => Value::Array(items.into_iter().map(tree).collect()),
+        CborValue::Map(entries) => Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = match tree(key) {
+                        Value::String(key) => key,
+                        key => key.to_string(),
+                    };
+                    (key, tree(value))
+                })
+                .collect(),
+        ),
+        _ => Value::String("unsupported".to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn round_trips_json() {
+        let written = r#"{"a":[1,-2,1.5,"x",null,true]}"#;
+        let bytes = Cbor.encode(written).unwrap();
+        assert!(Cbor.detect(&bytes));
+        assert_eq!(
+            Cbor.value(&bytes),
+            Some(json!({"a": [1, -2, 1.5, "x", null, true]}))
+        );
+        assert!(Cbor.encode("{").is_err());
+    }
+
+    #[test]
+    fn bytes_tags_and_keys() {
+        let value = CborValue::Map(vec![
+            (
+                CborValue::Integer(1.into()),
+                CborValue::Bytes(vec![0xab, 0x01]),
+            ),
+            (
+                CborValue::Text("t".to_owned()),
+                CborValue::Tag(1, Box::new(CborValue::Integer(0.into()))),
+            ),
+            (CborValue::Text("f".to_owned()), CborValue::Float(f64::NAN)),
+        ]);
+        let mut bytes = vec![];
+        ciborium::into_writer(&value, &mut bytes).unwrap();
+        assert_eq!(
+            Cbor.value(&bytes),
+            Some(json!({"1": "h'ab01'", "t": {"tag 1": 0}, "f": "NaN"}))
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_and_trailing_bytes() {
+        let bytes = Cbor.encode("[1, 2]").unwrap();
+        assert!(Cbor.decode(&bytes[..bytes.len() - 1]).is_err());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Cbor.decode(&trailing).err().unwrap(),
+            "1 bytes after the first item"
+        );
+    }
+}
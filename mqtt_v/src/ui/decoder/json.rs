@@ -0,0 +1,97 @@
+use eframe::epaint::{
+    text::{LayoutJob, TextFormat},
+    Color32,
+};
+use serde_json::Value;
+
+use super::{Decoded, PayloadDecoder};
+
+const INDENT: &str = "  ";
+
+/// Pretty printed and colored by token
+pub struct Json;
+
+impl PayloadDecoder for Json {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn detect(&self, payload: &[u8]) -> bool {
+        serde_json::from_slice::<Value>(payload).is_ok()
+    }
+
+    fn decode(&self, payload: &[u8]) -> Result<Decoded, String> {
+        let value: Value = serde_json::from_slice(payload).map_err(|e| e.to_string())?;
+        let mut job = LayoutJob::default();
+        highlight(&mut job, &value, 0);
+        Ok(Decoded::Highlighted(job))
+    }
+
+    fn value(&self, payload: &[u8]) -> Option<Value> {
+        serde_json::from_slice(payload).ok()
+    }
+}
+
+/// Appends `value` to `job` pretty printed, `depth` levels indented
+pub fn highlight(job: &mut LayoutJob, value: &Value, depth: usize) {
+    match value {
+        Value::Null => append(job, "null", Color32::GOLD),
+        Value::Bool(b) => append(job, &b.to_string(), Color32::GOLD),
+        Value::Number(n) => append(job, &n.to_string(), Color32::from_rgb(128, 140, 255)),
+        Value::String(s) => append(job, &quoted(s), Color32::LIGHT_GREEN),
+        Value::Array(items) if items.is_empty() => append(job, "[]", Color32::GRAY),
+        Value::Object(fields) if fields.is_empty() => append(job, "{}", Color32::GRAY),
+        Value::Array(items) => {
+            append(job, "[", Color32::GRAY);
+            for (i, item) in items.iter().enumerate() {
+                separate(job, i, depth + 1);
+                highlight(job, item, depth + 1);
+            }
+            close(job, "]", depth);
+        }
+        Value::Object(fields) => {
+            append(job, "{", Color32::GRAY);
+            for (i, (key, item)) in fields.iter().enumerate() {
+                separate(job, i, depth + 1);
+                append(job, &quoted(key), Color32::KHAKI);
+                append(job, ": ", Color32::GRAY);
+                highlight(job, item, depth + 1);
+            }
+            close(job, "}", depth);
+        }
+    }
+}
+
+fn separate(job: &mut LayoutJob, index: usize, depth: usize) {
+    let comma = if index > 0 { "," } else { "" };
+    append(
+        job,
+        &format!("{}\n{}", comma, INDENT.repeat(depth)),
+        Color32::GRAY,
+    );
+}
+
+fn close(job: &mut LayoutJob, bracket: &str, depth: usize) {
+    append(
+        job,
+        &format!("\n{}{}", INDENT.repeat(depth), bracket),
+        Color32::GRAY,
+    );
+}
+
+fn quoted(s: &str) -> String {
+    // serializing a string can not fail
+    serde_json::to_string(s).unwrap_or_default()
+}
+
+/// The font is left to whoever lays the job out
+fn append(job: &mut LayoutJob, text: &str, color: Color32) {
+    job.append(
+        text,
+        0.0,
+        TextFormat {
+            color,
+            ..TextFormat::default()
+        },
+    );
+}
@@ -0,0 +1,200 @@
+use eframe::epaint::text::LayoutJob;
+use prost_reflect::DescriptorPool;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::ui::client::client::topic_matches;
+
+mod builtin;
+mod cbor;
+mod json;
+mod msgpack;
+mod protobuf;
+pub mod sparkplug;
+
+pub use builtin::{Auto, Base64, Hex, Summary, Text};
+pub use cbor::Cbor;
+pub use json::Json;
+pub use msgpack::MsgPack;
+pub use protobuf::Protobuf;
+pub use sparkplug::SparkplugB;
+
+/// name of the decoder used when no rule matches a topic
+pub const AUTO: &str = "auto";
+
+/// Turns a payload into something readable
+pub trait PayloadDecoder {
+    /// Unique, shown in the pickers and stored in the topic rules
+    fn name(&self) -> &str;
+
+    /// Whether the payload looks like this format, used by auto-detection
+    fn detect(&self, payload: &[u8]) -> bool;
+
+    fn decode(&self, payload: &[u8]) -> Result<Decoded, String>;
+
+    /// The payload as a tree of values, for following a field across messages
+    fn value(&self, payload: &[u8]) -> Option<Value> {
+        match self.decode(payload) {
+            Ok(Decoded::Tree(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Whether `encode` turns JSON into this format
+    fn encodes(&self) -> bool {
+        false
+    }
+
+    /// The bytes published for a payload written in the publish tab, as
+    /// written unless the format `encodes`
+    fn encode(&self, written: &str) -> Result<Vec<u8>, String> {
+        Ok(written.as_bytes().to_vec())
+    }
+}
+
+pub enum Decoded {
+    /// shown in the color of the message direction
+    Text(String),
+    /// carries its own colors
+    Highlighted(LayoutJob),
+    /// structured formats, shown as collapsible fields
+    Tree(Value),
+}
+
+/// The decoder of the topics matching `filter`
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecoderRule {
+    pub filter: String,
+    pub decoder: String,
+}
+
+/// A `.proto` file or a serialized `FileDescriptorSet`
+pub struct Schema {
+    pub path: String,
+    /// why it could not be loaded, its message types are missing then
+    pub error: Option<String>,
+}
+
+/// The decoders a client picks from and the rules choosing one per topic
+pub struct Decoders {
+    decoders: Vec<Box<dyn PayloadDecoder>>,
+    /// the first rule whose filter matches a topic wins
+    pub rules: Vec<DecoderRule>,
+    /// every message type they define gets a decoder
+    schemas: Vec<Schema>,
+}
+
+impl Decoders {
+    pub fn new(rules: Vec<DecoderRule>, schemas: Vec<String>) -> Self {
+        let mut decoders = Self {
+            decoders: vec![],
+            rules,
+            schemas: schemas
+                .into_iter()
+                .map(|path| Schema { path, error: None })
+                .collect(),
+        };
+        decoders.reload();
+        decoders
+    }
+
+    pub fn schemas(&self) -> &[Schema] {
+        &self.schemas
+    }
+
+    pub fn add_schema(&mut self, path: String) {
+        if self.schemas.iter().all(|s| s.path != path) {
+            self.schemas.push(Schema { path, error: None });
+            self.reload();
+        }
+    }
+
+    pub fn remove_schema(&mut self, index: usize) {
+        self.schemas.remove(index);
+        self.reload();
+    }
+
+    /// Loads the schemas again and rebuilds the decoders from them
+    pub fn reload(&mut self) {
+        let mut pool = DescriptorPool::new();
+        for schema in &mut self.schemas {
+            schema.error = protobuf::load(&mut pool, &schema.path).err();
+        }
+
+        self.decoders = vec![
+            Box::new(Auto),
+            Box::new(Text),
+            Box::new(Json),
+            Box::new(Hex),
+            Box::new(Base64),
+            Box::new(Summary),
+            Box::new(Cbor),
+            Box::new(MsgPack),
+            Box::new(SparkplugB),
+        ];
+        let mut messages: Vec<_> = pool
+            .all_messages()
+            .filter(|message| !message.is_map_entry())
+            .collect();
+        messages.sort_by(|a, b| a.full_name().cmp(b.full_name()));
+        self.decoders.extend(
+            messages
+                .into_iter()
+                .map(|message| Box::new(Protobuf::new(message)) as Box<dyn PayloadDecoder>),
+        );
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.decoders.iter().map(|d| d.name())
+    }
+
+    /// Decoders that turn JSON into their format
+    pub fn encoders(&self) -> impl Iterator<Item = &str> {
+        self.decoders
+            .iter()
+            .filter(|d| d.encodes())
+            .map(|d| d.name())
+    }
+
+    /// The decoder called `name`, auto-detection for unknown names
+    pub fn get(&self, name: &str) -> &dyn PayloadDecoder {
+        self.decoders
+            .iter()
+            .find(|d| d.name() == name)
+            .unwrap_or(&self.decoders[0])
+            .as_ref()
+    }
+
+    /// The decoder picked for a packet, or the one of its topic
+    pub fn choose(&self, picked: &Option<String>, topic: &str) -> &dyn PayloadDecoder {
+        match picked {
+            Some(name) => self.get(name),
+            None => self.for_topic(topic),
+        }
+    }
+
+    /// The decoder of the first rule matching `topic`, without one Sparkplug
+    /// B topics are known by their namespace and the rest is auto-detected
+    pub fn for_topic(&self, topic: &str) -> &dyn PayloadDecoder {
+        match self
+            .rules
+            .iter()
+            .find(|rule| topic_matches(&rule.filter, topic))
+        {
+            Some(rule) => self.get(&rule.decoder),
+            None if sparkplug::Topic::parse(topic).is_some() => self.get(SparkplugB.name()),
+            None => &*self.decoders[0],
+        }
+    }
+}
+
+/// Binary data inside a tree, in CBOR diagnostic notation
+fn byte_string(bytes: &[u8]) -> String {
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("h'{}'", hex)
+}
+
+/// JSON has no NaN nor infinity, those become strings
+fn number(f: f64) -> Value {
+    serde_json::Number::from_f64(f).map_or_else(|| Value::String(f.to_string()), Value::Number)
+}
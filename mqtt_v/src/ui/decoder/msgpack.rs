@@ -0,0 +1,150 @@
+use rmpv::Value as MsgPackValue;
+use serde_json::Value;
+
+use super::{byte_string, number, Decoded, PayloadDecoder};
+
+pub struct MsgPack;
+
+impl MsgPack {
+    /// The single value making up the whole payload
+    fn parse(payload: &[u8]) -> Result<MsgPackValue, String> {
+        let mut rest = payload;
+        let value = rmpv::decode::read_value(&mut rest).map_err(|e| e.to_string())?;
+        if !rest.is_empty() {
+            return Err(format!("{} bytes after the first value", rest.len()));
+        }
+        Ok(value)
+    }
+}
+
+impl PayloadDecoder for MsgPack {
+    fn name(&self) -> &str {
+        "msgpack"
+    }
+
+    fn detect(&self, payload: &[u8]) -> bool {
+        Self::parse(payload).is_ok()
+    }
+
+    fn decode(&self, payload: &[u8]) -> Result<Decoded, String> {
+        Self::parse(payload).map(|value| Decoded::Tree(tree(value)))
+    }
+
+    fn encodes(&self) -> bool {
+        true
+    }
+
+    fn encode(&self, json: &str) -> Result<Vec<u8>, String> {
+        let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let mut bytes = vec![];
+        rmpv::encode::write_value(&mut bytes, &from_json(value)).map_err(|e| e.to_string())?;
+        Ok(bytes)
+    }
+}
+
+/// Binaries become `h'..'` strings, extensions an object keyed by their type
+/// and map keys their JSON text
+fn tree(value: MsgPackValue) -> Value {
+    match value {
+        MsgPackValue::Nil => Value::Null,
+        MsgPackValue::Boolean(b) => Value::Bool(b),
+        MsgPackValue::Integer(i) => match (i.as_i64(), i.as_u64()) {
+            (Some(i), _) => Value::from(i),
+            (_, Some(u)) => Value::from(u),
+            _ => Value::Null,
+        },
+        MsgPackValue::F32(f) => number(f as f64),
+        MsgPackValue::F64(f) => number(f),
+        MsgPackValue::String(s) => match s.into_str() {
+            Some(s) => Value::String(s),
+            None => Value::String("invalid UTF-8".to_owned()),
+        },
+        MsgPackValue::Binary(bytes) => Value::String(byte_string(&bytes)),
+        MsgPackValue::Array(items) => Value::Array(items.into_iter().map(tree).collect()),
+        MsgPackValue::Map(entries) => Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = match tree(key) {
+                        Value::String(key) => key,
+                        key => key.to_string(),
+                    };
+                    (key, tree(value))
+                })
+                .collect(),
+        ),
+        MsgPackValue::Ext(kind, bytes) => Value::Object(
+            [(format!("ext {}", kind), Value::String(byte_string(&bytes)))]
+                .into_iter()
+                .collect(),
+        ),
+    }
+}
+
+fn from_json(value: Value) -> MsgPackValue {
+    match value {
+        Value::Null => MsgPackValue::Nil,
+        Value::Bool(b) => MsgPackValue::Boolean(b),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) => MsgPackValue::from(u),
+            (_, Some(i)) => MsgPackValue::from(i),
+            _ => MsgPackValue::F64(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s) => MsgPackValue::from(s),
+        Value::Array(items) => MsgPackValue::Array(items.into_iter().map(from_json).collect()),
+        Value::Object(fields) => MsgPackValue::Map(
+            fields
+                .into_iter()
+                .map(|(key, value)| (MsgPackValue::from(key), from_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn round_trips_json() {
+        let written = r#"{"a":[1,-2,1.5,"x",null,true],"big":18446744073709551615}"#;
+        let bytes = MsgPack.encode(written).unwrap();
+        assert!(MsgPack.detect(&bytes));
+        assert_eq!(
+            MsgPack.value(&bytes),
+            Some(json!({"a": [1, -2, 1.5, "x", null, true], "big": u64::MAX}))
+        );
+        assert!(MsgPack.encode("{").is_err());
+    }
+
+    #[test]
+    fn binaries_extensions_and_keys() {
+        let value = MsgPackValue::Map(vec![
+            (
+                MsgPackValue::from(1),
+                MsgPackValue::Binary(vec![0xab, 0x01]),
+            ),
+            (MsgPackValue::from("e"), MsgPackValue::Ext(5, vec![0xff])),
+        ]);
+        let mut bytes = vec![];
+        rmpv::encode::write_value(&mut bytes, &value).unwrap();
+        assert_eq!(
+            MsgPack.value(&bytes),
+            Some(json!({"1": "h'ab01'", "e": {"ext 5": "h'ff'"}}))
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_and_trailing_bytes() {
+        let bytes = MsgPack.encode("[1, 2]").unwrap();
+        assert!(MsgPack.decode(&bytes[..bytes.len() - 1]).is_err());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            MsgPack.decode(&trailing).err().unwrap(),
+            "1 bytes after the first value"
+        );
+    }
+}
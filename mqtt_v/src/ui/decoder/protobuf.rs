@@ -0,0 +1,188 @@
+use std::{fs, path::Path};
+
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, SerializeOptions};
+
+use super::{Decoded, PayloadDecoder};
+
+/// start of the protobuf decoder names, the full message name follows
+pub const PREFIX: &str = "protobuf ";
+
+/// Decodes one message type of a loaded schema
+pub struct Protobuf {
+    name: String,
+    message: MessageDescriptor,
+}
+
+impl Protobuf {
+    pub fn new(message: MessageDescriptor) -> Self {
+        Self {
+            name: format!("{}{}", PREFIX, message.full_name()),
+            message,
+        }
+    }
+}
+
+impl PayloadDecoder for Protobuf {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn detect(&self, payload: &[u8]) -> bool {
+        DynamicMessage::decode(self.message.clone(), payload).is_ok()
+    }
+
+    fn decode(&self, payload: &[u8]) -> Result<Decoded, String> {
+        let message =
+            DynamicMessage::decode(self.message.clone(), payload).map_err(|e| e.to_string())?;
+        // every field, named as in the .proto file, 64 bit integers as numbers
+        let options = SerializeOptions::new()
+            .skip_default_fields(false)
+            .use_proto_field_name(true)
+            .stringify_64_bit_integers(false);
+        let value = message
+            .serialize_with_options(serde_json::value::Serializer, &options)
+            .map_err(|e| e.to_string())?;
+        Ok(Decoded::Tree(value))
+    }
+
+    fn encodes(&self) -> bool {
+        true
+    }
+
+    fn encode(&self, json: &str) -> Result<Vec<u8>, String> {
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let message = DynamicMessage::deserialize(self.message.clone(), &mut deserializer)
+            .and_then(|message| deserializer.end().map(|_| message))
+            .map_err(|e| e.to_string())?;
+        Ok(message.encode_to_vec())
+    }
+}
+
+/// Adds the schema at `path` to `pool`, a `.proto` file is compiled with the
+/// imports found next to it, anything else is read as a `FileDescriptorSet`
+pub fn load(pool: &mut DescriptorPool, path: &str) -> Result<(), String> {
+    let path = Path::new(path);
+    if path.extension().is_some_and(|ext| ext == "proto") {
+        let file = path.file_name().ok_or("not a file")?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let files = protox::compile([file], [dir]).map_err(|e| e.to_string())?;
+        pool.add_file_descriptor_set(files)
+            .map_err(|e| e.to_string())
+    } else {
+        let bytes = fs::read(path).map_err(|e| e.to_string())?;
+        pool.decode_file_descriptor_set(bytes.as_slice())
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::ui::decoder::Decoders;
+
+    const PROTO: &str = r#"
+        syntax = "proto3";
+        package demo;
+
+        message Reading {
+            string sensor = 1;
+            int64 at = 2;
+            repeated double values = 3;
+            Unit unit = 4;
+        }
+
+        enum Unit {
+            CELSIUS = 0;
+            KELVIN = 1;
+        }
+    "#;
+
+    /// A directory of its own, protox resolves imports next to the file
+    fn proto_file(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("mqtt_v-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("reading.proto");
+        fs::write(&path, PROTO).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn reading(pool: &DescriptorPool) -> Protobuf {
+        Protobuf::new(pool.get_message_by_name("demo.Reading").unwrap())
+    }
+
+    #[test]
+    fn round_trips_json_through_a_proto_file() {
+        let path = proto_file("proto");
+        let mut pool = DescriptorPool::new();
+        load(&mut pool, &path).unwrap();
+        fs::remove_dir_all(Path::new(&path).parent().unwrap()).unwrap();
+
+        let decoder = reading(&pool);
+        assert_eq!(decoder.name(), "protobuf demo.Reading");
+        let written =
+            r#"{"sensor": "s1", "at": "1700000000000", "values": [1.5, 2], "unit": "KELVIN"}"#;
+        let bytes = decoder.encode(written).unwrap();
+        assert!(decoder.detect(&bytes));
+        assert_eq!(
+            decoder.value(&bytes),
+            Some(json!({
+                "sensor": "s1",
+                "at": 1_700_000_000_000i64,
+                "values": [1.5, 2.0],
+                "unit": "KELVIN"
+            }))
+        );
+        // fields left out are shown with their default
+        let empty = decoder.encode("{}").unwrap();
+        assert_eq!(
+            decoder.value(&empty),
+            Some(json!({"sensor": "", "at": 0, "values": [], "unit": "CELSIUS"}))
+        );
+
+        assert!(decoder.encode(r#"{"missing": 1}"#).is_err());
+        assert!(decoder.encode(r#"{} {}"#).is_err());
+        assert!(decoder.decode(&[0xff, 0xff]).is_err());
+    }
+
+    #[test]
+    fn reads_a_file_descriptor_set() {
+        let path = proto_file("descriptor");
+        let dir = Path::new(&path).parent().unwrap().to_owned();
+        let set = protox::compile(["reading.proto"], [&dir]).unwrap();
+        let set_path = dir.join("reading.pb");
+        fs::write(&set_path, set.encode_to_vec()).unwrap();
+
+        let mut pool = DescriptorPool::new();
+        load(&mut pool, &set_path.to_string_lossy()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let bytes = reading(&pool).encode(r#"{"sensor": "s2"}"#).unwrap();
+        assert_eq!(reading(&pool).value(&bytes).unwrap()["sensor"], json!("s2"));
+    }
+
+    #[test]
+    fn missing_schemas_are_reported() {
+        let mut pool = DescriptorPool::new();
+        assert!(load(&mut pool, "/nonexistent/reading.proto").is_err());
+        assert!(load(&mut pool, "/nonexistent/reading.pb").is_err());
+
+        let path = proto_file("decoders");
+        let decoders = Decoders::new(
+            vec![],
+            vec!["/nonexistent/a.proto".to_owned(), path.clone()],
+        );
+        fs::remove_dir_all(Path::new(&path).parent().unwrap()).unwrap();
+        let errors: Vec<_> = decoders
+            .schemas()
+            .iter()
+            .map(|s| s.error.is_some())
+            .collect();
+        assert_eq!(errors, [true, false]);
+        assert!(decoders.names().any(|name| name == "protobuf demo.Reading"));
+    }
+}
@@ -0,0 +1,268 @@
+use prost::Message;
+use serde_json::{json, Map, Value};
+
+use super::{byte_string, number, Decoded, PayloadDecoder};
+
+/// first level of every Sparkplug B topic
+pub const NAMESPACE: &str = "spBv1.0";
+
+/// The Sparkplug B payload, the parts of `sparkplug_b.proto` worth showing
+#[derive(Clone, PartialEq, Message)]
+pub struct Payload {
+    #[prost(uint64, optional, tag = "1")]
+    pub timestamp: Option<u64>,
+    #[prost(message, repeated, tag = "2")]
+    pub metrics: Vec<Metric>,
+    #[prost(uint64, optional, tag = "3")]
+    pub seq: Option<u64>,
+    #[prost(string, optional, tag = "4")]
+    pub uuid: Option<String>,
+    #[prost(bytes = "vec", optional, tag = "5")]
+    pub body: Option<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Metric {
+    #[prost(string, optional, tag = "1")]
+    pub name: Option<String>,
+    #[prost(uint64, optional, tag = "2")]
+    pub alias: Option<u64>,
+    #[prost(uint64, optional, tag = "3")]
+    pub timestamp: Option<u64>,
+    #[prost(uint32, optional, tag = "4")]
+    pub datatype: Option<u32>,
+    #[prost(bool, optional, tag = "5")]
+    pub is_historical: Option<bool>,
+    #[prost(bool, optional, tag = "6")]
+    pub is_transient: Option<bool>,
+    #[prost(bool, optional, tag = "7")]
+    pub is_null: Option<bool>,
+    #[prost(oneof = "MetricValue", tags = "10, 11, 12, 13, 14, 15, 16, 17, 18, 19")]
+    pub value: Option<MetricValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum MetricValue {
+    #[prost(uint32, tag = "10")]
+    Int(u32),
+    #[prost(uint64, tag = "11")]
+    Long(u64),
+    #[prost(float, tag = "12")]
+    Float(f32),
+    #[prost(double, tag = "13")]
+    Double(f64),
+    #[prost(bool, tag = "14")]
+    Boolean(bool),
+    #[prost(string, tag = "15")]
+    String(String),
+    #[prost(bytes, tag = "16")]
+    Bytes(Vec<u8>),
+    /// data sets, templates and extensions are kept encoded
+    #[prost(bytes, tag = "17")]
+    DataSet(Vec<u8>),
+    #[prost(bytes, tag = "18")]
+    Template(Vec<u8>),
+    #[prost(bytes, tag = "19")]
+    Extension(Vec<u8>),
+}
+
+impl Metric {
+    pub fn datatype_name(&self) -> &'static str {
+        match self.datatype.unwrap_or(0) {
+            1 => "Int8",
+            2 => "Int16",
+            3 => "Int32",
+            4 => "Int64",
+            5 => "UInt8",
+            6 => "UInt16",
+            7 => "UInt32",
+            8 => "UInt64",
+            9 => "Float",
+            10 => "Double",
+            11 => "Boolean",
+            12 => "String",
+            13 => "DateTime",
+            14 => "Text",
+            15 => "UUID",
+            16 => "DataSet",
+            17 => "Bytes",
+            18 => "File",
+            19 => "Template",
+            20 => "PropertySet",
+            21 => "PropertySetList",
+            22..=34 => "Array",
+            _ => "Unknown",
+        }
+    }
+
+    /// The value as JSON, signed integers are stored as their two's complement
+    pub fn json_value(&self) -> Value {
+        if self.is_null == Some(true) {
+            return Value::Null;
+        }
+        let Some(value) = &self.value else {
+            return Value::Null;
+        };
+        match (self.datatype.unwrap_or(0), value) {
+            (1, MetricValue::Int(v)) => Value::from(*v as i8),
+            (2, MetricValue::Int(v)) => Value::from(*v as i16),
+            (3, MetricValue::Int(v)) => Value::from(*v as i32),
+            (4, MetricValue::Long(v)) => Value::from(*v as i64),
+            (_, MetricValue::Int(v)) => Value::from(*v),
+            (_, MetricValue::Long(v)) => Value::from(*v),
+            (_, MetricValue::Float(v)) => number(*v as f64),
+            (_, MetricValue::Double(v)) => number(*v),
+            (_, MetricValue::Boolean(v)) => Value::Bool(*v),
+            (_, MetricValue::String(v)) => Value::String(v.clone()),
+            (_, MetricValue::Bytes(v))
+            | (_, MetricValue::DataSet(v))
+            | (_, MetricValue::Template(v))
+            | (_, MetricValue::Extension(v)) => Value::String(byte_string(v)),
+        }
+    }
+}
+
+/// The parts of a Sparkplug B topic, `spBv1.0/group/NDATA/node[/device]`
+pub struct Topic<'a> {
+    pub group: &'a str,
+    pub kind: &'a str,
+    pub node: &'a str,
+    pub device: Option<&'a str>,
+}
+
+impl<'a> Topic<'a> {
+    /// `None` for other topics and for the `STATE` of host applications
+    pub fn parse(topic: &'a str) -> Option<Self> {
+        let mut levels = topic.split('/');
+        if levels.next() != Some(NAMESPACE) {
+            return None;
+        }
+        let topic = Topic {
+            group: levels.next()?,
+            kind: levels.next()?,
+            node: levels.next()?,
+            device: levels.next(),
+        };
+        levels.next().is_none().then_some(topic)
+    }
+}
+
+pub struct SparkplugB;
+
+impl PayloadDecoder for SparkplugB {
+    fn name(&self) -> &str {
+        "sparkplug b"
+    }
+
+    fn detect(&self, payload: &[u8]) -> bool {
+        Payload::decode(payload).is_ok()
+    }
+
+    fn decode(&self, payload: &[u8]) -> Result<Decoded, String> {
+        let payload = Payload::decode(payload).map_err(|e| e.to_string())?;
+        let metrics = payload
+            .metrics
+            .iter()
+            .map(|metric| {
+                let mut fields = Map::new();
+                if let Some(name) = &metric.name {
+                    fields.insert("name".to_owned(), json!(name));
+                }
+                if let Some(alias) = metric.alias {
+                    fields.insert("alias".to_owned(), json!(alias));
+                }
+                fields.insert("type".to_owned(), json!(metric.datatype_name()));
+                fields.insert("value".to_owned(), metric.json_value());
+                if let Some(timestamp) = metric.timestamp {
+                    fields.insert("timestamp".to_owned(), json!(timestamp));
+                }
+                if metric.is_historical == Some(true) {
+                    fields.insert("historical".to_owned(), json!(true));
+                }
+                if metric.is_transient == Some(true) {
+                    fields.insert("transient".to_owned(), json!(true));
+                }
+                Value::Object(fields)
+            })
+            .collect::<Vec<_>>();
+
+        let mut tree = Map::new();
+        if let Some(timestamp) = payload.timestamp {
+            tree.insert("timestamp".to_owned(), json!(timestamp));
+        }
+        if let Some(seq) = payload.seq {
+            tree.insert("seq".to_owned(), json!(seq));
+        }
+        if let Some(uuid) = &payload.uuid {
+            tree.insert("uuid".to_owned(), json!(uuid));
+        }
+        tree.insert("metrics".to_owned(), Value::Array(metrics));
+        if let Some(body) = &payload.body {
+            tree.insert("body".to_owned(), json!(byte_string(body)));
+        }
+        Ok(Decoded::Tree(Value::Object(tree)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topics() {
+        let topic = Topic::parse("spBv1.0/plant/DDATA/edge/pump").unwrap();
+        assert_eq!(
+            (topic.group, topic.kind, topic.node, topic.device),
+            ("plant", "DDATA", "edge", Some("pump"))
+        );
+        assert!(Topic::parse("spBv1.0/plant/NBIRTH/edge")
+            .unwrap()
+            .device
+            .is_none());
+        assert!(Topic::parse("spBv1.0/STATE/host").is_none());
+        assert!(Topic::parse("spBv1.0/plant/DDATA/edge/pump/extra").is_none());
+        assert!(Topic::parse("spAv1.0/plant/DDATA/edge").is_none());
+    }
+
+    #[test]
+    fn metrics_with_their_types() {
+        let metric = |name: &str, datatype, value| Metric {
+            name: Some(name.to_owned()),
+            datatype: Some(datatype),
+            value: Some(value),
+            ..Metric::default()
+        };
+        let payload = Payload {
+            timestamp: Some(1),
+            seq: Some(2),
+            metrics: vec![
+                metric("i8", 1, MetricValue::Int(0xff)),
+                metric("i64", 4, MetricValue::Long(u64::MAX)),
+                metric("u32", 7, MetricValue::Int(0xffff_ffff)),
+                metric("bytes", 17, MetricValue::Bytes(vec![0xab])),
+                Metric {
+                    is_null: Some(true),
+                    ..metric("null", 12, MetricValue::String("x".to_owned()))
+                },
+            ],
+            ..Payload::default()
+        };
+        let bytes = payload.encode_to_vec();
+        assert!(SparkplugB.detect(&bytes));
+        assert_eq!(
+            SparkplugB.value(&bytes),
+            Some(json!({
+                "timestamp": 1,
+                "seq": 2,
+                "metrics": [
+                    {"name": "i8", "type": "Int8", "value": -1},
+                    {"name": "i64", "type": "Int64", "value": -1},
+                    {"name": "u32", "type": "UInt32", "value": 0xffff_ffffu32},
+                    {"name": "bytes", "type": "Bytes", "value": "h'ab'"},
+                    {"name": "null", "type": "String", "value": null},
+                ],
+            }))
+        );
+        assert!(SparkplugB.decode(&[0xff]).is_err());
+    }
+}
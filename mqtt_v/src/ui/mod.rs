@@ -1,10 +1,12 @@
-use backend::{message::MqttOpts, Backend};
+use backend::{
+    message::{Event, FromClient, MqttOpts, Packet, Publish},
+    Backend,
+};
 
 use eframe::{
     egui::{
-        menu, Button, CentralPanel, Checkbox, Context, CursorIcon, DragValue, Frame, Id,
-        InnerResponse, Label, LayerId, Layout, RichText, SidePanel, Slider, TextEdit, TextStyle,
-        Ui, Window,
+        menu, Button, CentralPanel, Context, CursorIcon, Frame, Id, InnerResponse, Label, LayerId,
+        Layout, RichText, SidePanel, TextStyle, TopBottomPanel, Ui, Window,
     },
     emath::{Align, Align2},
     epaint::{
@@ -14,7 +16,10 @@ use eframe::{
     CreationContext,
 };
 use once_cell::sync::Lazy;
-use std::thread;
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
 use tokio::sync::mpsc::{Receiver, Sender};
 
 use self::{
@@ -23,16 +28,25 @@ use self::{
     widgets::docking::{self, NodeIndex},
 };
 
+// shared with other apps, it defines more than this one uses
+#[allow(dead_code)]
 mod app_theme;
-mod client;
+pub(crate) mod client;
+mod decoder;
 mod widgets;
 
-use client::{chat_tab, client::Client, tree_tab};
+use client::{
+    chat_tab, client::Client, dashboard_tab, options, plot_tab, profile, session_tab,
+    sparkplug_tab, stat_tab, tree_tab,
+};
 
 use backend::message::{ToBackend, ToFrontend};
 
 static THEME: Lazy<AppTheme> = Lazy::new(AppTheme::default);
 
+/// time spent handling backend messages per frame, the rest waits for the next one
+const DRAIN_BUDGET: Duration = Duration::from_millis(8);
+
 type ClientId = String;
 // #[derive(Default)]
 pub struct MqttAppUI {
@@ -41,7 +55,6 @@ pub struct MqttAppUI {
     back_rx: Receiver<ToFrontend>,
     // state
     state: State,
-    filter: String,
     clients: HashMap<ClientId, Client>,
 
     style: docking::Style,
@@ -54,20 +67,36 @@ struct State {
     settings: bool,
     mqtt_options: MqttOpts,
     active_client: Option<ClientId>,
+    /// backend messages handled in the last frame
+    drained: usize,
 }
 
 impl MqttAppUI {
     pub fn new(cc: &CreationContext) -> Self {
         let (front_tx, front_rx) = tokio::sync::mpsc::channel(2);
-        let (back_tx, back_rx) = tokio::sync::mpsc::channel(10);
+        let (back_tx, back_rx) = tokio::sync::mpsc::channel(4096);
+        let ctx = cc.egui_ctx.clone();
         thread::spawn(move || {
-            Backend::new(back_tx, front_rx).init();
+            Backend::new(back_tx, front_rx, ctx).init();
         });
 
         let event_tab = Box::new(chat_tab::ChatTab::new());
-        let stat_tab = Box::new(tree_tab::StatTab::new());
+        let tree_tab = Box::new(tree_tab::TreeTab::new());
+        let stat_tab = Box::new(stat_tab::StatTab::new());
+        let sparkplug_tab = Box::new(sparkplug_tab::SparkplugTab::new());
+        let plot_tab = Box::new(plot_tab::PlotTab::new());
+        let dashboard_tab = Box::new(dashboard_tab::DashboardTab::new());
+        let session_tab = Box::new(session_tab::SessionTab::new());
         let publish_tab = Box::new(publish_tab::PubulishTab::new());
-        let mut tree = docking::Tree::new(vec![event_tab, stat_tab]);
+        let mut tree = docking::Tree::new(vec![
+            event_tab,
+            tree_tab,
+            stat_tab,
+            sparkplug_tab,
+            plot_tab,
+            dashboard_tab,
+            session_tab,
+        ]);
 
         let [_a, _b] = tree.split_below(NodeIndex::root(), 0.75, vec![publish_tab]);
 
@@ -77,25 +106,20 @@ impl MqttAppUI {
             front_tx,
             back_rx,
             state: State::default(),
-            filter: "".to_owned(),
             clients,
             style: docking::Style::default(),
             tree: Some(tree),
         };
         // load storage
         if let Some(storage) = cc.storage {
-            if let Some(client_opts_list) =
-                eframe::get_value::<Vec<MqttOpts>>(storage, eframe::APP_KEY)
-            {
-                if !client_opts_list.is_empty() {
-                    client_opts_list.iter().for_each(|opts| {
-                        let key = opts.client_id();
-                        let client =
-                            client::client::create_client(opts.clone(), app.front_tx.clone());
-                        app.clients.insert(key, client);
-                    });
-                    app.state.active_client = Some(client_opts_list[0].client_id())
-                }
+            let profiles = profile::load(storage);
+            if let Some(first) = profiles.first() {
+                app.state.active_client = Some(first.options.client_id());
+            }
+            for profile in profiles {
+                let key = profile.options.client_id();
+                let client = client::client::create_client(profile, app.front_tx.clone());
+                app.clients.insert(key, client);
             }
         }
 
@@ -105,48 +129,69 @@ impl MqttAppUI {
 
 impl eframe::App for MqttAppUI {
     fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
-        ctx.request_repaint();
         self.handle_backend_msg(ctx);
+        self.tick_clients(ctx);
         self.render_side_panel(ctx);
+        self.render_status_bar(ctx);
         self.render_central_panel(ctx);
     }
 
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        let connect_opts: Vec<MqttOpts> =
-            self.clients.values().map(|v| v.options.clone()).collect();
-        eframe::set_value(storage, eframe::APP_KEY, &connect_opts);
+        let profiles = self.clients.values().map(Client::profile).collect();
+        profile::save(storage, profiles);
     }
 }
 
 impl MqttAppUI {
-    fn handle_backend_msg(&mut self, _ctx: &Context) {
-        let mut i = 10;
-        while i > 0 {
-            i -= 1;
-
-            match self.back_rx.try_recv() {
-                Ok(msg) => {
-                    match msg {
-                        ToFrontend::ClientMsg(client_id, msg) => {
-                            if let Some(client) = self.clients.get_mut(&client_id) {
-                                client.handle_msg(msg)
-                            }
-                        }
-                        ToFrontend::ClientCreated(client_id, tx) => {
-                            if let Some(client) = self.clients.get_mut(&client_id) {
-                                println!("created");
-                                client.publish_tx = Some(tx)
-                            }
+    /// Handles backend messages until the queue is empty or the frame budget
+    /// is spent, a backlog left over is picked up by the next frame
+    fn handle_backend_msg(&mut self, ctx: &Context) {
+        let start = Instant::now();
+        self.state.drained = 0;
+        while let Ok(msg) = self.back_rx.try_recv() {
+            self.state.drained += 1;
+            match msg {
+                ToFrontend::ClientMsg(client_id, msg) => {
+                    let will_of = match &msg {
+                        FromClient::Event(Event::Incoming(Packet::Publish(p))) => {
+                            self.will_owner(&client_id, p)
                         }
+                        _ => None,
+                    };
+                    if let Some(client) = self.clients.get_mut(&client_id) {
+                        client.handle_msg(msg, will_of)
                     }
-                    //  ctx.request_repaint();
                 }
-                Err(err) => {
-                    let _ = err;
+                ToFrontend::ClientCreated(client_id, tx) => {
+                    if let Some(client) = self.clients.get_mut(&client_id) {
+                        client.publish_tx = Some(tx);
+                        client.reconnect = None;
+                    }
                 }
             }
+            if start.elapsed() > DRAIN_BUDGET {
+                ctx.request_repaint();
+                break;
+            }
+        }
+    }
+
+    /// Replays and imports keep going on every client, not only the one shown
+    fn tick_clients(&mut self, ctx: &Context) {
+        let next = self.clients.values_mut().filter_map(Client::tick).min();
+        if let Some(next) = next {
+            ctx.request_repaint_after(next);
         }
     }
+
+    /// Finds the other profile whose last will `publish` is
+    fn will_owner(&self, receiver: &str, publish: &Publish) -> Option<ClientId> {
+        self.clients
+            .iter()
+            .filter(|(id, _)| id.as_str() != receiver)
+            .find(|(_, c)| matches!(c.options.last_will(), Some(w) if w.matches(publish)))
+            .map(|(id, _)| id.clone())
+    }
 }
 
 // ui
@@ -224,6 +269,26 @@ impl MqttAppUI {
             })
     }
 
+    fn render_status_bar(&mut self, ctx: &Context) {
+        TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let backlog = self.back_rx.len();
+                let color = if backlog > 0 {
+                    Color32::YELLOW
+                } else {
+                    THEME.colors.lighter_gray
+                };
+                ui.colored_label(color, format!("backlog {}", backlog))
+                    .on_hover_text("backend messages waiting for the next frame");
+                ui.separator();
+                ui.colored_label(
+                    THEME.colors.lighter_gray,
+                    format!("{} handled last frame", self.state.drained),
+                );
+            });
+        });
+    }
+
     fn render_central_panel(&mut self, ctx: &Context) -> InnerResponse<()> {
         CentralPanel::default().show(ctx, |ui| {
             //  ui.set_height(ui.available_height());
@@ -238,7 +303,10 @@ impl MqttAppUI {
                     let clip_rect = ui.clip_rect();
 
                     let mut ui = Ui::new(ctx.clone(), layer_id, id, max_rect, clip_rect);
-                    docking::show(&mut ui, id, &self.style, tree, client)
+                    docking::show(&mut ui, id, &self.style, tree, client);
+                    if std::mem::take(&mut client.show_plots) {
+                        tree.focus::<plot_tab::PlotTab>();
+                    }
                 }
             } else {
                 ui.centered_and_justified(|ui| {
@@ -259,6 +327,18 @@ impl MqttAppUI {
         window.show(ctx, |ui| {
             Frame::none().show(ui, |ui| {
                 menu::bar(ui, |ui| {
+                    let opts = &mut self.state.mqtt_options;
+                    let is_v5 = matches!(opts, MqttOpts::V5(_));
+                    if ui.selectable_label(!is_v5, "MQTT 3.1.1").clicked() {
+                        if let MqttOpts::V5(v5) = opts {
+                            *opts = MqttOpts::V3(v5.clone().into());
+                        }
+                    }
+                    if ui.selectable_label(is_v5, "MQTT 5").clicked() {
+                        if let MqttOpts::V3(v3) = opts {
+                            *opts = MqttOpts::V5(v3.clone().into());
+                        }
+                    }
                     ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                         let close_btn = ui.add(Button::new(
                             RichText::new("❌")
@@ -272,106 +352,54 @@ impl MqttAppUI {
                 });
                 ui.separator();
 
+                let key = self.state.mqtt_options.client_id();
                 match &mut self.state.mqtt_options {
-                    MqttOpts::V3(v3) => {
-                        let key = v3.client_id.clone();
-                        ui.horizontal(|ui| {
-                            ui.label("client_id");
-                            let client_id = TextEdit::singleline(&mut v3.client_id)
-                                .hint_text(RichText::new("client_id").color(THEME.colors.gray));
-
-                            ui.add(client_id)
-                        });
-
-                        ui.horizontal(|ui| {
-                            let addr = TextEdit::singleline(&mut v3.broker_addr).hint_text(
-                                RichText::new("broker_address").color(THEME.colors.gray),
-                            );
-                            let port_widget = DragValue::new(&mut v3.port).clamp_range(0..=65535);
-
-                            ui.label("broker_address:");
-                            ui.add(addr);
-                            ui.separator();
-                            ui.add(port_widget);
-                        });
-                        ui.separator();
-
-                        ui.horizontal(|ui| {
-                            ui.add(Checkbox::new(&mut v3.keep_alive, "keep_alive"));
-
-                            if v3.keep_alive {
-                                ui.separator();
-                                ui.add(Slider::new(&mut v3.heatbbeat, 5..=30).suffix("s"));
-                            }
-                        });
-                        ui.separator();
-                        ui.group(|ui| {
-                            if ui.selectable_label(v3.credentials, "credentials").clicked() {
-                                v3.credentials = !v3.credentials
-                            }
-                            if v3.credentials {
-                                ui.label("username");
-                                ui.add(TextEdit::singleline(&mut v3.username));
-                                ui.label("password");
-                                ui.add(TextEdit::singleline(&mut v3.password).password(true));
-                            }
-                        });
-                        ui.group(|ui| {
-                            ui.label("max packet size");
-                            ui.label("incoming");
-                            ui.add(
-                                Slider::new(&mut v3.max_packet_size.0, 5..=u16::MAX)
-                                    .suffix(" bytes"),
-                            );
-                            ui.label("outgoing");
-                            ui.add(
-                                Slider::new(&mut v3.max_packet_size.1, 5..=u16::MAX)
-                                    .suffix(" bytes"),
-                            );
-                        });
-                        ui.end_row();
-                        ui.add(Checkbox::new(&mut v3.clean_session, "clean_session"));
-                        ui.separator();
-                        ui.with_layout(Layout::left_to_right(Align::TOP), |ui| {
-                            if ui
-                                .button(
-                                    RichText::new("➖")
-                                        .text_style(TextStyle::Heading)
-                                        .color(Color32::RED),
-                                )
-                                .clicked()
-                            {
-                                self.clients.remove(&key);
-                                self.state.show_add = false;
-                                if matches!(&self.state.active_client, Some(k) if  k==&key) {
-                                    self.state.active_client = None;
-                                }
-                            }
-
-                            ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
-                                if ui
-                                    .button(
-                                        RichText::new("✅")
-                                            .text_style(TextStyle::Heading)
-                                            .color(Color32::GREEN),
-                                    )
-                                    .clicked()
-                                {
-                                    let client = client::client::create_client(
-                                        self.state.mqtt_options.clone(),
-                                        self.front_tx.clone(),
-                                    );
-                                    self.clients.insert(key.clone(), client);
-                                    self.state.show_add = false;
-                                    if self.state.active_client.is_none() {
-                                        self.state.active_client = Some(key);
-                                    }
-                                }
-                            });
-                        });
-                    }
-                    MqttOpts::V5(_v5) => todo!(),
+                    MqttOpts::V3(v3) => options::show_v3(ui, v3),
+                    MqttOpts::V5(v5) => options::show_v5(ui, v5),
                 };
+                ui.separator();
+                ui.with_layout(Layout::left_to_right(Align::TOP), |ui| {
+                    if ui
+                        .button(
+                            RichText::new("➖")
+                                .text_style(TextStyle::Heading)
+                                .color(Color32::RED),
+                        )
+                        .clicked()
+                    {
+                        self.clients.remove(&key);
+                        self.state.show_add = false;
+                        if matches!(&self.state.active_client, Some(k) if  k==&key) {
+                            self.state.active_client = None;
+                        }
+                    }
+
+                    ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                        if ui
+                            .button(
+                                RichText::new("✅")
+                                    .text_style(TextStyle::Heading)
+                                    .color(Color32::GREEN),
+                            )
+                            .clicked()
+                        {
+                            // editing a profile keeps its subscriptions and draft
+                            let mut profile = self
+                                .clients
+                                .get(&key)
+                                .map(Client::profile)
+                                .unwrap_or_default();
+                            profile.options = self.state.mqtt_options.clone();
+                            let client =
+                                client::client::create_client(profile, self.front_tx.clone());
+                            self.clients.insert(key.clone(), client);
+                            self.state.show_add = false;
+                            if self.state.active_client.is_none() {
+                                self.state.active_client = Some(key);
+                            }
+                        }
+                    });
+                });
             });
         });
     }
@@ -199,6 +199,18 @@ impl<Context> Tree<Context> {
         })
     }
 
+    /// Makes the first tab of type `T` the active one of its leaf
+    pub fn focus<T: Tab<Context> + 'static>(&mut self) {
+        for node in &mut self.tree {
+            if let Node::Leaf { tabs, active, .. } = node {
+                if let Some(index) = tabs.iter().position(|tab| tab.is::<T>()) {
+                    *active = index;
+                    return;
+                }
+            }
+        }
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.tree.len()
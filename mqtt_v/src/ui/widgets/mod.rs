@@ -1,3 +1,6 @@
+// kept as upstream wrote it, with the parts this app does not use
+#[allow(dead_code, unused_imports, clippy::manual_is_multiple_of)]
 pub mod docking;
 pub mod packet;
+pub mod packet_list;
 pub mod status_led;
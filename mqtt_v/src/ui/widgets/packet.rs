@@ -1,23 +1,105 @@
-use backend::message::{Event, Outgoing, Packet, QoS};
-use chrono::{DateTime, Local};
+use backend::message::{Delivery, Event, Outgoing, Packet, QoS};
 use eframe::{
-    egui::{self, style::Margin, Frame, Layout, RichText, Sense, Ui},
+    egui::{
+        self, style::Margin, CollapsingHeader, Frame, Id, Label, Layout, Response, RichText,
+        ScrollArea, Sense, Ui,
+    },
     emath::Align,
     epaint::{Color32, Rounding, Stroke, Vec2},
 };
+use serde_json::Value;
 
-use crate::ui::client::client::{ClientPacket, PacketData, Subcribe};
+use crate::ui::{
+    client::{
+        client::{delivery_label, reconnect_label, Client, ClientPacket, PacketData},
+        plot::{numbers, pointer, Plot},
+    },
+    decoder::{Decoded, PayloadDecoder},
+    THEME,
+};
 
-pub struct PacketUI {
-    event: Event,
-}
+/// characters of payload shown on a collapsed row
+const PREVIEW_LEN: usize = 160;
+
+pub struct PacketUI;
 
 impl PacketUI {
-    pub fn new(pkt: Event) -> Self {
-        PacketUI { event: pkt }
+    /// One line summary of `pkt` filling `height`, click it to expand the packet
+    pub fn show_line(ui: &mut Ui, pkt: &ClientPacket, client: &Client, height: f32) -> Response {
+        let size = Vec2::new(ui.available_width(), height);
+        let (rect, response) = ui.allocate_exact_size(size, Sense::click());
+        if !ui.is_rect_visible(rect) {
+            return response;
+        }
+        if response.hovered() {
+            ui.painter()
+                .rect_filled(rect, 2.0, ui.visuals().widgets.hovered.bg_fill);
+        }
+
+        let mut ui = ui.child_ui(rect, Layout::left_to_right(Align::Center));
+        ui.set_clip_rect(rect.intersect(ui.clip_rect()));
+        ui.spacing_mut().item_spacing.x = 6.0;
+        ui.label(
+            RichText::new(pkt.time.format("%H:%M:%S%.3f").to_string())
+                .color(THEME.colors.lighter_gray)
+                .monospace(),
+        );
+
+        match &pkt.data {
+            PacketData::Event(Event::Incoming(Packet::Publish(p))) => {
+                ui.colored_label(Color32::LIGHT_GREEN, "⬊");
+                if let Some(sub) = client.subscriptions.iter().find(|s| s.matches(&p.topic)) {
+                    let (bar, _) = ui.allocate_exact_size(Vec2::new(4.0, 14.0), Sense::hover());
+                    ui.painter().rect_filled(bar, 1.0, sub.color);
+                }
+                publish_line(&mut ui, &p.topic, p.qos, p.retain);
+                if let Some(owner) = &pkt.will_of {
+                    ui.colored_label(Color32::LIGHT_RED, "⚠ will")
+                        .on_hover_text(format!("last will of {}", owner));
+                }
+                ui.add(Label::new(preview(&p.payload, Color32::LIGHT_GREEN)).wrap(false));
+            }
+            PacketData::Event(Event::Incoming(packet)) => {
+                ui.colored_label(Color32::LIGHT_GREEN, "⬊");
+                ui.colored_label(THEME.colors.lighter_gray, packet_name(packet));
+            }
+            PacketData::Event(Event::Outgoing(outgoing)) => {
+                ui.colored_label(Color32::LIGHT_BLUE, "⬈");
+                ui.colored_label(THEME.colors.lighter_gray, format!("{:?}", outgoing));
+            }
+            PacketData::PublishPacket(id, p) => {
+                ui.colored_label(Color32::GREEN, "⬈");
+                publish_line(&mut ui, &p.topic, p.qos, p.retain);
+                if let Some(delivery) = client.deliveries.get(id) {
+                    let (text, color) = delivery_label(delivery);
+                    ui.colored_label(color, text);
+                }
+                ui.add(Label::new(preview(&p.payload, Color32::LIGHT_BLUE)).wrap(false));
+            }
+            PacketData::Reconnect(status) => {
+                let (text, color) = reconnect_label(status);
+                ui.colored_label(color, format!("↻ {}", text));
+            }
+            PacketData::Error(error) => {
+                ui.colored_label(Color32::LIGHT_RED, format!("⚠ {}", error));
+            }
+            PacketData::Dropped(count) => {
+                ui.colored_label(Color32::YELLOW, format!("⚠ {} events dropped", count));
+            }
+        }
+        response
     }
 
-    pub fn show(ui: &mut Ui, pkt: &ClientPacket, subs: &Vec<Subcribe>) {
+    /// `decoder` is the payload decoder picked for this packet, `None`
+    /// follows the rules of the client. Returns the plot of a payload field
+    /// when one was picked from its context menu
+    pub fn show(
+        ui: &mut Ui,
+        pkt: &ClientPacket,
+        client: &Client,
+        decoder: &mut Option<String>,
+    ) -> Option<Plot> {
+        let mut plot = None;
         ui.horizontal(|ui| {
             ui.set_width(ui.available_width());
             match &pkt.data {
@@ -26,7 +108,7 @@ impl PacketUI {
                         let layout = Layout::left_to_right(Align::Center);
                         ui.with_layout(layout, |ui| {
                             ui.set_width(ui.available_width());
-                            render_incomming(ui, incoming, &pkt.time, subs);
+                            plot = render_incomming(ui, incoming, pkt, client, decoder);
                         });
                     }
                     Event::Outgoing(outgoing) => {
@@ -37,7 +119,47 @@ impl PacketUI {
                         });
                     }
                 },
-                PacketData::PublishPacket(p) => {
+                PacketData::Reconnect(status) => {
+                    ui.with_layout(Layout::top_down(Align::Center), |ui| {
+                        let (text, color) = reconnect_label(status);
+                        ui.label(
+                            RichText::new(format!("{}  {}", pkt.time.format("%H:%M:%S"), text))
+                                .color(color)
+                                .small(),
+                        );
+                    });
+                }
+                PacketData::Dropped(count) => {
+                    ui.with_layout(Layout::top_down(Align::Center), |ui| {
+                        let text = format!(
+                            "{}  {} events dropped, the UI fell behind",
+                            pkt.time.format("%H:%M:%S"),
+                            count
+                        );
+                        ui.label(RichText::new(text).color(Color32::YELLOW).small());
+                    });
+                }
+                PacketData::Error(error) => {
+                    ui.with_layout(Layout::top_down(Align::Center), |ui| {
+                        Frame {
+                            fill: Color32::BLACK,
+                            inner_margin: Margin::same(6.0),
+                            rounding: Rounding::same(6.0),
+                            stroke: Stroke::new(1.0, Color32::LIGHT_RED),
+                            ..Frame::default()
+                        }
+                        .show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.colored_label(Color32::LIGHT_RED, "⚠");
+                                ui.colored_label(Color32::LIGHT_RED, error.to_string());
+                                ui.label(
+                                    RichText::new(pkt.time.format("%H:%M:%S").to_string()).small(),
+                                );
+                            });
+                        });
+                    });
+                }
+                PacketData::PublishPacket(id, p) => {
                     let layout = Layout::right_to_left(Align::Center);
                     ui.with_layout(layout, |ui| {
                         ui.set_width(ui.available_width());
@@ -61,8 +183,7 @@ impl PacketUI {
 
                                     ui.scope(|ui| {
                                         ui.spacing_mut().item_spacing.x = 1.0;
-                                        let mut i = 0;
-                                        for x in p.topic.split('/') {
+                                        for (i, x) in p.topic.split('/').enumerate() {
                                             if i == 0 {
                                                 if !x.is_empty() {
                                                     ui.label(
@@ -73,7 +194,6 @@ impl PacketUI {
                                                 ui.label(RichText::new("/").color(Color32::WHITE));
                                                 ui.label(RichText::new(x).color(Color32::KHAKI));
                                             }
-                                            i += 1;
                                         }
                                     });
 
@@ -89,29 +209,27 @@ impl PacketUI {
                                     })
                                 });
                                 ui.add_space(2.0);
-                                if let Ok(mut x) = String::from_utf8(p.payload.to_vec()) {
-                                    ui.add(
-                                        egui::TextEdit::multiline(&mut x)
-                                            .font(egui::TextStyle::Monospace) // for cursor height
-                                            .code_editor()
-                                            .text_color(Color32::LIGHT_BLUE)
-                                            .lock_focus(true)
-                                            .desired_width(f32::INFINITY), // .layouter(&mut layouter),
-                                    );
-                                } else {
-                                    let mut t = format!("{:x}", p.payload);
-                                    ui.add(
-                                        egui::TextEdit::multiline(&mut t)
-                                            .font(egui::TextStyle::Monospace) // for cursor height
-                                            .code_editor()
-                                            // .desired_rows(10)
-                                            .lock_focus(true)
-                                            .desired_width(f32::INFINITY), // .layouter(&mut layouter),
-                                    );
-                                }
+                                let payload_decoder = client.decoders.choose(decoder, &p.topic);
+                                let tree_id = ui.make_persistent_id(("payload", pkt.time));
+                                plot = payload_view(
+                                    ui,
+                                    tree_id,
+                                    payload_decoder,
+                                    &p.payload,
+                                    Color32::LIGHT_BLUE,
+                                )
+                                .map(|path| plot_of(client, &p.topic, path));
                                 ui.add_space(2.0);
                                 ui.horizontal(|ui| {
-                                    ui.label(format!("{}", pkt.time.format("%Y-%m-%d %H:%M")))
+                                    ui.label(format!("{}", pkt.time.format("%Y-%m-%d %H:%M")));
+                                    decoder_menu(ui, client, payload_decoder.name(), decoder);
+                                    if let Some(delivery) = client.deliveries.get(id) {
+                                        let (text, color) = delivery_label(delivery);
+                                        let label = ui.colored_label(color, text);
+                                        if let Delivery::Failed(reason) = delivery {
+                                            label.on_hover_text(reason);
+                                        }
+                                    }
                                 })
                             });
                         });
@@ -120,10 +238,30 @@ impl PacketUI {
             }
         });
         ui.add_space(4.0);
+        plot
     }
 }
 
-fn render_incomming(ui: &mut Ui, packet: &Packet, time: &DateTime<Local>, subs: &Vec<Subcribe>) {
+/// Plot of the field at `path` following the subscription `topic` came
+/// through, so that sibling topics are plotted too
+fn plot_of(client: &Client, topic: &str, path: String) -> Plot {
+    let filter = client
+        .subscriptions
+        .iter()
+        .find(|sub| sub.matches(topic))
+        .map_or_else(|| topic.to_owned(), |sub| sub.topic.clone());
+    Plot::new(filter, path)
+}
+
+fn render_incomming(
+    ui: &mut Ui,
+    packet: &Packet,
+    pkt: &ClientPacket,
+    client: &Client,
+    decoder: &mut Option<String>,
+) -> Option<Plot> {
+    let mut plot = None;
+    let subs = &client.subscriptions;
     match packet {
         // Packet::Connect(_) => {}
         // Packet::ConnAck(_) => {}
@@ -156,7 +294,7 @@ fn render_incomming(ui: &mut Ui, packet: &Packet, time: &DateTime<Local>, subs:
                                         rect,
                                         ui.style().visuals.noninteractive().rounding,
                                         *color,
-                                        Stroke::none(),
+                                        Stroke::NONE,
                                     );
                                     resp.on_hover_text(topic);
                                 }
@@ -173,8 +311,7 @@ fn render_incomming(ui: &mut Ui, packet: &Packet, time: &DateTime<Local>, subs:
                         let response = ui
                             .scope(|ui| {
                                 ui.spacing_mut().item_spacing.x = 1.0;
-                                let mut i = 0;
-                                for x in p.topic.split('/') {
+                                for (i, x) in p.topic.split('/').enumerate() {
                                     if i == 0 {
                                         if !x.is_empty() {
                                             ui.label(RichText::new(x).color(Color32::KHAKI));
@@ -183,7 +320,6 @@ fn render_incomming(ui: &mut Ui, packet: &Packet, time: &DateTime<Local>, subs:
                                         ui.label(RichText::new("/").color(Color32::WHITE));
                                         ui.label(RichText::new(x).color(Color32::KHAKI));
                                     }
-                                    i += 1;
                                 }
                             })
                             .response
@@ -202,31 +338,22 @@ fn render_incomming(ui: &mut Ui, packet: &Packet, time: &DateTime<Local>, subs:
                                     QoS::ExactlyOnce => "2",
                                 },
                             );
+                            if let Some(owner) = &pkt.will_of {
+                                ui.label(RichText::new("⚠ will").color(Color32::LIGHT_RED))
+                                    .on_hover_text(format!("last will of {}", owner));
+                            }
                         })
                     });
                     ui.add_space(2.0);
-                    if let Ok(mut x) = String::from_utf8(p.payload.to_vec()) {
-                        ui.add(
-                            egui::TextEdit::multiline(&mut x)
-                                .font(egui::TextStyle::Monospace) // for cursor height
-                                .code_editor()
-                                .text_color(Color32::LIGHT_GREEN)
-                                .lock_focus(true)
-                                .desired_width(f32::INFINITY), // .layouter(&mut layouter),
-                        );
-                    } else {
-                        let mut t = format!("{:x}", p.payload);
-                        ui.add(
-                            egui::TextEdit::multiline(&mut t)
-                                .font(egui::TextStyle::Monospace) // for cursor height
-                                .code_editor()
-                                // .desired_rows(10)
-                                .lock_focus(true)
-                                .desired_width(f32::INFINITY), // .layouter(&mut layouter),
-                        );
-                    }
+                    let payload_decoder = client.decoders.choose(decoder, &p.topic);
+                    let id = ui.make_persistent_id(("payload", pkt.time));
+                    plot = payload_view(ui, id, payload_decoder, &p.payload, Color32::LIGHT_GREEN)
+                        .map(|path| plot_of(client, &p.topic, path));
                     ui.add_space(2.0);
-                    ui.horizontal(|ui| ui.label(format!("{}", time.format("%Y-%m-%d %H:%M"))))
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}", pkt.time.format("%Y-%m-%d %H:%M")));
+                        decoder_menu(ui, client, payload_decoder.name(), decoder);
+                    })
                 });
             });
 
@@ -283,6 +410,7 @@ fn render_incomming(ui: &mut Ui, packet: &Packet, time: &DateTime<Local>, subs:
             });
         }
     }
+    plot
 }
 
 fn render_outgoing(ui: &mut Ui, outgoing: &Outgoing) {
@@ -346,3 +474,216 @@ fn render_outgoing(ui: &mut Ui, outgoing: &Outgoing) {
         }
     }
 }
+
+/// The payload decoded by `decoder`, selectable but read only, `id` keeps
+/// which fields of a tree are open
+fn payload_view(
+    ui: &mut Ui,
+    id: Id,
+    decoder: &dyn PayloadDecoder,
+    payload: &[u8],
+    color: Color32,
+) -> Option<String> {
+    let mut plot = None;
+    let decoded = match decoder.decode(payload) {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            ui.colored_label(
+                Color32::LIGHT_RED,
+                format!("⚠ not {}: {}", decoder.name(), e),
+            );
+            Decoded::Text(String::from_utf8_lossy(payload).into_owned())
+        }
+    };
+    let response = match decoded {
+        Decoded::Text(text) => ui.add(
+            egui::TextEdit::multiline(&mut text.as_str())
+                .font(egui::TextStyle::Monospace) // for cursor height
+                .code_editor()
+                .text_color(color)
+                .desired_width(f32::INFINITY),
+        ),
+        Decoded::Highlighted(job) => {
+            let mut layouter = |ui: &Ui, _: &str, wrap_width: f32| {
+                let mut job = job.clone();
+                let font = egui::TextStyle::Monospace.resolve(ui.style());
+                for section in &mut job.sections {
+                    section.format.font_id = font.clone();
+                }
+                job.wrap.max_width = wrap_width;
+                ui.fonts().layout_job(job)
+            };
+            ui.add(
+                egui::TextEdit::multiline(&mut job.text.as_str())
+                    .font(egui::TextStyle::Monospace) // for cursor height
+                    .code_editor()
+                    .desired_width(f32::INFINITY)
+                    .layouter(&mut layouter),
+            )
+        }
+        Decoded::Tree(value) => {
+            value_tree(ui, id, None, "", &value, &mut plot);
+            return plot;
+        }
+    };
+    // text has no fields to click, every number of the payload is offered
+    response.context_menu(|ui| {
+        let numbers = decoder.value(payload).map(|tree| numbers(&tree));
+        match numbers {
+            Some(numbers) if !numbers.is_empty() => {
+                for (path, value) in numbers {
+                    if ui.button(format!("📈 plot {} = {}", path, value)).clicked() {
+                        plot = Some(path);
+                        ui.close_menu();
+                    }
+                }
+            }
+            _ => {
+                ui.label("no numbers to plot");
+            }
+        }
+    });
+    plot
+}
+
+/// A field of a decoded payload at the JSON pointer `path`, objects and
+/// arrays collapse, right-click a number to plot it
+fn value_tree(
+    ui: &mut Ui,
+    id: Id,
+    key: Option<&str>,
+    path: &str,
+    value: &Value,
+    plot: &mut Option<String>,
+) {
+    let key = key.map_or(String::new(), |key| format!("{}: ", key));
+    let children: Vec<(String, &Value)> = match value {
+        Value::Object(fields) if !fields.is_empty() => {
+            fields.iter().map(|(k, v)| (k.clone(), v)).collect()
+        }
+        Value::Array(items) if !items.is_empty() => items
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (i.to_string(), v))
+            .collect(),
+        _ => {
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = 0.0;
+                ui.label(RichText::new(key).color(Color32::KHAKI).monospace());
+                let label = ui.add(Label::new(leaf(value)).sense(Sense::click()));
+                if value.is_number() {
+                    label.context_menu(|ui| {
+                        if ui.button("📈 plot").clicked() {
+                            *plot = Some(path.to_owned());
+                            ui.close_menu();
+                        }
+                    });
+                }
+            });
+            return;
+        }
+    };
+    let summary = match value {
+        Value::Array(_) => format!("[{}]", children.len()),
+        _ => format!("{{{}}}", children.len()),
+    };
+    CollapsingHeader::new(
+        RichText::new(format!("{}{}", key, summary))
+            .color(Color32::KHAKI)
+            .monospace(),
+    )
+    .id_source(id)
+    .default_open(true)
+    .show(ui, |ui| {
+        for (key, child) in children {
+            let path = pointer(path, &key);
+            value_tree(ui, id.with(&key), Some(&key), &path, child, plot);
+        }
+    });
+}
+
+fn leaf(value: &Value) -> RichText {
+    let (text, color) = match value {
+        Value::String(s) => (format!("{:?}", s), Color32::LIGHT_GREEN),
+        Value::Number(n) => (n.to_string(), Color32::from_rgb(128, 140, 255)),
+        Value::Array(_) => ("[]".to_owned(), Color32::GRAY),
+        Value::Object(_) => ("{}".to_owned(), Color32::GRAY),
+        other => (other.to_string(), Color32::GOLD),
+    };
+    RichText::new(text).color(color).monospace()
+}
+
+/// Picks the decoder of a single packet, `current` is the one in use
+fn decoder_menu(ui: &mut Ui, client: &Client, current: &str, decoder: &mut Option<String>) {
+    ui.menu_button(RichText::new(format!("{} ⏷", current)).small(), |ui| {
+        if ui
+            .selectable_label(decoder.is_none(), "topic default")
+            .clicked()
+        {
+            *decoder = None;
+            ui.close_menu();
+        }
+        ui.separator();
+        ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+            for name in client.decoders.names() {
+                if ui
+                    .selectable_label(decoder.as_deref() == Some(name), name)
+                    .clicked()
+                {
+                    *decoder = Some(name.to_owned());
+                    ui.close_menu();
+                }
+            }
+        });
+    })
+    .response
+    .on_hover_text("payload decoder");
+}
+
+fn publish_line(ui: &mut Ui, topic: &str, qos: QoS, retain: bool) {
+    ui.label(RichText::new(topic).color(Color32::KHAKI));
+    ui.colored_label(Color32::from_rgb(128, 140, 255), (qos as u8).to_string());
+    if retain {
+        ui.colored_label(Color32::GOLD, "R")
+            .on_hover_text("retained");
+    }
+}
+
+/// Start of the payload on a single line, binary payloads as hex
+pub(crate) fn preview(payload: &[u8], color: Color32) -> RichText {
+    let text = match std::str::from_utf8(payload) {
+        Ok(text) => {
+            let mut line: String = text.chars().take(PREVIEW_LEN).collect();
+            line.retain(|c| c != '\r');
+            line.replace('\n', "⏎")
+        }
+        Err(_) => {
+            let hex: String = payload
+                .iter()
+                .take(PREVIEW_LEN / 3)
+                .map(|b| format!("{:02x} ", b))
+                .collect();
+            format!("{}({} bytes)", hex, payload.len())
+        }
+    };
+    RichText::new(text).color(color).monospace()
+}
+
+fn packet_name(packet: &Packet) -> String {
+    match packet {
+        Packet::Connect(_) => "Connect".to_owned(),
+        Packet::ConnAck(ack) => format!("ConnAck {:?}", ack.code),
+        Packet::Publish(p) => format!("Publish #{}", p.pkid),
+        Packet::PubAck(ack) => format!("PubAck #{}", ack.pkid),
+        Packet::PubRec(rec) => format!("PubRec #{}", rec.pkid),
+        Packet::PubRel(rel) => format!("PubRel #{}", rel.pkid),
+        Packet::PubComp(comp) => format!("PubComp #{}", comp.pkid),
+        Packet::Subscribe(sub) => format!("Subscribe #{}", sub.pkid),
+        Packet::SubAck(ack) => format!("SubAck #{}", ack.pkid),
+        Packet::Unsubscribe(unsub) => format!("Unsubscribe #{}", unsub.pkid),
+        Packet::UnsubAck(ack) => format!("UnsubAck #{}", ack.pkid),
+        Packet::PingReq => "PingReq".to_owned(),
+        Packet::PingResp => "PingResp".to_owned(),
+        Packet::Disconnect => "Disconnect".to_owned(),
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use eframe::{
+    egui::{ScrollArea, Ui},
+    emath::Rect,
+};
+
+use crate::ui::client::{client::Client, plot::Plot};
+
+use super::packet::PacketUI;
+
+/// The event history of a client, only the rows in view are laid out.
+///
+/// Rows are one line until clicked, the measured height of the expanded ones
+/// is kept by the sequence number of their packet so it survives eviction.
+#[derive(Default)]
+pub struct PacketList {
+    expanded: BTreeMap<u64, f32>,
+    /// payload decoders picked for single packets
+    decoders: BTreeMap<u64, String>,
+}
+
+impl PacketList {
+    /// `rows` are the sequence numbers of the packets to show, ascending.
+    /// Returns the plot picked from the payload of an expanded row
+    pub fn show(&mut self, ui: &mut Ui, client: &Client, rows: &VecDeque<u64>) -> Option<Plot> {
+        let mut plot = None;
+        let store = &client.packets;
+        self.expanded = self.expanded.split_off(&store.seq(0));
+        self.decoders = self.decoders.split_off(&store.seq(0));
+
+        let spacing = ui.spacing().item_spacing.y;
+        let line_height = ui.spacing().interact_size.y;
+        let line = line_height + spacing;
+        let total = rows.len();
+        let extra: f32 = self
+            .expanded
+            .iter()
+            .filter(|(seq, _)| rows.binary_search(seq).is_ok())
+            .map(|(_, h)| h - line)
+            .sum();
+
+        ScrollArea::vertical()
+            .stick_to_bottom(true)
+            .auto_shrink([false; 2])
+            .show_viewport(ui, |ui, viewport| {
+                ui.set_height((line * total as f32 + extra - spacing).max(0.0));
+
+                let (start, mut y) = self.row_at(viewport.min.y, line, rows);
+                let top = ui.max_rect().top();
+                let rect = Rect::from_x_y_ranges(
+                    ui.max_rect().x_range(),
+                    top + y..=ui.max_rect().bottom().max(top + y),
+                );
+
+                ui.allocate_ui_at_rect(rect, |ui| {
+                    ui.skip_ahead_auto_ids(start);
+                    for &seq in rows.range(start.min(total)..) {
+                        if y > viewport.max.y {
+                            break;
+                        }
+                        let Some(pkt) = store.index_of(seq).and_then(|i| store.get(i)) else {
+                            continue;
+                        };
+                        let open = self.expanded.contains_key(&seq);
+
+                        let row_top = ui.cursor().top();
+                        let clicked = PacketUI::show_line(ui, pkt, client, line_height).clicked();
+                        if open {
+                            let mut decoder = self.decoders.remove(&seq);
+                            if let Some(picked) = PacketUI::show(ui, pkt, client, &mut decoder) {
+                                plot = Some(picked);
+                            }
+                            if let Some(decoder) = decoder {
+                                self.decoders.insert(seq, decoder);
+                            }
+                        }
+                        let height = ui.cursor().top() - row_top;
+                        y += height;
+
+                        if clicked && open {
+                            self.expanded.remove(&seq);
+                        } else if clicked || open {
+                            self.expanded.insert(seq, height);
+                        }
+                    }
+                });
+            });
+        plot
+    }
+
+    /// First row at or below `y` and where it starts, collapsed rows are
+    /// `line` high and only the expanded ones need walking
+    fn row_at(&self, y: f32, line: f32, rows: &VecDeque<u64>) -> (usize, f32) {
+        let mut extra = 0.0;
+        for (seq, height) in &self.expanded {
+            let Ok(index) = rows.binary_search(seq) else {
+                continue;
+            };
+            let row_y = index as f32 * line + extra;
+            if y < row_y {
+                break;
+            }
+            if y < row_y + height {
+                return (index, row_y);
+            }
+            extra += height - line;
+        }
+        let index = ((y - extra) / line).floor().max(0.0) as usize;
+        (index, index as f32 * line + extra)
+    }
+}
@@ -1,17 +1,37 @@
+use backend::message::ConnectionState;
 use eframe::egui::{Response, Sense, Ui, Widget};
 use eframe::emath::{lerp, Vec2};
 use eframe::epaint::{Color32, Rgba, Stroke};
 
 pub struct StatusLed {
-    value: bool,
+    color: Color32,
+    /// blink while a transition is in progress
+    pulse: bool,
+    tooltip: String,
     size: f32,
     animated: bool,
 }
 
 impl StatusLed {
-    pub fn new(value: &bool) -> Self {
+    pub fn new(state: &ConnectionState) -> Self {
+        let (color, pulse, tooltip) = match state {
+            ConnectionState::Idle => (Color32::DARK_GRAY, false, "idle".to_owned()),
+            ConnectionState::Connecting => (Color32::YELLOW, true, "connecting".to_owned()),
+            ConnectionState::Connected => (Color32::GREEN, false, "connected".to_owned()),
+            ConnectionState::Reconnecting(attempt) => (
+                Color32::from_rgb(255, 140, 0),
+                true,
+                format!("reconnecting, attempt {}", attempt),
+            ),
+            ConnectionState::Disconnecting => {
+                (Color32::LIGHT_GRAY, true, "disconnecting".to_owned())
+            }
+            ConnectionState::Failed(error) => (Color32::RED, false, error.to_string()),
+        };
         Self {
-            value: *value,
+            color,
+            pulse,
+            tooltip,
             size: 20.0,
             animated: true,
         }
@@ -25,31 +45,44 @@ impl Widget for StatusLed {
         let (rect, response) = ui.allocate_exact_size(desired_size, Sense::hover());
 
         if ui.is_rect_visible(rect) {
-            let end = if self.value { 1.0 } else { 0.0 };
-            let v = if self.animated {
-                ui.ctx().animate_value_with_time(response.id, end, 0.2)
+            let v = if self.animated && self.pulse {
+                ui.ctx().request_repaint();
+                let time = ui.input().time as f32;
+                0.6 + 0.4 * (time * 6.0).sin()
             } else {
-                end
+                1.0
+            };
+            let color = if self.animated {
+                animate_color(ui, response.id, self.color)
+            } else {
+                self.color
             };
 
             ui.painter().rect(
                 rect,
                 ui.style().visuals.noninteractive().rounding,
                 Color32::TRANSPARENT,
-                Stroke::none(),
+                Stroke::NONE,
             );
 
             ui.painter().circle(
                 rect.center(),
                 5.0,
-                Color32::from(lerp(
-                    Rgba::from(Color32::BLACK)..=Rgba::from(Color32::GREEN),
-                    v,
-                )),
-                Stroke::none(),
+                Color32::from(lerp(Rgba::from(Color32::BLACK)..=Rgba::from(color), v)),
+                Stroke::NONE,
             );
         }
 
-        response
+        response.on_hover_text(self.tooltip)
     }
 }
+
+/// Fades between state colors instead of switching abruptly
+fn animate_color(ui: &Ui, id: eframe::egui::Id, target: Color32) -> Color32 {
+    let [r, g, b, _] = target.to_array();
+    let channel = |i: usize, value: u8| {
+        ui.ctx()
+            .animate_value_with_time(id.with(i), value as f32, 0.2) as u8
+    };
+    Color32::from_rgb(channel(0, r), channel(1, g), channel(2, b))
+}